@@ -6,10 +6,14 @@ use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
 use std::path::{Path, PathBuf};
 use std::{fs, io::Read as _};
 
+use crate::ant::folder_scan::SymlinkPolicy;
+use crate::ant::network::{EvmNetworkConfig, NetworkConfig, NetworkKind};
+use crate::ant::vault_registry::{VaultIdentity, DEFAULT_VAULT_ID};
 use autonomi::Multiaddr;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use thiserror::Error as ThisError;
-use tracing::info;
+use tracing::{error, info};
 
 const QUALIFIER: &str = "com";
 const ORGANIZATION: &str = "autonomi";
@@ -45,8 +49,29 @@ pub enum StoreError {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppData {
     pub download_path: Option<PathBuf>,
+    /// Bootstrap peers of the selected network.
     pub peers: Option<Vec<Multiaddr>>,
+    pub network: Option<NetworkKind>,
+    /// Only used for testnets and custom networks.
+    pub network_id: Option<u8>,
+    pub evm_network: Option<EvmNetworkConfig>,
     pub use_paymaster: Option<bool>,
+    pub vaults: Option<Vec<VaultIdentity>>,
+    pub active_vault: Option<String>,
+    /// Log directories older than this are deleted on startup.
+    pub log_retention_days: Option<u64>,
+    /// Older log directories are deleted on startup once all logs exceed this size.
+    pub log_retention_max_mb: Option<u64>,
+    /// Log filter directives, e.g. `info,autonomi=debug`.
+    pub log_level: Option<String>,
+    /// `.gitignore`-style patterns skipped in every folder upload, defaults to
+    /// [`DEFAULT_IGNORE_PATTERNS`](crate::ant::ignore_rules::DEFAULT_IGNORE_PATTERNS).
+    pub ignore_patterns: Option<Vec<String>>,
+    /// How folder uploads treat symbolic links, defaults to following them.
+    pub symlink_policy: Option<SymlinkPolicy>,
+    /// Size limit of the on-disk chunk cache in MB, defaults to
+    /// [`DEFAULT_MAX_SIZE_MB`](crate::ant::chunk_cache::DEFAULT_MAX_SIZE_MB).
+    pub chunk_cache_max_mb: Option<u64>,
 }
 
 impl Default for AppData {
@@ -55,12 +80,56 @@ impl Default for AppData {
             download_path: directories::UserDirs::new()
                 .and_then(|d| d.download_dir().map(|d| d.to_owned())),
             peers: None,
+            network: None,
+            network_id: None,
+            evm_network: None,
             use_paymaster: Some(false),
+            vaults: None,
+            active_vault: None,
+            log_retention_days: None,
+            log_retention_max_mb: None,
+            log_level: None,
+            ignore_patterns: None,
+            symlink_policy: None,
+            chunk_cache_max_mb: None,
         }
     }
 }
 
 impl AppData {
+    /// The configured vault identities, or the single wallet identity if none were configured.
+    pub fn vault_identities(&self) -> Vec<VaultIdentity> {
+        match &self.vaults {
+            Some(vaults) if !vaults.is_empty() => vaults.clone(),
+            _ => vec![VaultIdentity::default_wallet()],
+        }
+    }
+
+    pub fn active_vault_id(&self) -> String {
+        self.active_vault
+            .clone()
+            .unwrap_or_else(|| DEFAULT_VAULT_ID.to_string())
+    }
+
+    /// The network to connect to. Without a configured network, `ANT_LOCAL` selects the local
+    /// network as before.
+    pub fn network_config(&self) -> NetworkConfig {
+        let kind = self.network.unwrap_or_else(|| {
+            if std::env::var("ANT_LOCAL").is_ok() {
+                NetworkKind::Local
+            } else {
+                NetworkKind::Mainnet
+            }
+        });
+
+        NetworkConfig {
+            kind,
+            peers: self.peers.clone().unwrap_or_default(),
+            network_id: self.network_id,
+            evm_network: self.evm_network.clone(),
+        }
+    }
+
     pub fn load() -> Result<Self, LoadError> {
         let filepath = filepath().ok_or(LoadError::NoValidHome)?;
         let mut file =
@@ -162,3 +231,30 @@ pub fn data_dir() -> Option<PathBuf> {
     directories::ProjectDirs::from(QUALIFIER, ORGANIZATION, APPLICATION)
         .map(|dirs| dirs.data_dir().to_owned())
 }
+
+/// Write a file through a temporary file next to it, so a crash never leaves a truncated file
+/// behind.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> Result<(), std::io::Error> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+    fs::write(&tmp_path, contents)?;
+    fs::rename(tmp_path, path)
+}
+
+/// Load a JSON file, `None` if it doesn't exist or can't be parsed.
+pub fn load_json<T: DeserializeOwned>(path: &Path) -> Option<T> {
+    let contents = fs::read_to_string(path).ok()?;
+    serde_json::from_str(&contents)
+        .inspect_err(|err| error!("Failed to parse {}: {err}", path.display()))
+        .ok()
+}
+
+/// Save a JSON file with [`write_atomic`].
+pub fn save_json<T: Serialize + ?Sized>(path: &Path, value: &T) -> Result<(), std::io::Error> {
+    write_atomic(path, &serde_json::to_vec(value)?)
+}
@@ -0,0 +1,176 @@
+//! Persistent cache of decoded vault archives.
+//!
+//! Archives on the network are immutable, so the decoded file listing of an archive can be kept
+//! on disk indefinitely, keyed by the archive address. Listings of private archives hold the
+//! datamaps of their files, so they are only written encrypted to a key derived from the vault key.
+
+use crate::ant::app_data;
+use crate::ant::files::FileMetadata;
+use crate::ant::vault_registry::derive_vault_key;
+use autonomi::client::vault::VaultSecretKey;
+use blsttc::Ciphertext;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedArchive {
+    pub name: String,
+    pub is_private: bool,
+    pub files: Vec<FileMetadata>,
+    pub timestamp: u64,
+}
+
+/// How a listing is written to disk.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum StoredArchive {
+    Public(CachedArchive),
+    /// Hex of the listing, encrypted to the listing key of the vault it was read from
+    Private(String),
+}
+
+/// Key private listings are encrypted to, derived from the vault key.
+pub fn listing_key(vault_key: &VaultSecretKey) -> VaultSecretKey {
+    derive_vault_key(vault_key, "archive-cache")
+}
+
+pub struct ArchiveCache {
+    cache_dir: PathBuf,
+}
+
+impl ArchiveCache {
+    pub fn new(base_dir: &Path) -> Result<Self, std::io::Error> {
+        let cache_dir = base_dir.join("archive_cache");
+        fs::create_dir_all(&cache_dir)?;
+        Ok(Self { cache_dir })
+    }
+
+    /// Load the cached listing of an archive, if present and readable with the listing key.
+    pub fn load(&self, cache_key: &str, listing_key: &VaultSecretKey) -> Option<CachedArchive> {
+        let path = self.cache_dir.join(cache_key);
+        if !path.exists() {
+            return None;
+        }
+
+        match app_data::load_json(&path) {
+            Some(StoredArchive::Public(archive)) => Some(archive),
+            Some(StoredArchive::Private(ciphertext)) => {
+                let ciphertext = Ciphertext::from_bytes(&hex::decode(ciphertext).ok()?).ok()?;
+                let json = listing_key.decrypt(&ciphertext)?;
+                serde_json::from_slice(&json).ok()
+            }
+            None => {
+                // Written by an older version, which kept private listings in plaintext
+                let _ = fs::remove_file(path);
+                None
+            }
+        }
+    }
+
+    pub fn save(
+        &self,
+        cache_key: &str,
+        name: &str,
+        is_private: bool,
+        files: &[FileMetadata],
+        listing_key: &VaultSecretKey,
+    ) -> Result<(), std::io::Error> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        let cached_archive = CachedArchive {
+            name: name.to_string(),
+            is_private,
+            files: files.to_vec(),
+            timestamp,
+        };
+
+        let stored = if is_private {
+            let json = serde_json::to_vec(&cached_archive)?;
+            StoredArchive::Private(hex::encode(
+                listing_key.public_key().encrypt(json).to_bytes(),
+            ))
+        } else {
+            StoredArchive::Public(cached_archive)
+        };
+        app_data::save_json(&self.cache_dir.join(cache_key), &stored)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ant::files::{FileAccess, FileType};
+    use autonomi::data::DataAddress;
+    use autonomi::files::Metadata;
+    use autonomi::XorName;
+    use tempfile::TempDir;
+
+    fn listing() -> Vec<FileMetadata> {
+        vec![FileMetadata {
+            path: "notes/secret-plans.txt".to_string(),
+            metadata: Metadata::new_with_size(12),
+            file_type: FileType::Private,
+            is_loaded: true,
+            archive_name: "notes".to_string(),
+            access_data: Some(FileAccess::Public(DataAddress::new(XorName::from_content(
+                b"plans",
+            )))),
+        }]
+    }
+
+    #[test]
+    fn test_private_listing_is_encrypted() {
+        let temp_dir = TempDir::new().unwrap();
+        let cache = ArchiveCache::new(temp_dir.path()).unwrap();
+        let key = listing_key(&VaultSecretKey::random());
+
+        cache
+            .save("archive", "notes", true, &listing(), &key)
+            .unwrap();
+
+        let on_disk = fs::read_to_string(temp_dir.path().join("archive_cache/archive")).unwrap();
+        assert!(!on_disk.contains("secret-plans"));
+
+        let loaded = cache.load("archive", &key).unwrap();
+        assert_eq!(loaded.files[0].path, "notes/secret-plans.txt");
+
+        let other = listing_key(&VaultSecretKey::random());
+        assert!(cache.load("archive", &other).is_none());
+    }
+
+    #[test]
+    fn test_public_listing_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        let cache = ArchiveCache::new(temp_dir.path()).unwrap();
+        let key = listing_key(&VaultSecretKey::random());
+
+        cache
+            .save("archive", "notes", false, &listing(), &key)
+            .unwrap();
+
+        let other = listing_key(&VaultSecretKey::random());
+        let loaded = cache.load("archive", &other).unwrap();
+        assert_eq!(loaded.files.len(), 1);
+    }
+
+    #[test]
+    fn test_plaintext_entries_are_dropped() {
+        let temp_dir = TempDir::new().unwrap();
+        let cache = ArchiveCache::new(temp_dir.path()).unwrap();
+        let path = temp_dir.path().join("archive_cache/archive");
+        fs::write(
+            &path,
+            r#"{"name":"notes","is_private":true,"files":[],"timestamp":0}"#,
+        )
+        .unwrap();
+
+        let key = listing_key(&VaultSecretKey::random());
+        assert!(cache.load("archive", &key).is_none());
+        assert!(!path.exists());
+    }
+}
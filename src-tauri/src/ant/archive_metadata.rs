@@ -0,0 +1,421 @@
+//! Extra metadata stored with archive entries.
+//!
+//! The `extra` field of an entry's [`Metadata`] holds a JSON object, so fields added by different
+//! features can live side by side. A non-JSON value written by another client is replaced when a
+//! field is set.
+//!
+//! Archive entries also carry what is needed to restore a folder as it was uploaded: the POSIX
+//! mode of each file, and placeholder entries for empty directories. Sizes and timestamps use the
+//! regular [`Metadata`] fields.
+
+use crate::ant::file_metadata::metadata_from_path;
+use crate::ant::files::FileAccess;
+use autonomi::chunk::DataMapChunk;
+use autonomi::data::DataAddress;
+use autonomi::files::Metadata;
+use filetime::FileTime;
+use serde_json::{json, Map, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// Address of the archive version an archive was updated from.
+const PREVIOUS_VERSION: &str = "previous_version";
+
+/// Target of an entry that is a symbolic link.
+const SYMLINK: &str = "symlink";
+
+/// Permission bits of the entry, only recorded on Unix. Setuid, setgid and sticky bits are left
+/// out.
+const MODE: &str = "mode";
+
+/// Marks a placeholder entry for an empty directory.
+const DIRECTORY: &str = "directory";
+
+fn extra_fields(metadata: &Metadata) -> Map<String, Value> {
+    metadata
+        .extra
+        .as_deref()
+        .and_then(|extra| serde_json::from_str(extra).ok())
+        .unwrap_or_default()
+}
+
+pub fn extra_field(metadata: &Metadata, key: &str) -> Option<Value> {
+    extra_fields(metadata).remove(key)
+}
+
+pub fn set_extra_field(metadata: &mut Metadata, key: &str, value: Value) {
+    let mut fields = extra_fields(metadata);
+    fields.insert(key.to_string(), value);
+    metadata.extra = Some(Value::Object(fields).to_string());
+}
+
+pub fn set_symlink_target(metadata: &mut Metadata, target: &Path) {
+    set_extra_field(
+        metadata,
+        SYMLINK,
+        Value::String(target.to_string_lossy().into_owned()),
+    );
+}
+
+/// The target of an entry that was stored as a symbolic link.
+pub fn symlink_target(metadata: &Metadata) -> Option<PathBuf> {
+    extra_field(metadata, SYMLINK)?.as_str().map(PathBuf::from)
+}
+
+/// Link an entry to the archive version it was updated from.
+pub fn set_previous_version(metadata: &mut Metadata, previous: &FileAccess) {
+    let value = match previous {
+        FileAccess::Private(data_map) => json!({ "private": data_map.to_hex() }),
+        FileAccess::Public(address) => json!({ "public": address.to_hex() }),
+    };
+    set_extra_field(metadata, PREVIOUS_VERSION, value);
+}
+
+/// Drop the link to an earlier version, e.g. from an entry that is carried into a new version.
+pub fn clear_previous_version(metadata: &mut Metadata) {
+    let mut fields = extra_fields(metadata);
+    if fields.remove(PREVIOUS_VERSION).is_some() {
+        metadata.extra = Some(Value::Object(fields).to_string());
+    }
+}
+
+/// The archive version an entry links to, set on the first entry of an archive update.
+pub fn previous_version(metadata: &Metadata) -> Option<FileAccess> {
+    let previous = extra_field(metadata, PREVIOUS_VERSION)?;
+
+    if let Some(data_map) = previous["private"].as_str() {
+        DataMapChunk::from_hex(data_map)
+            .ok()
+            .map(FileAccess::Private)
+    } else {
+        DataAddress::from_hex(previous["public"].as_str()?)
+            .ok()
+            .map(FileAccess::Public)
+    }
+}
+
+/// Metadata of a file or directory on disk: size, timestamps and, on Unix, its mode.
+pub fn metadata_from_disk(path: &Path) -> std::io::Result<Metadata> {
+    let mut metadata = metadata_from_path(path)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(path)?.permissions().mode() & 0o777;
+        set_extra_field(&mut metadata, MODE, Value::from(mode));
+    }
+
+    Ok(metadata)
+}
+
+pub fn mode(metadata: &Metadata) -> Option<u32> {
+    extra_field(metadata, MODE)?
+        .as_u64()
+        .and_then(|mode| u32::try_from(mode).ok())
+}
+
+pub fn set_directory(metadata: &mut Metadata) {
+    set_extra_field(metadata, DIRECTORY, Value::Bool(true));
+}
+
+/// Whether the entry is a placeholder for an empty directory rather than a file.
+pub fn is_directory(metadata: &Metadata) -> bool {
+    extra_field(metadata, DIRECTORY).and_then(|value| value.as_bool()) == Some(true)
+}
+
+fn restore_entry(path: &Path, metadata: &Metadata) -> std::io::Result<()> {
+    // Times first, a restored mode may leave the entry read-only
+    // `File::set_modified` needs a writable handle on Windows, which can't be opened for
+    // directories, filetime opens both the way each platform needs
+    if metadata.modified != 0 {
+        let modified = FileTime::from_unix_time(metadata.modified as i64, 0);
+        filetime::set_file_mtime(path, modified)?;
+    }
+
+    #[cfg(unix)]
+    if let Some(mode) = mode(metadata) {
+        use std::os::unix::fs::PermissionsExt;
+        // Archives written by other clients may still carry setuid and similar bits
+        fs::set_permissions(path, fs::Permissions::from_mode(mode & 0o777))?;
+    }
+
+    Ok(())
+}
+
+/// Restore modification times and modes of downloaded entries.
+///
+/// Directories are restored last and deepest first, as writing into a directory changes its
+/// modification time. Links are left alone, restoring them would change their targets. Entries
+/// that can't be restored are logged and skipped.
+pub fn restore_metadata(entries: &[(PathBuf, Metadata)]) {
+    let (mut directories, files): (Vec<_>, Vec<_>) = entries
+        .iter()
+        .filter(|(path, _)| !path.is_symlink())
+        .partition(|(path, _)| path.is_dir());
+    directories.sort_by_key(|(path, _)| std::cmp::Reverse(path.components().count()));
+
+    for (path, metadata) in files.into_iter().chain(directories) {
+        if let Err(err) = restore_entry(path, metadata) {
+            warn!("Failed to restore metadata of {path:?}: {err}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ant::files::{write_archive_entries, DownloadError, SharedProgress};
+    use crate::ant::folder_scan::{placeholder_content, scan_directory, Placeholder};
+    use autonomi::chunk::Chunk;
+    use autonomi::files::PrivateArchive;
+    use autonomi::Bytes;
+    use self_encryption::{DataMap, EncryptedChunk};
+    use std::collections::HashMap;
+    use std::time::UNIX_EPOCH;
+    use tempfile::TempDir;
+
+    fn set_modified(path: &Path, secs: u64) {
+        filetime::set_file_mtime(path, FileTime::from_unix_time(secs as i64, 0)).unwrap();
+    }
+
+    fn modified_secs(path: &Path) -> u64 {
+        fs::metadata(path)
+            .unwrap()
+            .modified()
+            .unwrap()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+
+    /// Upload a folder into an archive and download it again, keeping the chunks in memory.
+    fn round_trip(folder: &Path, dest: &Path) {
+        round_trip_with_links(folder, dest, &[]).unwrap();
+    }
+
+    /// [`round_trip`] with extra link entries, as (relative path, target).
+    fn round_trip_with_links(
+        folder: &Path,
+        dest: &Path,
+        links: &[(&str, &str)],
+    ) -> Result<(), DownloadError> {
+        let scan = tauri::async_runtime::block_on(scan_directory(folder.to_path_buf())).unwrap();
+
+        let mut archive = PrivateArchive::new();
+        let mut stored: HashMap<String, Vec<EncryptedChunk>> = HashMap::new();
+        let mut store = |content: Bytes| {
+            let (data_map, chunks) = self_encryption::encrypt(content).unwrap();
+            let data_map_chunk = DataMapChunk::from(Chunk::new(Bytes::from(
+                self_encryption::serialize(&data_map).unwrap(),
+            )));
+            stored.insert(data_map_chunk.address(), chunks);
+            data_map_chunk
+        };
+
+        for (relative_path, path) in &scan.files {
+            archive.add_file(
+                relative_path.clone(),
+                store(Bytes::from(fs::read(path).unwrap())),
+                metadata_from_disk(path).unwrap(),
+            );
+        }
+        let placeholders = scan.placeholders.iter().cloned().chain(
+            links
+                .iter()
+                .map(|(path, target)| Placeholder::link(PathBuf::from(path), Path::new(target))),
+        );
+        for placeholder in placeholders {
+            archive.add_file(
+                placeholder.relative_path.clone(),
+                store(placeholder_content(&placeholder.metadata).unwrap()),
+                placeholder.metadata.clone(),
+            );
+        }
+
+        let archive = PrivateArchive::from_bytes(archive.to_bytes().unwrap()).unwrap();
+        let entries = archive
+            .map()
+            .iter()
+            .map(|(path, (data_map, metadata))| {
+                (
+                    path.clone(),
+                    FileAccess::Private(data_map.clone()),
+                    metadata.clone(),
+                )
+            })
+            .collect();
+
+        tauri::async_runtime::block_on(write_archive_entries(
+            entries,
+            dest,
+            true,
+            &SharedProgress::default(),
+            |file_access, path| {
+                let stored = &stored;
+                async move {
+                    let FileAccess::Private(data_map_chunk) = file_access else {
+                        panic!("private archives have private entries");
+                    };
+                    let data_map: DataMap =
+                        self_encryption::deserialize(&data_map_chunk.0.value).unwrap();
+                    let chunks = &stored[&data_map_chunk.address()];
+                    let content = self_encryption::decrypt(&data_map, chunks).unwrap();
+                    fs::create_dir_all(path.parent().unwrap()).unwrap();
+                    fs::write(&path, content).unwrap();
+                    Ok(path)
+                }
+            },
+        ))
+    }
+
+    #[test]
+    fn test_extra_fields_side_by_side() {
+        let mut metadata = Metadata::new_with_size(1);
+        set_directory(&mut metadata);
+        set_symlink_target(&mut metadata, Path::new("target"));
+
+        assert!(is_directory(&metadata));
+        assert_eq!(symlink_target(&metadata), Some(PathBuf::from("target")));
+        assert_eq!(mode(&metadata), None);
+    }
+
+    #[test]
+    fn test_round_trip_contents_and_times() {
+        let source = TempDir::new().unwrap();
+        let dest = TempDir::new().unwrap();
+        let folder = source.path().join("folder");
+        fs::create_dir_all(folder.join("nested")).unwrap();
+        fs::create_dir_all(folder.join("empty")).unwrap();
+
+        let large: Vec<u8> = (0..100_000u32).map(|i| (i * 31 % 251) as u8).collect();
+        fs::write(folder.join("notes.txt"), "some notes\n").unwrap();
+        fs::write(folder.join("nested/data.bin"), &large).unwrap();
+        set_modified(&folder.join("notes.txt"), 1_600_000_000);
+        set_modified(&folder.join("nested/data.bin"), 1_650_000_000);
+        // Directories can only be opened for their times on Unix
+        if cfg!(unix) {
+            set_modified(&folder.join("empty"), 1_500_000_000);
+        }
+
+        round_trip(&folder, dest.path());
+        let restored = dest.path().join("folder");
+
+        assert_eq!(
+            fs::read(restored.join("notes.txt")).unwrap(),
+            b"some notes\n"
+        );
+        assert_eq!(fs::read(restored.join("nested/data.bin")).unwrap(), large);
+        assert!(restored.join("empty").is_dir());
+        assert_eq!(fs::read_dir(restored.join("empty")).unwrap().count(), 0);
+
+        assert_eq!(modified_secs(&restored.join("notes.txt")), 1_600_000_000);
+        assert_eq!(
+            modified_secs(&restored.join("nested/data.bin")),
+            1_650_000_000
+        );
+        if cfg!(unix) {
+            assert_eq!(modified_secs(&restored.join("empty")), 1_500_000_000);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_round_trip_modes() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let source = TempDir::new().unwrap();
+        let dest = TempDir::new().unwrap();
+        let folder = source.path().join("folder");
+        fs::create_dir_all(folder.join("empty")).unwrap();
+        fs::write(folder.join("script.sh"), "#!/bin/sh\necho hi\n").unwrap();
+        fs::write(folder.join("private.txt"), "secret\n").unwrap();
+        fs::write(folder.join("setuid.sh"), "#!/bin/sh\nid\n").unwrap();
+
+        let set_mode = |path: &Path, mode| {
+            fs::set_permissions(path, fs::Permissions::from_mode(mode)).unwrap()
+        };
+        set_mode(&folder.join("script.sh"), 0o755);
+        set_mode(&folder.join("private.txt"), 0o600);
+        set_mode(&folder.join("empty"), 0o750);
+        set_mode(&folder.join("setuid.sh"), 0o4755);
+
+        round_trip(&folder, dest.path());
+        let restored = dest.path().join("folder");
+
+        let mode_of = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o7777;
+        assert_eq!(mode_of(&restored.join("script.sh")), 0o755);
+        assert_eq!(mode_of(&restored.join("setuid.sh")), 0o755);
+        assert_eq!(mode_of(&restored.join("private.txt")), 0o600);
+        assert_eq!(mode_of(&restored.join("empty")), 0o750);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_links_stay_inside_dest() {
+        let source = TempDir::new().unwrap();
+        let dest = TempDir::new().unwrap();
+        let folder = source.path().join("folder");
+        fs::create_dir_all(&folder).unwrap();
+        fs::write(folder.join("notes.txt"), "some notes\n").unwrap();
+
+        round_trip_with_links(
+            &folder,
+            dest.path(),
+            &[
+                ("folder/inside", "notes.txt"),
+                ("folder/up", ".."),
+                ("folder/absolute", "/etc/passwd"),
+                ("folder/escape", "../../outside"),
+                // Lexically inside, but `up` leads to the download folder itself
+                ("folder/chain", "up/.."),
+                ("../evil", "folder/notes.txt"),
+            ],
+        )
+        .unwrap();
+        let restored = dest.path().join("folder");
+
+        assert_eq!(
+            fs::read_link(restored.join("inside")).unwrap(),
+            PathBuf::from("notes.txt")
+        );
+        assert_eq!(
+            fs::read_to_string(restored.join("inside")).unwrap(),
+            "some notes\n"
+        );
+        assert!(restored.join("up").is_symlink());
+        for (name, target) in [
+            ("absolute", "/etc/passwd"),
+            ("escape", "../../outside"),
+            ("chain", "up/.."),
+        ] {
+            let path = restored.join(name);
+            assert!(!path.is_symlink(), "{name} is a link");
+            assert_eq!(
+                fs::read_to_string(path).unwrap(),
+                format!("symlink to {target}\n")
+            );
+        }
+        assert!(!dest.path().parent().unwrap().join("evil").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_no_writes_through_links() {
+        let source = TempDir::new().unwrap();
+        let dest = TempDir::new().unwrap();
+        let outside = TempDir::new().unwrap();
+        let folder = source.path().join("folder");
+        fs::create_dir_all(folder.join("nested")).unwrap();
+        fs::write(folder.join("nested/data.bin"), "data").unwrap();
+
+        // Left behind by an earlier download, or put there by someone else
+        fs::create_dir_all(dest.path().join("folder")).unwrap();
+        std::os::unix::fs::symlink(outside.path(), dest.path().join("folder/nested")).unwrap();
+
+        let result = round_trip_with_links(&folder, dest.path(), &[]);
+
+        assert!(matches!(result, Err(DownloadError::UnsafePath(_))));
+        assert_eq!(fs::read_dir(outside.path()).unwrap().count(), 0);
+    }
+}
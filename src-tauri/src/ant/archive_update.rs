@@ -0,0 +1,797 @@
+//! Incremental archive updates.
+//!
+//! The previous version of an archive is loaded by address and diffed against the local folder by
+//! relative path and content. Only new or changed files are encrypted, quoted and uploaded,
+//! unchanged entries keep the datamaps of the previous version. The new archive links back to the
+//! version it was updated from through the metadata of its first entry, see [`archive_metadata`].
+
+use crate::ant::archive_metadata;
+use crate::ant::client::SharedClient;
+use crate::ant::encryption::encrypt_file_or_folder;
+use crate::ant::files::{collect_files_from_directory, FileAccess, UploadError, UploadProgress};
+use crate::ant::stream::content_addresses_from_encryption_stream;
+use crate::ant::upload::batch_upload_encryption_stream;
+use crate::ant::vault_registry::SessionKey;
+use crate::ant::{local_storage, vault};
+use autonomi::chunk::{Chunk, DataMapChunk};
+use autonomi::client::graph::GraphError;
+use autonomi::client::payment::Receipt;
+use autonomi::client::quote::DataTypes;
+use autonomi::client::vault::user_data::UserDataVaultError;
+use autonomi::client::vault::{UserData, VaultError, VaultSecretKey};
+use autonomi::client::GetError;
+use autonomi::data::DataAddress;
+use autonomi::files::{Metadata, PrivateArchive, PublicArchive};
+use autonomi::{Amount, Client};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use tauri::{AppHandle, Emitter, State};
+use tracing::{error, info, warn};
+
+/// Versions followed at most when listing the history of an archive.
+const MAX_VERSIONS: usize = 100;
+
+/// Archive entries by relative path, regardless of whether the archive is private or public.
+type Entries = BTreeMap<PathBuf, (FileAccess, Metadata)>;
+
+pub enum UpdatedArchive {
+    Private(PrivateArchive),
+    Public(PublicArchive),
+}
+
+impl UpdatedArchive {
+    fn new(is_public: bool) -> Self {
+        if is_public {
+            Self::Public(PublicArchive::new())
+        } else {
+            Self::Private(PrivateArchive::new())
+        }
+    }
+
+    fn is_public(&self) -> bool {
+        matches!(self, Self::Public(_))
+    }
+
+    fn add_file(&mut self, path: PathBuf, access: FileAccess, metadata: Metadata) {
+        match (self, access) {
+            (Self::Private(archive), FileAccess::Private(data_map)) => {
+                archive.add_file(path, data_map, metadata)
+            }
+            (Self::Public(archive), FileAccess::Public(address)) => {
+                archive.add_file(path, address, metadata)
+            }
+            // Entries are always encrypted the same way as the archive they belong to
+            (_, access) => warn!("Skipping {path:?}, {access:?} does not match the archive"),
+        }
+    }
+
+    /// Encrypt the archive, returns its chunks and how to access it.
+    fn encrypt(&self) -> Result<(Vec<Chunk>, FileAccess), UploadError> {
+        let archive_bytes = match self {
+            Self::Private(archive) => archive.to_bytes(),
+            Self::Public(archive) => archive.to_bytes(),
+        }
+        .map_err(|err| UploadError::Encryption(err.to_string()))?;
+
+        let (archive_datamap, mut archive_chunks) =
+            autonomi::self_encryption::encrypt(archive_bytes)
+                .map_err(|err| UploadError::Encryption(err.to_string()))?;
+        let archive_datamap_chunk = DataMapChunk::from(archive_datamap.clone());
+
+        match self {
+            Self::Private(_) => Ok((archive_chunks, FileAccess::Private(archive_datamap_chunk))),
+            Self::Public(_) => {
+                // The public archive's address is the archive datamap's address
+                archive_chunks.push(archive_datamap);
+                Ok((
+                    archive_chunks,
+                    FileAccess::Public(DataAddress::new(*archive_datamap_chunk.0.name())),
+                ))
+            }
+        }
+    }
+}
+
+/// How the files of a folder relate to the entries of the previous archive version.
+#[derive(Debug, Default)]
+struct Diff {
+    /// Entries carried over as they are.
+    unchanged: Entries,
+    /// Files that have to be encrypted to tell whether their content changed, by relative path,
+    /// with their absolute path and metadata.
+    touched: Vec<(PathBuf, PathBuf, Metadata)>,
+    /// Entries of the previous version whose file is gone.
+    removed: usize,
+}
+
+/// Whether a file still matches its previous entry by size, modification time and mode.
+///
+/// Entries without a modification time can't be trusted. Entries published before modes were
+/// recorded match any mode.
+fn matches_entry(previous: &Metadata, current: &Metadata) -> bool {
+    previous.modified != 0
+        && previous.size == current.size
+        && previous.modified == current.modified
+        && archive_metadata::mode(previous)
+            .is_none_or(|mode| archive_metadata::mode(current) == Some(mode))
+}
+
+/// Diff the files of a folder, by relative path, against the entries of the previous version.
+fn diff(previous: &Entries, files: Vec<(PathBuf, PathBuf, Metadata)>) -> Diff {
+    let mut diff = Diff::default();
+
+    for (relative_path, absolute_path, metadata) in files {
+        match previous.get(&relative_path) {
+            Some((access, previous_metadata)) if matches_entry(previous_metadata, &metadata) => {
+                diff.unchanged
+                    .insert(relative_path, (access.clone(), previous_metadata.clone()));
+            }
+            _ => diff.touched.push((relative_path, absolute_path, metadata)),
+        }
+    }
+
+    diff.removed = previous
+        .keys()
+        .filter(|path| {
+            !diff.unchanged.contains_key(*path)
+                && !diff.touched.iter().any(|(touched, _, _)| touched == *path)
+        })
+        .count();
+
+    diff
+}
+
+/// Build the new archive version from its entries, linking it to the previous version.
+///
+/// Entries carried over still link to the version before, only the first entry keeps the link.
+fn build_archive(
+    entries: Entries,
+    is_public: bool,
+    previous_archive: &FileAccess,
+) -> UpdatedArchive {
+    let mut archive = UpdatedArchive::new(is_public);
+
+    for (index, (path, (access, mut metadata))) in entries.into_iter().enumerate() {
+        archive_metadata::clear_previous_version(&mut metadata);
+        if index == 0 {
+            archive_metadata::set_previous_version(&mut metadata, previous_archive);
+        }
+        archive.add_file(path, access, metadata);
+    }
+
+    archive
+}
+
+/// A new archive version waiting to be paid for.
+pub struct ArchiveUpdate {
+    pub archive_name: String,
+    pub archive: UpdatedArchive,
+    /// The version this update replaces in the vault.
+    pub previous_archive: FileAccess,
+    /// Files with new or changed content, the only ones that are uploaded.
+    pub changed_files: Vec<PathBuf>,
+    pub total_files: usize,
+    pub total_size: u64,
+}
+
+async fn archive_entries(client: &Client, archive: &FileAccess) -> Result<Entries, UploadError> {
+    let entries = match archive {
+        FileAccess::Private(data_map) => client
+            .archive_get(data_map)
+            .await
+            .map_err(|err| UploadError::ArchiveGet(err.to_string()))?
+            .map()
+            .iter()
+            .map(|(path, (data_map, metadata))| {
+                (
+                    path.clone(),
+                    (FileAccess::Private(data_map.clone()), metadata.clone()),
+                )
+            })
+            .collect(),
+        FileAccess::Public(address) => client
+            .archive_get_public(address)
+            .await
+            .map_err(|err| UploadError::ArchiveGet(err.to_string()))?
+            .map()
+            .iter()
+            .map(|(path, (address, metadata))| {
+                (
+                    path.clone(),
+                    (FileAccess::Public(*address), metadata.clone()),
+                )
+            })
+            .collect(),
+    };
+
+    Ok(entries)
+}
+
+/// Earlier versions of an archive, newest first.
+pub async fn archive_versions(
+    client: &Client,
+    archive: FileAccess,
+) -> Result<Vec<FileAccess>, UploadError> {
+    let mut versions = vec![];
+    let mut current = archive;
+
+    while versions.len() < MAX_VERSIONS {
+        let entries = archive_entries(client, &current).await?;
+        let Some(previous) = entries
+            .values()
+            .find_map(|(_, metadata)| archive_metadata::previous_version(metadata))
+        else {
+            break;
+        };
+
+        versions.push(previous.clone());
+        current = previous;
+    }
+
+    Ok(versions)
+}
+
+/// The user data of a vault, empty only if the vault doesn't exist yet.
+///
+/// Other errors are returned, as writing over a vault that couldn't be read would drop everything
+/// else in it. Backups of watched folders are tried again at their next interval.
+async fn vault_user_data(
+    client: &Client,
+    secret_key: &VaultSecretKey,
+) -> Result<UserData, UploadError> {
+    match client.vault_get_user_data(secret_key).await {
+        Ok(user_data) => Ok(user_data),
+        Err(UserDataVaultError::Vault(VaultError::GraphEntry(GraphError::GetError(
+            GetError::RecordNotFound,
+        )))) => Ok(UserData::new()),
+        Err(err) => Err(UploadError::UserDataGet(err.to_string())),
+    }
+}
+
+/// Replace the previous version of an archive in the vault's user data with the new one. Older
+/// versions stay reachable through the new version's history, see [`archive_versions`].
+fn add_to_user_data(
+    user_data: &mut UserData,
+    archive: &FileAccess,
+    previous_archive: &FileAccess,
+    archive_name: &str,
+) {
+    match previous_archive {
+        FileAccess::Private(data_map) => {
+            user_data.private_file_archives.remove(data_map);
+        }
+        FileAccess::Public(address) => {
+            user_data.file_archives.remove(address);
+        }
+    }
+
+    match archive {
+        FileAccess::Private(data_map) => {
+            user_data
+                .private_file_archives
+                .insert(data_map.clone(), archive_name.to_string());
+        }
+        FileAccess::Public(address) => {
+            user_data
+                .file_archives
+                .insert(*address, archive_name.to_string());
+        }
+    }
+}
+
+/// Diff a folder against the previous version of its archive and quote the new version.
+///
+/// Files are taken to be unchanged when their size and modification time match the previous
+/// entry. Other files are encrypted, and only paid for if that yields a different datamap.
+#[allow(clippy::too_many_arguments)]
+pub async fn start_archive_update(
+    app: AppHandle,
+    previous_archive: FileAccess,
+    folder: PathBuf,
+    archive_name: String,
+    upload_id: String,
+    add_to_vault: bool,
+    vault_secret_key: Option<&VaultSecretKey>,
+    shared_client: State<'_, SharedClient>,
+    pending_uploads: Option<&tokio::sync::Mutex<crate::PendingUploads>>,
+) -> Result<(), UploadError> {
+    info!(
+        ">>> start_archive_update called with upload_id: {}, folder: {:?}",
+        upload_id, folder
+    );
+
+    let client = shared_client.get_client().await?;
+    let is_public = matches!(previous_archive, FileAccess::Public(_));
+    let previous_entries = archive_entries(&client, &previous_archive).await?;
+
+    let mut files = vec![];
+    let mut total_size = 0;
+    for (relative_path, absolute_path) in collect_files_from_directory(folder).await? {
+        let file_metadata = archive_metadata::metadata_from_disk(&absolute_path)
+            .map_err(|_| UploadError::Read(absolute_path.clone()))?;
+        total_size += file_metadata.size;
+        files.push((relative_path, absolute_path, file_metadata));
+    }
+
+    let Diff {
+        mut unchanged,
+        touched,
+        removed: removed_files,
+    } = diff(&previous_entries, files);
+    let mut changed_files = vec![];
+    let mut content_addresses = vec![];
+
+    for (relative_path, absolute_path, file_metadata) in touched {
+        let mut encryption_streams = encrypt_file_or_folder(absolute_path.clone(), is_public)
+            .await
+            .map_err(|err| UploadError::Encryption(format!("{:?}", err)))?;
+        let stream = encryption_streams
+            .first_mut()
+            .ok_or(UploadError::Encryption("Expected one stream".to_string()))?;
+
+        let file_content_addresses = content_addresses_from_encryption_stream(stream).await;
+
+        // Get datamap after stream is done
+        let datamap = stream
+            .data_map_chunk()
+            .ok_or(UploadError::Encryption("Failed to get datamap".to_string()))?;
+        let access = if is_public {
+            FileAccess::Public(DataAddress::new(*datamap.0.name()))
+        } else {
+            FileAccess::Private(datamap)
+        };
+
+        // Touched but identical content, the chunks are already stored
+        let same_content = previous_entries
+            .get(&relative_path)
+            .is_some_and(|(previous_access, _)| previous_access.local_key() == access.local_key());
+        if !same_content {
+            content_addresses.extend(file_content_addresses);
+            changed_files.push(absolute_path);
+        }
+
+        unchanged.insert(relative_path, (access, file_metadata));
+    }
+    let entries = unchanged;
+
+    let total_files = entries.len();
+    info!(
+        ">>> Archive update: {} changed, {} unchanged, {} removed files",
+        changed_files.len(),
+        total_files - changed_files.len(),
+        removed_files
+    );
+
+    let archive = build_archive(entries, is_public, &previous_archive);
+    let (archive_chunks, archive_access) = archive.encrypt()?;
+    content_addresses.extend(
+        archive_chunks
+            .iter()
+            .map(|chunk| (*chunk.address.xorname(), chunk.value.len())),
+    );
+
+    info!(
+        ">>> Getting store quotes for {} chunks...",
+        content_addresses.len()
+    );
+    let mut store_quote = client
+        .get_store_quotes(DataTypes::Chunk, content_addresses.into_iter())
+        .await
+        .map_err(|err| {
+            error!(">>> Failed to get store quotes: {}", err);
+            UploadError::StoreQuote(err.to_string())
+        })?;
+
+    let mut vault_update = Default::default();
+
+    if add_to_vault {
+        if let Some(secret_key) = vault_secret_key {
+            info!(">>> Getting vault quote for archive update...");
+
+            let mut user_data = vault_user_data(&client, secret_key).await?;
+            add_to_user_data(
+                &mut user_data,
+                &archive_access,
+                &previous_archive,
+                &archive_name,
+            );
+
+            let vault_data = user_data
+                .to_bytes()
+                .map_err(|e| UploadError::Serialization(e.to_string()))?;
+
+            let vault_quote_result =
+                crate::ant::vault::vault_quote(&client, vault_data, secret_key)
+                    .await
+                    .map_err(|e| UploadError::StoreQuote(e.to_string()))?;
+
+            store_quote.0.extend(vault_quote_result.quote.0);
+
+            vault_update = vault::VaultUpdate {
+                new_graph_entries: vault_quote_result.new_graph_entries,
+                new_scratchpad_derivations: vault_quote_result.new_scratchpad_derivations,
+                file_metadata: None,
+            };
+        }
+    }
+
+    let total_cost: Amount = store_quote
+        .payments()
+        .iter()
+        .map(|(_, _, amount)| *amount)
+        .sum();
+
+    let payments: Vec<serde_json::Value> = store_quote
+        .payments()
+        .iter()
+        .filter(|(_, _, amount)| *amount > Amount::ZERO)
+        .map(|(addr, _, amount)| {
+            serde_json::json!({
+                "address": hex::encode(addr),
+                "amount": amount.to_string(),
+                "amount_formatted": format!("{} {}", amount, "ATTO")
+            })
+        })
+        .collect();
+
+    let raw_payments: Vec<_> = store_quote
+        .payments()
+        .into_iter()
+        .filter(|(_, _, amount)| *amount > Amount::ZERO)
+        .collect();
+
+    app.emit(
+        "upload-quote",
+        serde_json::json!({
+            "upload_id": upload_id.clone(),
+            "total_files": total_files,
+            "total_size": total_size,
+            "total_cost_nano": total_cost.to_string(),
+            "total_cost_formatted": format!("{} {}", total_cost, "ATTO"),
+            "payment_required": total_cost > Amount::ZERO,
+            "payments": payments,
+            "raw_payments": raw_payments,
+            "changed_files": changed_files.len(),
+            "removed_files": removed_files
+        }),
+    )
+    .map_err(|err| UploadError::EmitEvent(err.to_string()))?;
+
+    let update = ArchiveUpdate {
+        archive_name,
+        archive,
+        previous_archive,
+        changed_files,
+        total_files,
+        total_size,
+    };
+
+    if total_cost == Amount::ZERO {
+        // Everything is stored already, the archive still has to be published and recorded
+        info!(
+            ">>> Archive update already stored (cost=0), publishing it for upload_id: {}",
+            upload_id
+        );
+        let receipt = autonomi::client::payment::receipt_from_store_quotes(store_quote);
+        execute_archive_update(
+            app,
+            update,
+            receipt,
+            vault_update,
+            upload_id,
+            add_to_vault,
+            vault_secret_key,
+            shared_client,
+        )
+        .await?;
+    } else if let Some(pending_uploads) = pending_uploads {
+        pending_uploads.lock().await.store_archive_update(
+            upload_id,
+            update,
+            store_quote,
+            vault_update,
+            add_to_vault,
+            vault_secret_key.map(SessionKey::from),
+        );
+    }
+
+    Ok(())
+}
+
+/// Upload the changed files and the new archive version after payment.
+#[allow(clippy::too_many_arguments)]
+pub async fn execute_archive_update(
+    app: AppHandle,
+    update: ArchiveUpdate,
+    receipt: Receipt,
+    vault_update: vault::VaultUpdate,
+    upload_id: String,
+    add_to_vault: bool,
+    vault_secret_key: Option<&VaultSecretKey>,
+    shared_client: State<'_, SharedClient>,
+) -> Result<(), UploadError> {
+    info!(
+        ">>> execute_archive_update called for upload_id: {}, {} changed files",
+        upload_id,
+        update.changed_files.len()
+    );
+
+    let client = shared_client.get_client().await?;
+
+    app.emit(
+        "upload-progress",
+        UploadProgress::Started {
+            upload_id: upload_id.clone(),
+            total_files: update.total_files,
+            total_size: update.total_size,
+        },
+    )
+    .map_err(|err| UploadError::EmitEvent(err.to_string()))?;
+
+    let vault_secret_key = vault_secret_key.cloned();
+    let (archive_chunks, archive_access) = update.archive.encrypt()?;
+
+    // Spawn the actual upload work in background
+    tokio::spawn(async move {
+        let result = async {
+            let mut total_chunks = 0;
+
+            for path in &update.changed_files {
+                let mut encryption_streams =
+                    encrypt_file_or_folder(path.clone(), update.archive.is_public())
+                        .await
+                        .map_err(|err| UploadError::Encryption(format!("{:?}", err)))?;
+
+                let stream = encryption_streams
+                    .first_mut()
+                    .ok_or(UploadError::Encryption("Expected one stream".to_string()))?;
+
+                total_chunks += stream.total_chunks();
+
+                batch_upload_encryption_stream(&client, &receipt, stream)
+                    .await
+                    .map_err(|err| UploadError::Put(format!("{:?}", err)))?;
+            }
+
+            total_chunks += archive_chunks.len();
+
+            client
+                .chunk_batch_upload(archive_chunks.iter().collect(), &receipt)
+                .await
+                .map_err(|err| UploadError::Put(err.to_string()))?;
+
+            info!(">>> Archive update uploaded for upload_id: {}", upload_id);
+
+            app.emit(
+                "upload-progress",
+                UploadProgress::Uploading {
+                    upload_id: upload_id.clone(),
+                    chunks_uploaded: total_chunks,
+                    total_chunks,
+                    bytes_uploaded: update.total_size,
+                    total_bytes: update.total_size,
+                },
+            )
+            .map_err(|err| UploadError::EmitEvent(err.to_string()))?;
+
+            if add_to_vault {
+                if let Some(secret_key) = vault_secret_key.as_ref() {
+                    info!(">>> Adding archive update to vault...");
+
+                    let mut user_data = vault_user_data(&client, secret_key).await?;
+                    add_to_user_data(
+                        &mut user_data,
+                        &archive_access,
+                        &update.previous_archive,
+                        &update.archive_name,
+                    );
+
+                    let vault_data = user_data
+                        .to_bytes()
+                        .map_err(|e| UploadError::Serialization(e.to_string()))?;
+
+                    vault::vault_update(
+                        &client,
+                        vault_data,
+                        secret_key,
+                        receipt,
+                        vault_update.new_graph_entries,
+                        vault_update.new_scratchpad_derivations,
+                    )
+                    .await
+                    .map_err(|err| {
+                        error!(">>> Failed to update vault: {:?}", err);
+                        UploadError::Scratchpad(err.to_string())
+                    })?;
+                } else {
+                    warn!(">>> Warning: add_to_vault=true but no vault_secret_key provided");
+                }
+            }
+
+            // Store the archive locally for future reference
+            let stored = match &archive_access {
+                FileAccess::Private(data_map) => local_storage::write_local_private_file_archive(
+                    data_map.to_hex(),
+                    data_map.address(),
+                    &update.archive_name,
+                ),
+                FileAccess::Public(address) => local_storage::write_local_public_file_archive(
+                    hex::encode(address.xorname().0),
+                    &update.archive_name,
+                ),
+            };
+            if let Err(err) = stored {
+                // Don't fail the upload for local storage issues
+                warn!(">>> Warning: Failed to store local reference: {:?}", err);
+            }
+
+            Ok::<(), UploadError>(())
+        }
+        .await;
+
+        let progress = match result {
+            Ok(()) => UploadProgress::Completed {
+                upload_id,
+                total_files: update.total_files,
+                total_bytes: update.total_size,
+                add_to_vault,
+                file_access: Some(archive_access),
+            },
+            Err(err) => {
+                error!(">>> Archive update failed: {}", err);
+                UploadProgress::Failed {
+                    upload_id,
+                    error: err.to_string(),
+                }
+            }
+        };
+        if let Err(err) = app.emit("upload-progress", progress) {
+            error!("Failed to emit upload progress: {}", err);
+        }
+    });
+
+    // Return immediately - the upload continues in background
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use autonomi::XorName;
+    use serde_json::Value;
+
+    fn public_access(name: &str) -> FileAccess {
+        FileAccess::Public(DataAddress::new(XorName::from_content(name.as_bytes())))
+    }
+
+    fn metadata(size: u64, modified: u64, mode: Option<u32>) -> Metadata {
+        let mut metadata = Metadata::new_with_size(size);
+        metadata.modified = modified;
+        if let Some(mode) = mode {
+            archive_metadata::set_extra_field(&mut metadata, "mode", Value::from(mode));
+        }
+        metadata
+    }
+
+    fn file(path: &str, metadata: Metadata) -> (PathBuf, PathBuf, Metadata) {
+        (
+            PathBuf::from(path),
+            PathBuf::from("/tmp").join(path),
+            metadata,
+        )
+    }
+
+    #[test]
+    fn test_matches_entry() {
+        let previous = metadata(10, 100, Some(0o644));
+
+        assert!(matches_entry(&previous, &metadata(10, 100, Some(0o644))));
+        assert!(!matches_entry(&previous, &metadata(11, 100, Some(0o644))));
+        assert!(!matches_entry(&previous, &metadata(10, 101, Some(0o644))));
+        assert!(!matches_entry(&previous, &metadata(10, 100, Some(0o755))));
+
+        // Published before modes were recorded
+        assert!(matches_entry(
+            &metadata(10, 100, None),
+            &metadata(10, 100, Some(0o644))
+        ));
+        // Published without a modification time
+        assert!(!matches_entry(
+            &metadata(10, 0, None),
+            &metadata(10, 0, None)
+        ));
+    }
+
+    #[test]
+    fn test_diff() {
+        let previous: Entries = [
+            ("dir/same", metadata(1, 100, Some(0o644))),
+            ("dir/changed", metadata(2, 100, Some(0o644))),
+            ("dir/removed", metadata(3, 100, Some(0o644))),
+        ]
+        .into_iter()
+        .map(|(path, metadata)| (PathBuf::from(path), (public_access(path), metadata)))
+        .collect();
+
+        let diff = diff(
+            &previous,
+            vec![
+                file("dir/same", metadata(1, 100, Some(0o644))),
+                file("dir/changed", metadata(2, 200, Some(0o644))),
+                file("dir/new", metadata(4, 100, Some(0o644))),
+            ],
+        );
+
+        assert_eq!(
+            diff.unchanged.keys().collect::<Vec<_>>(),
+            vec![&PathBuf::from("dir/same")]
+        );
+        assert_eq!(
+            diff.unchanged[&PathBuf::from("dir/same")].0.local_key(),
+            public_access("dir/same").local_key()
+        );
+        assert_eq!(
+            diff.touched
+                .iter()
+                .map(|(path, _, _)| path.clone())
+                .collect::<Vec<_>>(),
+            vec![PathBuf::from("dir/changed"), PathBuf::from("dir/new")]
+        );
+        assert_eq!(diff.removed, 1);
+    }
+
+    #[test]
+    fn test_previous_version_is_stored_once() {
+        let older = public_access("older version");
+        let previous = public_access("previous version");
+
+        // A carried over entry still links to the version before the previous one
+        let mut carried = metadata(1, 100, None);
+        archive_metadata::set_previous_version(&mut carried, &older);
+        let entries: Entries = [
+            (
+                PathBuf::from("dir/a"),
+                (public_access("a"), metadata(1, 100, None)),
+            ),
+            (PathBuf::from("dir/b"), (public_access("b"), carried)),
+        ]
+        .into_iter()
+        .collect();
+
+        let UpdatedArchive::Public(archive) = build_archive(entries, true, &previous) else {
+            panic!("expected a public archive");
+        };
+        let links: Vec<_> = archive
+            .map()
+            .values()
+            .filter_map(|(_, metadata)| archive_metadata::previous_version(metadata))
+            .collect();
+
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].local_key(), previous.local_key());
+    }
+
+    #[test]
+    fn test_update_replaces_previous_version_in_vault() {
+        let other = public_access("other archive");
+        let previous = public_access("previous version");
+        let updated = public_access("updated version");
+
+        let address = |access: &FileAccess| match access {
+            FileAccess::Public(address) => *address,
+            FileAccess::Private(_) => unreachable!(),
+        };
+
+        let mut user_data = UserData::new();
+        for (access, name) in [(&other, "Other"), (&previous, "Photos")] {
+            user_data
+                .file_archives
+                .insert(address(access), name.to_string());
+        }
+        add_to_user_data(&mut user_data, &updated, &previous, "Photos");
+
+        assert_eq!(user_data.file_archives.len(), 2);
+        assert_eq!(user_data.file_archives[&address(&other)], "Other");
+        assert_eq!(user_data.file_archives[&address(&updated)], "Photos");
+    }
+}
@@ -1,3 +1,4 @@
+use crate::ant::ignore_rules::IgnoreRules;
 use autonomi::client::payment::Receipt;
 use serde::{Deserialize, Serialize};
 use std::collections::hash_map::DefaultHasher;
@@ -178,7 +179,8 @@ impl PaymentCache {
             if let Ok(metadata) = fs::metadata(&file.path) {
                 if metadata.is_dir() {
                     // Recursively collect files from directory
-                    Self::collect_files_recursive(&file.path, &mut all_file_paths)?;
+                    let ignore_rules = IgnoreRules::for_folder(&file.path);
+                    Self::collect_files_recursive(&file.path, &ignore_rules, &mut all_file_paths)?;
                 } else {
                     // Single file
                     all_file_paths.push(file.path.clone());
@@ -214,13 +216,17 @@ impl PaymentCache {
         Ok(format!("{:x}", hasher.finish()))
     }
     
-    fn collect_files_recursive(dir_path: &std::path::Path, file_paths: &mut Vec<std::path::PathBuf>) -> Result<(), std::io::Error> {
+    fn collect_files_recursive(dir_path: &std::path::Path, ignore_rules: &IgnoreRules, file_paths: &mut Vec<std::path::PathBuf>) -> Result<(), std::io::Error> {
         for entry in fs::read_dir(dir_path)? {
             let entry = entry?;
             let path = entry.path();
             
+            if ignore_rules.is_ignored(&path, path.is_dir()) {
+                continue;
+            }
+            
             if path.is_dir() {
-                Self::collect_files_recursive(&path, file_paths)?;
+                Self::collect_files_recursive(&path, ignore_rules, file_paths)?;
             } else if path.is_file() {
                 file_paths.push(path);
             }
@@ -0,0 +1,592 @@
+//! On-disk cache of network chunks, used by downloads and previews.
+//!
+//! Chunks are immutable and named by the hash of their content, so a cached chunk never goes
+//! stale and is checked against its name on every read. Files in the cache directory are named
+//! by the chunk's `XorName`, their modification time marks the last use. Once the cache grows
+//! past its size limit the least recently used chunks are removed, except those pinned by a
+//! download that is still reading them.
+//!
+//! Datamaps that were shrunk into child datamaps are resolved through the cache as well, so a
+//! file's content can be read in ranges that only touch the chunks holding them.
+
+use crate::ant::files::FileAccess;
+use autonomi::chunk::ChunkAddress;
+use autonomi::{Bytes, Client, XorName};
+use self_encryption::{DataMap, EncryptedChunk};
+use std::collections::HashMap;
+use std::fs;
+use std::future::Future;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::SystemTime;
+use thiserror::Error as ThisError;
+use tracing::{info, warn};
+
+/// Size limit used when the settings don't set one.
+pub const DEFAULT_MAX_SIZE_MB: u64 = 1024;
+
+/// Chunks fetched from the network at the same time.
+const FETCH_CONCURRENCY: usize = 8;
+
+static MAX_SIZE: AtomicU64 = AtomicU64::new(DEFAULT_MAX_SIZE_MB * 1024 * 1024);
+
+static CHUNK_CACHE: OnceLock<Result<ChunkCache, String>> = OnceLock::new();
+
+#[derive(ThisError, Debug)]
+pub enum ChunkCacheError {
+    #[error("Could not get chunk: {0:?}")]
+    Get(#[from] autonomi::client::GetError),
+    #[error("Could not download file: {0:?}")]
+    Download(#[from] autonomi::client::files::DownloadError),
+    #[error("Could not decrypt file: {0}")]
+    Decryption(String),
+    #[error("Chunk cache I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Chunk fetch failed: {0}")]
+    Task(String),
+}
+
+/// Use the size limit from the settings, `None` for the default.
+pub fn set_max_size_mb(max_size_mb: Option<u64>) {
+    let max_size_mb = max_size_mb.unwrap_or(DEFAULT_MAX_SIZE_MB);
+    MAX_SIZE.store(max_size_mb.saturating_mul(1024 * 1024), Ordering::Relaxed);
+}
+
+fn max_size() -> u64 {
+    MAX_SIZE.load(Ordering::Relaxed)
+}
+
+pub struct ChunkCache {
+    cache_dir: PathBuf,
+    /// Chunks in use, with the number of downloads using them.
+    pinned: Mutex<HashMap<XorName, usize>>,
+}
+
+/// Keeps chunks from being evicted while it lives.
+pub struct PinnedChunks<'a> {
+    cache: &'a ChunkCache,
+    names: Vec<XorName>,
+}
+
+impl Drop for PinnedChunks<'_> {
+    fn drop(&mut self) {
+        let mut pinned = self.cache.pinned.lock().unwrap_or_else(|e| e.into_inner());
+        for name in &self.names {
+            if let Some(count) = pinned.get_mut(name) {
+                *count -= 1;
+                if *count == 0 {
+                    pinned.remove(name);
+                }
+            }
+        }
+    }
+}
+
+/// The shared chunk cache, `None` if there is no app data directory to keep it in.
+pub fn get_chunk_cache() -> Option<&'static ChunkCache> {
+    CHUNK_CACHE
+        .get_or_init(|| {
+            crate::ant::app_data::data_dir()
+                .ok_or_else(|| "Could not get app data directory".to_string())
+                .and_then(|dir| {
+                    ChunkCache::new(&dir)
+                        .map_err(|e| format!("Failed to create chunk cache: {}", e))
+                })
+        })
+        .as_ref()
+        .inspect_err(|err| warn!("{err}"))
+        .ok()
+}
+
+impl ChunkCache {
+    pub fn new(base_dir: &Path) -> Result<Self, std::io::Error> {
+        let cache_dir = base_dir.join("chunk_cache");
+        fs::create_dir_all(&cache_dir)?;
+        Ok(Self {
+            cache_dir,
+            pinned: Mutex::default(),
+        })
+    }
+
+    fn chunk_path(&self, name: &XorName) -> PathBuf {
+        self.cache_dir.join(hex::encode(name.0))
+    }
+
+    /// Keep `names` from being evicted until the returned guard is dropped.
+    pub fn pin(&self, names: &[XorName]) -> PinnedChunks<'_> {
+        let mut pinned = self.pinned.lock().unwrap_or_else(|e| e.into_inner());
+        for name in names {
+            *pinned.entry(*name).or_default() += 1;
+        }
+        PinnedChunks {
+            cache: self,
+            names: names.to_vec(),
+        }
+    }
+
+    fn is_pinned(&self, name: &XorName) -> bool {
+        self.pinned
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .contains_key(name)
+    }
+
+    /// Mark a chunk as recently used, `false` if it isn't cached.
+    fn touch(&self, name: &XorName) -> bool {
+        fs::File::options()
+            .write(true)
+            .open(self.chunk_path(name))
+            .and_then(|file| file.set_modified(SystemTime::now()))
+            .is_ok()
+    }
+
+    /// A cached chunk, `None` if it isn't cached or its content doesn't match its name.
+    pub fn get(&self, name: &XorName) -> Option<Bytes> {
+        let path = self.chunk_path(name);
+        let content = fs::read(&path).ok()?;
+
+        if XorName::from_content(&content) != *name {
+            warn!("Removing corrupt cached chunk {name:?}");
+            let _ = fs::remove_file(&path);
+            return None;
+        }
+
+        self.touch(name);
+        Some(Bytes::from(content))
+    }
+
+    pub fn put(&self, name: &XorName, content: &[u8]) -> Result<(), std::io::Error> {
+        crate::ant::app_data::write_atomic(&self.chunk_path(name), content)
+    }
+
+    /// Remove the least recently used chunks until the cache fits its size limit.
+    pub fn evict(&self) -> Result<(), std::io::Error> {
+        self.evict_to(max_size())
+    }
+
+    /// Remove the least recently used chunks that aren't pinned until the cache holds at most
+    /// `max_size` bytes, or only pinned chunks are left.
+    fn evict_to(&self, max_size: u64) -> Result<(), std::io::Error> {
+        let mut chunks = vec![];
+        for entry in fs::read_dir(&self.cache_dir)? {
+            let entry = entry?;
+            // Chunks still being written have a temporary name, they aren't counted yet
+            let Some(name) = entry
+                .file_name()
+                .to_str()
+                .and_then(|name| hex::decode(name).ok())
+                .and_then(|name| <[u8; 32]>::try_from(name).ok())
+            else {
+                continue;
+            };
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            let used = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            chunks.push((used, metadata.len(), XorName(name), entry.path()));
+        }
+
+        let mut size: u64 = chunks.iter().map(|(_, len, _, _)| len).sum();
+        if size <= max_size {
+            return Ok(());
+        }
+
+        chunks.sort_by_key(|(used, _, _, _)| *used);
+        let mut evicted = 0;
+        for (_, len, name, path) in chunks {
+            if size <= max_size {
+                break;
+            }
+            if self.is_pinned(&name) {
+                continue;
+            }
+            // Another eviction may have removed it already
+            if fs::remove_file(path).is_ok() {
+                evicted += 1;
+            }
+            size -= len;
+        }
+
+        info!("Evicted {evicted} chunks from the chunk cache");
+        Ok(())
+    }
+
+    pub fn clear_cache(&self) -> Result<(), std::io::Error> {
+        for entry in fs::read_dir(&self.cache_dir)? {
+            let entry = entry?;
+            let _ = fs::remove_file(entry.path());
+        }
+        Ok(())
+    }
+}
+
+/// A chunk from the cache, or from the network and then cached.
+async fn fetch_chunk(
+    client: &Client,
+    cache: &ChunkCache,
+    name: XorName,
+) -> Result<Bytes, ChunkCacheError> {
+    if let Some(content) = cache.get(&name) {
+        return Ok(content);
+    }
+
+    let chunk = client.chunk_get(&ChunkAddress::new(name)).await?;
+    if let Err(err) = cache.put(&name, &chunk.value) {
+        warn!("Failed to cache chunk {name:?}: {err}");
+    }
+    Ok(chunk.value)
+}
+
+/// Make sure all chunks are cached, fetching the missing ones a few at a time.
+async fn fetch_chunks(
+    client: &Client,
+    cache: &'static ChunkCache,
+    names: &[XorName],
+) -> Result<(), ChunkCacheError> {
+    // Touching cached chunks keeps the ones this download is about to read from being evicted
+    let missing: Vec<XorName> = names
+        .iter()
+        .filter(|name| !cache.touch(name))
+        .copied()
+        .collect();
+
+    for batch in missing.chunks(FETCH_CONCURRENCY) {
+        let tasks: Vec<_> = batch
+            .iter()
+            .map(|name| {
+                let client = client.clone();
+                let name = *name;
+                tauri::async_runtime::spawn(async move { fetch_chunk(&client, cache, name).await })
+            })
+            .collect();
+
+        for task in tasks {
+            task.await
+                .map_err(|err| ChunkCacheError::Task(err.to_string()))??;
+        }
+    }
+
+    Ok(())
+}
+
+/// A chunk through the cache, or straight from the network when there is no cache.
+async fn get_chunk(client: &Client, name: XorName) -> Result<Bytes, ChunkCacheError> {
+    match get_chunk_cache() {
+        Some(cache) => fetch_chunk(client, cache, name).await,
+        None => Ok(client.chunk_get(&ChunkAddress::new(name)).await?.value),
+    }
+}
+
+/// Chunks by name, see [`get_chunk`].
+async fn get_chunks(
+    client: &Client,
+    names: &[XorName],
+) -> Result<HashMap<XorName, Bytes>, ChunkCacheError> {
+    let cache = get_chunk_cache();
+    let _pinned = cache.map(|cache| cache.pin(names));
+    if let Some(cache) = cache {
+        fetch_chunks(client, cache, names).await?;
+    }
+
+    let mut chunks = HashMap::new();
+    for name in names {
+        chunks.insert(*name, get_chunk(client, *name).await?);
+    }
+    Ok(chunks)
+}
+
+/// Datamap chunks are MessagePack, the levels below a shrunk datamap are bincode.
+fn parse_data_map(content: &Bytes) -> Result<DataMap, ChunkCacheError> {
+    Client::deserialize_data_map(content)
+        .ok()
+        .or_else(|| self_encryption::deserialize(content).ok())
+        .ok_or_else(|| ChunkCacheError::Decryption("Invalid datamap".to_string()))
+}
+
+/// The datamap listing the chunks of a file's content, child datamaps resolved.
+pub async fn root_data_map(
+    client: &Client,
+    file_access: &FileAccess,
+) -> Result<DataMap, ChunkCacheError> {
+    let content = match file_access {
+        FileAccess::Private(data_map) => data_map.0.value.clone(),
+        // The datamap of a public file is stored as a chunk at the file address
+        FileAccess::Public(addr) => get_chunk(client, *addr.xorname()).await?,
+    };
+
+    resolve_data_map(
+        &content,
+        |names| async move { get_chunks(client, &names).await },
+    )
+    .await
+}
+
+/// Parse a datamap chunk and resolve its child levels, with the chunks `get_chunks` returns.
+pub(crate) async fn resolve_data_map<F, Fut>(
+    content: &Bytes,
+    mut get_chunks: F,
+) -> Result<DataMap, ChunkCacheError>
+where
+    F: FnMut(Vec<XorName>) -> Fut,
+    Fut: Future<Output = Result<HashMap<XorName, Bytes>, ChunkCacheError>>,
+{
+    let mut data_map = parse_data_map(content)?;
+    while data_map.is_child() {
+        let chunks = get_chunks(chunk_names(&data_map))
+            .await?
+            .into_values()
+            .map(|content| EncryptedChunk { content })
+            .collect::<Vec<_>>();
+
+        // A child datamap is the encrypted content of the level above it
+        let content = self_encryption::decrypt(&DataMap::new(data_map.infos()), &chunks)
+            .map_err(|err| ChunkCacheError::Decryption(format!("{err:?}")))?;
+        data_map = parse_data_map(&content)?;
+    }
+
+    Ok(data_map)
+}
+
+fn chunk_names(data_map: &DataMap) -> Vec<XorName> {
+    data_map.infos().iter().map(|info| info.dst_hash).collect()
+}
+
+/// The chunks holding `range` of the content of a root datamap.
+fn range_chunk_names(data_map: &DataMap, range: &Range<usize>) -> Vec<XorName> {
+    let mut infos = data_map.infos();
+    infos.sort_by_key(|info| info.index);
+
+    let mut offset = 0;
+    infos
+        .into_iter()
+        .filter_map(|info| {
+            let chunk = offset..offset + info.src_size;
+            offset = chunk.end;
+            (chunk.start < range.end && range.start < chunk.end).then_some(info.dst_hash)
+        })
+        .collect()
+}
+
+/// Part of a file's content, only the chunks holding `range` are fetched and decrypted.
+pub async fn data_get_range(
+    client: &Client,
+    data_map: &DataMap,
+    range: Range<usize>,
+) -> Result<Bytes, ChunkCacheError> {
+    let chunks = get_chunks(client, &range_chunk_names(data_map, &range)).await?;
+
+    let content = self_encryption::streaming_decrypt(data_map, |batch: &[(usize, XorName)]| {
+        batch
+            .iter()
+            .map(|(index, name)| {
+                chunks
+                    .get(name)
+                    .map(|content| (*index, content.clone()))
+                    .ok_or_else(|| {
+                        self_encryption::Error::Generic(format!("Chunk {name:?} is not fetched"))
+                    })
+            })
+            .collect()
+    })
+    .and_then(|stream| stream.range(range))
+    .map_err(|err| ChunkCacheError::Decryption(format!("{err:?}")))?;
+
+    if let Some(cache) = get_chunk_cache() {
+        cache.evict()?;
+    }
+    Ok(content)
+}
+
+/// Download a file to `dest` through the cache.
+///
+/// Chunks are cached on disk first and decrypted from there, so memory use stays bounded. Files
+/// that don't fit in the cache are downloaded straight from the network.
+pub async fn file_download(
+    client: &Client,
+    file_access: &FileAccess,
+    dest: PathBuf,
+) -> Result<(), ChunkCacheError> {
+    let Some(cache) = get_chunk_cache() else {
+        return network_file_download(client, file_access, dest).await;
+    };
+    let data_map = root_data_map(client, file_access).await?;
+    if data_map.original_file_size() as u64 >= max_size() {
+        return network_file_download(client, file_access, dest).await;
+    }
+
+    let names = chunk_names(&data_map);
+    let pinned = cache.pin(&names);
+    fetch_chunks(client, cache, &names).await?;
+
+    self_encryption::streaming_decrypt_from_storage(&data_map, &dest, |batch| {
+        batch
+            .iter()
+            .map(|(index, name)| {
+                cache
+                    .get(name)
+                    .map(|content| (*index, content))
+                    .ok_or_else(|| {
+                        self_encryption::Error::Generic(format!("Chunk {name:?} is not cached"))
+                    })
+            })
+            .collect()
+    })
+    .map_err(|err| ChunkCacheError::Decryption(format!("{err:?}")))?;
+
+    drop(pinned);
+    cache.evict()?;
+    Ok(())
+}
+
+async fn network_file_download(
+    client: &Client,
+    file_access: &FileAccess,
+    dest: PathBuf,
+) -> Result<(), ChunkCacheError> {
+    match file_access {
+        FileAccess::Private(data_map) => client.file_download(data_map, dest).await?,
+        FileAccess::Public(addr) => client.file_download_public(addr, dest).await?,
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+    use tempfile::TempDir;
+
+    /// A cache with a chunk per content, last used in the given order.
+    fn cache_with(dir: &TempDir, contents: &[&[u8]]) -> (ChunkCache, Vec<XorName>) {
+        let cache = ChunkCache::new(dir.path()).unwrap();
+        let names: Vec<XorName> = contents
+            .iter()
+            .enumerate()
+            .map(|(i, content)| {
+                let name = XorName::from_content(content);
+                cache.put(&name, content).unwrap();
+                fs::File::options()
+                    .write(true)
+                    .open(cache.chunk_path(&name))
+                    .unwrap()
+                    .set_modified(UNIX_EPOCH + Duration::from_secs(1_000 + i as u64))
+                    .unwrap();
+                name
+            })
+            .collect();
+        (cache, names)
+    }
+
+    fn cached(cache: &ChunkCache, names: &[XorName]) -> Vec<bool> {
+        names
+            .iter()
+            .map(|name| cache.chunk_path(name).exists())
+            .collect()
+    }
+
+    #[test]
+    fn test_get_put() {
+        let dir = TempDir::new().unwrap();
+        let (cache, names) = cache_with(&dir, &[b"first chunk"]);
+
+        assert_eq!(cache.get(&names[0]).unwrap(), &b"first chunk"[..]);
+        assert!(cache.get(&XorName::from_content(b"missing")).is_none());
+        // Nothing but chunks is left in the cache directory
+        assert_eq!(fs::read_dir(&cache.cache_dir).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_corrupt_chunk_is_removed() {
+        let dir = TempDir::new().unwrap();
+        let (cache, names) = cache_with(&dir, &[b"first chunk"]);
+        fs::write(cache.chunk_path(&names[0]), b"flipped bits").unwrap();
+
+        assert!(cache.get(&names[0]).is_none());
+        assert_eq!(cached(&cache, &names), [false]);
+    }
+
+    #[test]
+    fn test_evict_least_recently_used() {
+        let dir = TempDir::new().unwrap();
+        let (cache, names) = cache_with(&dir, &[b"chunk 0", b"chunk 1", b"chunk 2"]);
+
+        // Reading a chunk makes it the most recently used
+        cache.get(&names[0]).unwrap();
+        cache.evict_to(14).unwrap();
+        assert_eq!(cached(&cache, &names), [true, false, true]);
+
+        cache.evict_to(7).unwrap();
+        assert_eq!(cached(&cache, &names), [true, false, false]);
+    }
+
+    #[test]
+    fn test_evict_skips_pinned() {
+        let dir = TempDir::new().unwrap();
+        let (cache, names) = cache_with(&dir, &[b"chunk 0", b"chunk 1", b"chunk 2"]);
+
+        let pinned = cache.pin(&names[..1]);
+        let pinned_twice = cache.pin(&names[..2]);
+        cache.evict_to(0).unwrap();
+        assert_eq!(cached(&cache, &names), [true, true, false]);
+
+        drop(pinned_twice);
+        cache.evict_to(0).unwrap();
+        assert_eq!(cached(&cache, &names), [true, false, false]);
+
+        drop(pinned);
+        cache.evict_to(0).unwrap();
+        assert_eq!(cached(&cache, &names), [false, false, false]);
+    }
+
+    #[test]
+    fn test_evict_ignores_chunks_being_written() {
+        let dir = TempDir::new().unwrap();
+        let (cache, names) = cache_with(&dir, &[b"chunk 0"]);
+        let tmp_path = cache.chunk_path(&names[0]).with_extension("tmp");
+        fs::write(&tmp_path, b"half a chu").unwrap();
+
+        cache.evict_to(0).unwrap();
+        assert_eq!(cached(&cache, &names), [false]);
+        assert!(tmp_path.exists());
+    }
+
+    #[test]
+    fn test_range_chunk_names() {
+        let content: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+        let (data_map, chunks) = self_encryption::encrypt(Bytes::from(content.clone())).unwrap();
+        let chunks: HashMap<XorName, Bytes> = chunks
+            .into_iter()
+            .map(|chunk| (XorName::from_content(&chunk.content), chunk.content))
+            .collect();
+
+        let mut infos = data_map.infos();
+        infos.sort_by_key(|info| info.index);
+        let first = infos[0].src_size;
+
+        assert_eq!(range_chunk_names(&data_map, &(0..1)), [infos[0].dst_hash]);
+        assert_eq!(
+            range_chunk_names(&data_map, &(first - 1..first + 1)),
+            [infos[0].dst_hash, infos[1].dst_hash]
+        );
+        assert_eq!(
+            range_chunk_names(&data_map, &(0..content.len())),
+            chunk_names(&data_map)
+        );
+
+        // The chunks of a range are enough to decrypt it
+        let range = first + 10..first + 500;
+        let names = range_chunk_names(&data_map, &range);
+        let stream =
+            self_encryption::streaming_decrypt(&data_map, |batch: &[(usize, XorName)]| {
+                Ok(batch
+                    .iter()
+                    .filter(|(_, name)| names.contains(name))
+                    .map(|(index, name)| (*index, chunks[name].clone()))
+                    .collect())
+            })
+            .unwrap();
+        assert_eq!(stream.range(range.clone()).unwrap(), content[range]);
+    }
+}
@@ -1,14 +1,45 @@
+use crate::ant::health::{NetworkStatus, CONNECT_TIMEOUT};
+use crate::ant::network::{self, NetworkConfig, NetworkConfigError};
 use autonomi::client::ConnectError;
 use autonomi::Client;
+use thiserror::Error as ThisError;
 use tokio::sync::RwLock;
+use tracing::info;
+
+#[derive(ThisError, Debug)]
+pub enum ClientError {
+    #[error("{0:?}")]
+    Connect(#[from] ConnectError),
+    #[error("Invalid network configuration: {0}")]
+    Config(#[from] NetworkConfigError),
+    #[error("The network is unreachable, reconnecting in the background")]
+    Offline,
+}
+
+impl ClientError {
+    /// Whether the error means the network can't be reached, as opposed to a bad configuration.
+    pub fn is_offline(&self) -> bool {
+        matches!(self, Self::Connect(_) | Self::Offline)
+    }
+}
 
 #[derive(Default)]
 pub struct SharedClient {
     client: RwLock<Option<Client>>,
+    config: RwLock<NetworkConfig>,
+    status: RwLock<NetworkStatus>,
 }
 
 impl SharedClient {
-    pub async fn connect(&self) -> Result<Client, ConnectError> {
+    pub fn new(config: NetworkConfig) -> Self {
+        Self {
+            client: RwLock::new(None),
+            config: RwLock::new(config),
+            status: RwLock::new(NetworkStatus::Connecting),
+        }
+    }
+
+    pub async fn connect(&self) -> Result<Client, ClientError> {
         let mut client_lock = self.client.write().await;
 
         // check if another thread already connected the client in the meanwhile
@@ -16,26 +47,58 @@ impl SharedClient {
             return Ok(client.clone());
         }
 
-        let client = if std::env::var("ANT_LOCAL").is_ok() {
-            Client::init_local().await?
-        } else {
-            Client::init().await?
-        };
+        let config = self.config.read().await.clone();
+        info!("connecting to the {:?} network", config.kind);
+
+        let client = network::connect(&config).await?;
         *client_lock = Some(client.clone());
 
         Ok(client)
     }
 
-    #[expect(dead_code)]
+    /// Drop the current client and connect with a new network configuration.
+    pub async fn reconnect(&self, config: NetworkConfig) -> Result<Client, ClientError> {
+        config.validate()?;
+
+        *self.config.write().await = config;
+        self.disconnect().await;
+
+        self.connect().await
+    }
+
     pub async fn disconnect(&self) {
         *self.client.write().await = None;
     }
 
-    pub async fn get_client(&self) -> Result<Client, ConnectError> {
+    /// The connected client, without trying to connect.
+    pub async fn current_client(&self) -> Option<Client> {
+        self.client.read().await.clone()
+    }
+
+    pub async fn get_client(&self) -> Result<Client, ClientError> {
         if let Some(client) = self.client.read().await.as_ref() {
             return Ok(client.clone());
         }
 
-        self.connect().await
+        // The health monitor keeps retrying, don't make every command wait for it
+        if *self.status.read().await == NetworkStatus::Offline {
+            return Err(ClientError::Offline);
+        }
+
+        tokio::time::timeout(CONNECT_TIMEOUT, self.connect())
+            .await
+            .map_err(|_elapsed| ClientError::Offline)?
+    }
+
+    pub async fn status(&self) -> NetworkStatus {
+        *self.status.read().await
+    }
+
+    /// Record the connection status, returns whether it changed.
+    pub async fn set_status(&self, status: NetworkStatus) -> bool {
+        let mut current = self.status.write().await;
+        let changed = *current != status;
+        *current = status;
+        changed
     }
 }
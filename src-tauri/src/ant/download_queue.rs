@@ -0,0 +1,372 @@
+//! Download manager: a persistent queue of downloads that run in the background.
+//!
+//! Downloads are kept in the app data directory so they survive a restart, and at most
+//! [`MAX_CONCURRENT_DOWNLOADS`] run at a time. A running download can be paused, which stops it
+//! where it is and removes its partial files. Resuming or retrying a download runs it again:
+//! files that earlier attempts wrote are verified against their datamap and kept or replaced,
+//! nothing else at the destination is touched.
+
+use crate::ant::app_data;
+use crate::ant::files::{self, DownloadProgress, FileAccess, SharedProgress};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Emitter, Manager};
+use thiserror::Error as ThisError;
+use tokio::sync::{Mutex, Notify};
+use tracing::{error, info, warn};
+
+const QUEUE_FILE: &str = "download_queue.json";
+
+/// Downloads that run at the same time.
+pub const MAX_CONCURRENT_DOWNLOADS: usize = 3;
+
+#[derive(ThisError, Debug)]
+pub enum DownloadQueueError {
+    #[error("Could not determine the app data directory")]
+    NoDataDir,
+    #[error("Could not store the download queue: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Could not serialize the download queue: {0}")]
+    Serialization(#[from] serde_json::Error),
+    #[error("Unknown download: {0}")]
+    UnknownDownload(String),
+    #[error("Download {0} can't be {1} while it is {2:?}")]
+    InvalidTransition(String, &'static str, DownloadStatus),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DownloadStatus {
+    Queued,
+    Running,
+    Paused,
+    Completed,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueuedDownload {
+    pub id: String,
+    pub created: u64,
+    pub file_access: FileAccess,
+    pub dest: PathBuf,
+    pub restore_metadata: bool,
+    pub status: DownloadStatus,
+    /// Started attempts.
+    pub attempts: u32,
+    /// Why the last attempt failed.
+    pub error: Option<String>,
+    /// Where the files that earlier attempts wrote ended up, by the path they were downloaded to.
+    #[serde(default)]
+    pub written: BTreeMap<PathBuf, PathBuf>,
+}
+
+pub struct DownloadQueue {
+    path: Option<PathBuf>,
+    downloads: Mutex<Vec<QueuedDownload>>,
+    /// Download id -> the task running it, and what it wrote so far.
+    tasks: Mutex<HashMap<String, (JoinHandle<()>, SharedProgress)>>,
+    /// Wakes the scheduler when a download is queued or a slot frees up.
+    wake: Notify,
+}
+
+impl Default for DownloadQueue {
+    fn default() -> Self {
+        Self::load()
+    }
+}
+
+impl DownloadQueue {
+    /// Load the queue from the app data directory.
+    ///
+    /// Completed downloads are dropped, and downloads that were running when the app exited are
+    /// queued again.
+    pub fn load() -> Self {
+        let path = app_data::data_dir().map(|dir| dir.join(QUEUE_FILE));
+
+        let mut downloads: Vec<QueuedDownload> = path
+            .as_deref()
+            .and_then(app_data::load_json)
+            .unwrap_or_default();
+
+        downloads.retain(|download| download.status != DownloadStatus::Completed);
+        for download in &mut downloads {
+            if download.status == DownloadStatus::Running {
+                download.status = DownloadStatus::Queued;
+            }
+        }
+
+        if !downloads.is_empty() {
+            info!("Loaded {} queued downloads", downloads.len());
+        }
+
+        Self {
+            path,
+            downloads: Mutex::new(downloads),
+            tasks: Mutex::new(HashMap::new()),
+            wake: Notify::new(),
+        }
+    }
+
+    fn save(&self, downloads: &[QueuedDownload]) -> Result<(), DownloadQueueError> {
+        let path = self.path.as_ref().ok_or(DownloadQueueError::NoDataDir)?;
+        app_data::save_json(path, downloads)?;
+        Ok(())
+    }
+
+    pub async fn push(
+        &self,
+        file_access: FileAccess,
+        dest: PathBuf,
+        restore_metadata: bool,
+    ) -> Result<QueuedDownload, DownloadQueueError> {
+        let download = QueuedDownload {
+            id: hex::encode(rand::random::<[u8; 8]>()),
+            created: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            file_access,
+            dest,
+            restore_metadata,
+            status: DownloadStatus::Queued,
+            attempts: 0,
+            error: None,
+            written: BTreeMap::new(),
+        };
+
+        let mut downloads = self.downloads.lock().await;
+        downloads.push(download.clone());
+        self.save(&downloads)?;
+
+        info!("Queued download {} to {:?}", download.id, download.dest);
+        self.wake.notify_one();
+        Ok(download)
+    }
+
+    pub async fn list(&self) -> Vec<QueuedDownload> {
+        self.downloads.lock().await.clone()
+    }
+
+    async fn modify(
+        &self,
+        id: &str,
+        f: impl FnOnce(&mut QueuedDownload) -> Result<(), DownloadQueueError>,
+    ) -> Result<QueuedDownload, DownloadQueueError> {
+        let mut downloads = self.downloads.lock().await;
+
+        let download = downloads
+            .iter_mut()
+            .find(|download| download.id == id)
+            .ok_or_else(|| DownloadQueueError::UnknownDownload(id.to_string()))?;
+        f(download)?;
+        let download = download.clone();
+
+        self.save(&downloads)?;
+        Ok(download)
+    }
+
+    /// Stop the task running a download, remove its partial files and keep track of the files
+    /// it finished.
+    async fn stop_task(&self, id: &str) {
+        let Some((task, progress)) = self.tasks.lock().await.remove(id) else {
+            return;
+        };
+        task.abort();
+        let _ = task.await;
+
+        let progress = std::mem::take(&mut *progress.lock().unwrap_or_else(|e| e.into_inner()));
+        for path in &progress.partial {
+            let _ = std::fs::remove_file(path);
+        }
+        if let Err(err) = self
+            .modify(id, |download| {
+                download.written = progress.written;
+                Ok(())
+            })
+            .await
+        {
+            error!("Failed to update download {id}: {err}");
+        }
+    }
+
+    pub async fn pause(&self, id: &str) -> Result<QueuedDownload, DownloadQueueError> {
+        self.stop_task(id).await;
+        let download = self
+            .modify(id, |download| match download.status {
+                DownloadStatus::Queued | DownloadStatus::Running => {
+                    download.status = DownloadStatus::Paused;
+                    Ok(())
+                }
+                status => Err(DownloadQueueError::InvalidTransition(
+                    id.to_string(),
+                    "paused",
+                    status,
+                )),
+            })
+            .await?;
+
+        self.wake.notify_one();
+        Ok(download)
+    }
+
+    /// Queue a paused or failed download again, `action` names the transition in errors.
+    async fn requeue(
+        &self,
+        id: &str,
+        from: DownloadStatus,
+        action: &'static str,
+    ) -> Result<QueuedDownload, DownloadQueueError> {
+        let download = self
+            .modify(id, |download| {
+                if download.status != from {
+                    return Err(DownloadQueueError::InvalidTransition(
+                        id.to_string(),
+                        action,
+                        download.status,
+                    ));
+                }
+                download.status = DownloadStatus::Queued;
+                download.error = None;
+                Ok(())
+            })
+            .await?;
+
+        self.wake.notify_one();
+        Ok(download)
+    }
+
+    pub async fn resume(&self, id: &str) -> Result<QueuedDownload, DownloadQueueError> {
+        self.requeue(id, DownloadStatus::Paused, "resumed").await
+    }
+
+    pub async fn retry(&self, id: &str) -> Result<QueuedDownload, DownloadQueueError> {
+        self.requeue(id, DownloadStatus::Failed, "retried").await
+    }
+
+    /// Remove a download from the queue, stopping it if it is running.
+    ///
+    /// Files it already wrote are left in place.
+    pub async fn remove(&self, id: &str) -> Result<(), DownloadQueueError> {
+        self.stop_task(id).await;
+
+        let mut downloads = self.downloads.lock().await;
+        let len = downloads.len();
+        downloads.retain(|download| download.id != id);
+        if downloads.len() == len {
+            return Err(DownloadQueueError::UnknownDownload(id.to_string()));
+        }
+
+        self.save(&downloads)?;
+        self.wake.notify_one();
+        Ok(())
+    }
+}
+
+fn emit_download(app: &AppHandle, download: &QueuedDownload) {
+    if let Err(err) = app.emit("download-queue", download) {
+        warn!("Failed to emit download update: {err}");
+    }
+}
+
+/// Record the outcome of an attempt, unless the download was paused or removed meanwhile.
+async fn finish(
+    app: &AppHandle,
+    id: &str,
+    progress: &SharedProgress,
+    result: Result<(), files::DownloadError>,
+) {
+    let queue = app.state::<DownloadQueue>();
+    if queue.tasks.lock().await.remove(id).is_none() {
+        return;
+    }
+
+    let (status, error) = match result {
+        Ok(()) => (DownloadStatus::Completed, None),
+        Err(err) => {
+            warn!("Download {id} failed: {err}");
+            (DownloadStatus::Failed, Some(err.to_string()))
+        }
+    };
+
+    let written = std::mem::take(&mut progress.lock().unwrap_or_else(|e| e.into_inner()).written);
+    match queue
+        .modify(id, |download| {
+            download.status = status;
+            download.error = error;
+            download.written = written;
+            Ok(())
+        })
+        .await
+    {
+        Ok(download) => emit_download(app, &download),
+        Err(err) => error!("Failed to update download {id}: {err}"),
+    }
+
+    queue.wake.notify_one();
+}
+
+/// Start queued downloads while there are free slots.
+async fn start_queued(app: &AppHandle) {
+    let queue = app.state::<DownloadQueue>();
+    let mut tasks = queue.tasks.lock().await;
+
+    let queued: Vec<String> = queue
+        .list()
+        .await
+        .into_iter()
+        .filter(|download| download.status == DownloadStatus::Queued)
+        .map(|download| download.id)
+        .take(MAX_CONCURRENT_DOWNLOADS.saturating_sub(tasks.len()))
+        .collect();
+
+    for id in queued {
+        let download = match queue
+            .modify(&id, |download| {
+                download.status = DownloadStatus::Running;
+                download.attempts += 1;
+                Ok(())
+            })
+            .await
+        {
+            Ok(download) => download,
+            Err(err) => {
+                error!("Failed to start download {id}: {err}");
+                continue;
+            }
+        };
+        emit_download(app, &download);
+
+        let progress = Arc::new(std::sync::Mutex::new(DownloadProgress {
+            written: download.written.clone(),
+            ..Default::default()
+        }));
+
+        let app = app.clone();
+        let task_progress = progress.clone();
+        let task = tauri::async_runtime::spawn(async move {
+            let result = files::download(
+                &download.file_access,
+                download.dest.clone(),
+                download.restore_metadata,
+                &task_progress,
+                app.state(),
+            )
+            .await;
+            finish(&app, &download.id, &task_progress, result).await;
+        });
+        tasks.insert(id, (task, progress));
+    }
+}
+
+/// Run queued downloads, for the lifetime of the app.
+pub async fn run(app: AppHandle) {
+    let queue = app.state::<DownloadQueue>();
+    loop {
+        start_queued(&app).await;
+        queue.wake.notified().await;
+    }
+}
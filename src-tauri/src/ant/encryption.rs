@@ -1,3 +1,4 @@
+use crate::ant::files::collect_files_from_directory;
 use autonomi::self_encryption::EncryptionStream;
 use std::path::PathBuf;
 use tracing::{error, info};
@@ -11,6 +12,35 @@ pub(crate) enum EncryptionError {
 pub(crate) async fn encrypt_file_or_folder(
     path: PathBuf,
     is_public: bool,
+) -> Result<Vec<EncryptionStream>, EncryptionError> {
+    // Encrypt folders file by file, so the ignore rules apply
+    if path.is_dir() {
+        let files = collect_files_from_directory(path)
+            .await
+            .map_err(|err| EncryptionError::IO(err.to_string()))?;
+
+        return encrypt_scanned_files(&files, is_public).await;
+    }
+
+    encrypt_files(path, is_public).await
+}
+
+/// Encrypt the files found by a folder scan, as (relative_path, absolute_path).
+pub(crate) async fn encrypt_scanned_files(
+    files: &[(PathBuf, PathBuf)],
+    is_public: bool,
+) -> Result<Vec<EncryptionStream>, EncryptionError> {
+    let mut encryption_streams = vec![];
+    for (_, file_path) in files {
+        encryption_streams.extend(encrypt_files(file_path.clone(), is_public).await?);
+    }
+
+    Ok(encryption_streams)
+}
+
+async fn encrypt_files(
+    path: PathBuf,
+    is_public: bool,
 ) -> Result<Vec<EncryptionStream>, EncryptionError> {
     let encryption_results = autonomi::self_encryption::encrypt_directory_files(path, is_public)
         .await
@@ -0,0 +1,361 @@
+//! Metadata of individually uploaded files.
+//!
+//! The vault user data only records a name per individual file, so size and timestamps captured
+//! at upload time are written to a second vault, owned by a key derived from the vault key, and
+//! also kept locally keyed by the file address. When neither has an entry (e.g. the file was added
+//! by another app) the original size is recovered from the datamap, which does not require
+//! downloading the file contents.
+
+use crate::ant::app_data;
+use crate::ant::chunk_cache;
+use crate::ant::files::FileAccess;
+use crate::ant::vault::{self, VaultUpdate};
+use crate::ant::vault_registry::derive_vault_key;
+use autonomi::client::payment::Receipt;
+use autonomi::client::quote::StoreQuote;
+use autonomi::files::Metadata;
+use autonomi::vault::{
+    vault_content_type_from_app_name, VaultContentType, VaultError, VaultSecretKey,
+};
+use autonomi::{Bytes, Client};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{info, warn};
+
+static FILE_METADATA_VAULT_CONTENT_IDENTIFIER: LazyLock<VaultContentType> =
+    LazyLock::new(|| vault_content_type_from_app_name("FileMetadata"));
+
+pub struct FileMetadataStore {
+    store_dir: PathBuf,
+}
+
+impl FileMetadataStore {
+    pub fn new(base_dir: &Path) -> Result<Self, std::io::Error> {
+        let store_dir = base_dir.join("file_metadata");
+        fs::create_dir_all(&store_dir)?;
+        Ok(Self { store_dir })
+    }
+
+    pub fn save(
+        &self,
+        file_access: &FileAccess,
+        metadata: &Metadata,
+    ) -> Result<(), std::io::Error> {
+        let json = serde_json::to_vec(metadata)?;
+        app_data::write_atomic(&self.store_dir.join(file_access.local_key()), &json)
+    }
+
+    pub fn load(&self, file_access: &FileAccess) -> Option<Metadata> {
+        let contents = fs::read_to_string(self.store_dir.join(file_access.local_key())).ok()?;
+        serde_json::from_str(&contents).ok()
+    }
+}
+
+/// Metadata of a vault's individual files, keyed by [`FileAccess::local_key`].
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct VaultFileMetadata {
+    files: HashMap<String, Metadata>,
+}
+
+impl VaultFileMetadata {
+    /// Fetch the file metadata of a vault, empty if it was never written or can't be read.
+    pub async fn fetch(client: &Client, vault_key: &VaultSecretKey) -> Self {
+        let (bytes, content_type) = match client.vault_get(&metadata_vault_key(vault_key)).await {
+            Ok(vault) => vault,
+            Err(err) => {
+                info!("No file metadata in vault: {err}");
+                return Self::default();
+            }
+        };
+
+        if content_type != *FILE_METADATA_VAULT_CONTENT_IDENTIFIER {
+            warn!("Unexpected content type {content_type} in file metadata vault");
+            return Self::default();
+        }
+
+        serde_json::from_slice(&bytes)
+            .inspect_err(|err| warn!("Failed to decode file metadata vault: {err}"))
+            .unwrap_or_default()
+    }
+
+    pub fn get(&self, file_access: &FileAccess) -> Option<&Metadata> {
+        self.files.get(&file_access.local_key())
+    }
+
+    pub fn insert(&mut self, file_access: &FileAccess, metadata: Metadata) {
+        self.files.insert(file_access.local_key(), metadata);
+    }
+
+    fn to_bytes(&self) -> Bytes {
+        Bytes::from(serde_json::to_vec(self).expect("file metadata always serializes"))
+    }
+}
+
+/// A pending write of a file's metadata to the metadata vault.
+#[derive(Debug, Clone)]
+pub struct MetadataVaultUpdate {
+    file_access: FileAccess,
+    metadata: Metadata,
+    update: VaultUpdate,
+}
+
+fn metadata_vault_key(vault_key: &VaultSecretKey) -> VaultSecretKey {
+    derive_vault_key(vault_key, "file-metadata")
+}
+
+/// Quote adding a file's metadata to the metadata vault, to be paid together with the upload.
+pub async fn quote_vault_metadata(
+    client: &Client,
+    vault_key: &VaultSecretKey,
+    file_access: FileAccess,
+    metadata: Metadata,
+) -> Result<(StoreQuote, MetadataVaultUpdate), VaultError> {
+    let mut stored = VaultFileMetadata::fetch(client, vault_key).await;
+    stored.insert(&file_access, metadata.clone());
+
+    let quote =
+        vault::vault_quote(client, stored.to_bytes(), &metadata_vault_key(vault_key)).await?;
+
+    let update = VaultUpdate {
+        new_graph_entries: quote.new_graph_entries,
+        new_scratchpad_derivations: quote.new_scratchpad_derivations,
+        file_metadata: None,
+    };
+
+    Ok((
+        quote.quote,
+        MetadataVaultUpdate {
+            file_access,
+            metadata,
+            update,
+        },
+    ))
+}
+
+/// Add a file's metadata to the metadata vault, with a receipt covering the quote.
+pub async fn write_vault_metadata(
+    client: &Client,
+    vault_key: &VaultSecretKey,
+    pending: MetadataVaultUpdate,
+    receipt: Receipt,
+) -> Result<(), VaultError> {
+    // Re-read the vault, other uploads may have added files since the quote
+    let mut stored = VaultFileMetadata::fetch(client, vault_key).await;
+    stored.insert(&pending.file_access, pending.metadata);
+
+    vault::vault_write(
+        client,
+        stored.to_bytes(),
+        *FILE_METADATA_VAULT_CONTENT_IDENTIFIER,
+        &metadata_vault_key(vault_key),
+        receipt,
+        pending.update,
+    )
+    .await
+}
+
+/// Add a file's metadata to the metadata vault when that needs no payment, i.e. the vault
+/// already exists and has room for it.
+pub async fn write_vault_metadata_if_free(
+    client: &Client,
+    vault_key: &VaultSecretKey,
+    file_access: FileAccess,
+    metadata: Metadata,
+) {
+    let result = async {
+        let (quote, pending) =
+            quote_vault_metadata(client, vault_key, file_access, metadata).await?;
+        if !quote.0.is_empty() {
+            info!("Skipping file metadata, the metadata vault needs to grow first");
+            return Ok(());
+        }
+        write_vault_metadata(client, vault_key, pending, Receipt::default()).await
+    }
+    .await;
+
+    if let Err(err) = result {
+        warn!("Failed to write file metadata to vault: {err}");
+    }
+}
+
+/// Quote writing the metadata of the file at `path` to the metadata vault.
+///
+/// Metadata is a convenience, so failures are logged and the upload goes ahead without it.
+pub async fn quote_file_metadata(
+    client: &Client,
+    vault_key: &VaultSecretKey,
+    file_access: FileAccess,
+    path: &Path,
+) -> Option<(StoreQuote, MetadataVaultUpdate)> {
+    let metadata = metadata_from_path(path)
+        .inspect_err(|err| warn!("Failed to read metadata of {path:?}: {err}"))
+        .ok()?;
+
+    quote_vault_metadata(client, vault_key, file_access, metadata)
+        .await
+        .inspect_err(|err| warn!("Failed to quote file metadata vault: {err}"))
+        .ok()
+}
+
+/// Write the metadata of an uploaded file to the metadata vault, paid with `receipt` if it was
+/// quoted, otherwise only if that is free.
+pub async fn store_file_metadata(
+    client: &Client,
+    vault_key: &VaultSecretKey,
+    file_access: FileAccess,
+    path: &Path,
+    pending: Option<Box<MetadataVaultUpdate>>,
+    receipt: Receipt,
+) {
+    match pending {
+        Some(pending) => {
+            if let Err(err) = write_vault_metadata(client, vault_key, *pending, receipt).await {
+                warn!("Failed to write file metadata to vault: {err}");
+            }
+        }
+        None => match metadata_from_path(path) {
+            Ok(metadata) => {
+                write_vault_metadata_if_free(client, vault_key, file_access, metadata).await
+            }
+            Err(err) => warn!("Failed to read metadata of {path:?}: {err}"),
+        },
+    }
+}
+
+/// Capture size, creation and modification time of a file on disk.
+pub fn metadata_from_path(path: &Path) -> Result<Metadata, std::io::Error> {
+    let fs_metadata = fs::metadata(path)?;
+
+    let to_secs = |time: std::io::Result<SystemTime>| {
+        time.ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|duration| duration.as_secs())
+    };
+
+    let mut metadata = Metadata::new_with_size(fs_metadata.len());
+    if let Some(modified) = to_secs(fs_metadata.modified()) {
+        metadata.modified = modified;
+        // Not every platform tracks creation time, fall back to the modification time
+        metadata.created = to_secs(fs_metadata.created()).unwrap_or(modified);
+    }
+
+    Ok(metadata)
+}
+
+/// Original size of a file, read from its datamap.
+///
+/// Datamaps that were shrunk into a child datamap are resolved first, as those only describe the
+/// size of the serialized parent datamap.
+pub async fn datamap_file_size(client: &Client, file_access: &FileAccess) -> Option<u64> {
+    chunk_cache::root_data_map(client, file_access)
+        .await
+        .inspect_err(|err| warn!("Failed to resolve datamap of {file_access:?}: {err}"))
+        .ok()
+        .map(|data_map| data_map.original_file_size() as u64)
+}
+
+/// Metadata recorded at upload time, in the vault or locally.
+pub fn recorded_file_metadata(
+    vault_metadata: &VaultFileMetadata,
+    store: Option<&FileMetadataStore>,
+    file_access: &FileAccess,
+) -> Option<Metadata> {
+    vault_metadata
+        .get(file_access)
+        .cloned()
+        .or_else(|| store.and_then(|store| store.load(file_access)))
+}
+
+/// Resolve the metadata of an individual file, preferring what was recorded at upload time.
+pub async fn resolve_file_metadata(
+    client: &Client,
+    vault_metadata: &VaultFileMetadata,
+    store: Option<&FileMetadataStore>,
+    file_access: &FileAccess,
+) -> Metadata {
+    match recorded_file_metadata(vault_metadata, store, file_access) {
+        Some(metadata) => metadata,
+        None => datamap_metadata(client, file_access).await,
+    }
+}
+
+/// Metadata with the original file size read from the datamap.
+pub async fn datamap_metadata(client: &Client, file_access: &FileAccess) -> Metadata {
+    Metadata::new_with_size(datamap_file_size(client, file_access).await.unwrap_or(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use autonomi::data::DataAddress;
+    use autonomi::self_encryption::EncryptionStream;
+    use autonomi::XorName;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_recorded_metadata_prefers_vault() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = FileMetadataStore::new(temp_dir.path()).unwrap();
+        let file_access = FileAccess::Public(DataAddress::new(XorName::from_content(b"file")));
+        let other = FileAccess::Public(DataAddress::new(XorName::from_content(b"other")));
+
+        store
+            .save(&file_access, &Metadata::new_with_size(1))
+            .unwrap();
+        store.save(&other, &Metadata::new_with_size(2)).unwrap();
+
+        let mut vault_metadata = VaultFileMetadata::default();
+        vault_metadata.insert(&file_access, Metadata::new_with_size(10));
+
+        let recorded = |file_access| {
+            recorded_file_metadata(&vault_metadata, Some(&store), file_access).map(|m| m.size)
+        };
+        assert_eq!(recorded(&file_access), Some(10));
+        assert_eq!(recorded(&other), Some(2));
+
+        let unknown = FileAccess::Public(DataAddress::new(XorName::from_content(b"unknown")));
+        assert_eq!(recorded(&unknown), None);
+    }
+
+    /// Whether the datamap autonomi's encryption produces for `size` bytes holds a child
+    /// datamap, and the size read from it.
+    fn encrypted_size(size: usize) -> (bool, u64) {
+        let content: Vec<u8> = (0..size).map(|i| (i % 251) as u8).collect();
+        let (mut stream, data_map_chunk) =
+            EncryptionStream::new_in_memory(Bytes::from(content), false).unwrap();
+
+        let mut chunks = HashMap::new();
+        while let Some(batch) = stream.next_batch(64) {
+            chunks.extend(batch.into_iter().map(|chunk| (*chunk.name(), chunk.value)));
+        }
+
+        let is_child = Client::deserialize_data_map(&data_map_chunk.0.value)
+            .unwrap()
+            .is_child();
+        let data_map = tauri::async_runtime::block_on(chunk_cache::resolve_data_map(
+            &data_map_chunk.0.value,
+            |names| {
+                let level = names
+                    .iter()
+                    .map(|name| (*name, chunks[name].clone()))
+                    .collect();
+                async move { Ok(level) }
+            },
+        ))
+        .unwrap();
+        (is_child, data_map.original_file_size() as u64)
+    }
+
+    #[test]
+    fn test_datamap_file_size() {
+        assert_eq!(encrypted_size(10_000), (false, 10_000));
+
+        // More than 3 chunks, the datamap chunk holds a child datamap
+        let size = 3 * self_encryption::MAX_CHUNK_SIZE + 1_000;
+        assert_eq!(encrypted_size(size), (true, size as u64));
+    }
+}
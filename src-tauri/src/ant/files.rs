@@ -1,16 +1,21 @@
 use crate::ant::app_data;
+use crate::ant::archive_cache::{self, ArchiveCache};
+use crate::ant::archive_metadata;
 use crate::ant::cached_payments::PaymentCache;
+use crate::ant::chunk_cache;
 use crate::ant::client::SharedClient;
-use crate::ant::encryption::encrypt_file_or_folder;
+use crate::ant::encryption::{encrypt_file_or_folder, encrypt_scanned_files};
+use crate::ant::file_metadata::{self, FileMetadataStore};
+use crate::ant::folder_scan::{self, scan_directory, FileReport};
 use crate::ant::quote::combine_quotes;
 use crate::ant::receipt_utils::validate_receipt_coverage_with_content_addresses;
 use crate::ant::stream::content_addresses_from_encryption_stream;
 use crate::ant::upload::batch_upload_encryption_stream;
-use crate::ant::{local_storage, vault};
+use crate::ant::vault_registry::SessionKey;
+use crate::ant::{local_index, local_storage, vault};
 use autonomi::chunk::DataMapChunk;
 use autonomi::client::payment::{PaymentOption, Receipt};
 use autonomi::client::quote::DataTypes;
-use autonomi::client::vault::key::vault_key_from_signature_hex;
 use autonomi::client::vault::{UserData, VaultSecretKey};
 use autonomi::client::GetError;
 use autonomi::data::DataAddress;
@@ -20,15 +25,33 @@ use autonomi::{Amount, Bytes, XorName};
 use hex;
 use serde::{Deserialize, Serialize};
 use serde_json;
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, BTreeSet};
 use std::path::PathBuf;
-use std::sync::OnceLock;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
 use tauri::{AppHandle, Emitter, State};
 use thiserror::Error as ThisError;
 use tokio::fs;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
 use tracing::{error, info, warn};
 
+/// Maximum number of vault archives fetched from the network at the same time.
+const MAX_CONCURRENT_ARCHIVE_FETCHES: usize = 8;
+/// Maximum number of datamaps fetched at the same time to size individual vault files.
+const MAX_CONCURRENT_METADATA_FETCHES: usize = 8;
+/// Time allowed for fetching a single archive before it is reported as failed.
+const ARCHIVE_FETCH_TIMEOUT: Duration = Duration::from_secs(60);
+/// Delays before each background retry of archives that failed to load.
+const ARCHIVE_RETRY_DELAYS: [Duration; 3] = [
+    Duration::from_secs(10),
+    Duration::from_secs(30),
+    Duration::from_secs(90),
+];
+
 static PAYMENT_CACHE: OnceLock<Result<PaymentCache, String>> = OnceLock::new();
+static ARCHIVE_CACHE: OnceLock<Result<ArchiveCache, String>> = OnceLock::new();
+static FILE_METADATA_STORE: OnceLock<Result<FileMetadataStore, String>> = OnceLock::new();
 
 pub fn get_payment_cache() -> Result<&'static PaymentCache, &'static str> {
     PAYMENT_CACHE
@@ -44,7 +67,96 @@ pub fn get_payment_cache() -> Result<&'static PaymentCache, &'static str> {
         .map_err(|_| "Payment cache not available")
 }
 
-#[derive(Deserialize, Clone)]
+pub fn get_archive_cache() -> Result<&'static ArchiveCache, &'static str> {
+    ARCHIVE_CACHE
+        .get_or_init(|| {
+            app_data::data_dir()
+                .ok_or_else(|| "Could not get app data directory".to_string())
+                .and_then(|dir| {
+                    ArchiveCache::new(&dir)
+                        .map_err(|e| format!("Failed to create archive cache: {}", e))
+                })
+        })
+        .as_ref()
+        .map_err(|_| "Archive cache not available")
+}
+
+pub fn get_file_metadata_store() -> Result<&'static FileMetadataStore, &'static str> {
+    FILE_METADATA_STORE
+        .get_or_init(|| {
+            app_data::data_dir()
+                .ok_or_else(|| "Could not get app data directory".to_string())
+                .and_then(|dir| {
+                    FileMetadataStore::new(&dir)
+                        .map_err(|e| format!("Failed to create file metadata store: {}", e))
+                })
+        })
+        .as_ref()
+        .map_err(|_| "File metadata store not available")
+}
+
+/// Record the metadata of an individually uploaded file, so the vault can show it later.
+fn record_upload_metadata(file_access: &FileAccess, path: &std::path::Path) {
+    let metadata = match file_metadata::metadata_from_path(path) {
+        Ok(metadata) => metadata,
+        Err(err) => {
+            warn!("Failed to read metadata of {path:?}: {err}");
+            return;
+        }
+    };
+
+    if let Ok(store) = get_file_metadata_store() {
+        if let Err(err) = store.save(file_access, &metadata) {
+            warn!("Failed to store metadata of {path:?}: {err}");
+        }
+    }
+    local_index::record_details(file_access, Some(path), metadata.size);
+}
+
+/// Record the total size of an uploaded archive, and its folder if a single one was uploaded.
+fn record_archive_details(archive_access: &FileAccess, files: &[File], total_size: u64) {
+    let source_path = match files {
+        [file] => Some(file.path.as_path()),
+        _ => None,
+    };
+    local_index::record_details(archive_access, source_path, total_size);
+}
+
+/// Look up the decoded file listing of an archive in the local archive cache.
+///
+/// Files are reported with the same `is_loaded` as a listing fetched from the network.
+fn cached_archive_files(
+    cache_key: &str,
+    listing_key: &VaultSecretKey,
+    is_loaded: bool,
+) -> Option<Vec<FileMetadata>> {
+    let cached = get_archive_cache().ok()?.load(cache_key, listing_key)?;
+
+    Some(
+        cached
+            .files
+            .into_iter()
+            .map(|file| FileMetadata { is_loaded, ..file })
+            .collect(),
+    )
+}
+
+/// Store the decoded file listing of an archive in the local archive cache.
+fn cache_archive_files(
+    cache_key: &str,
+    archive_name: &str,
+    is_private: bool,
+    files: &[FileMetadata],
+    listing_key: &VaultSecretKey,
+) {
+    if let Ok(cache) = get_archive_cache() {
+        if let Err(err) = cache.save(cache_key, archive_name, is_private, files, listing_key) {
+            warn!("Failed to cache archive {archive_name}: {err}");
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct File {
     pub name: String,
     pub path: PathBuf,
@@ -100,10 +212,27 @@ pub enum UploadProgress {
     },
 }
 
+/// Id and outcome of a finished upload from the payload of an `upload-progress` event, `None`
+/// while the upload is still running. Completed uploads yield how to access what was uploaded,
+/// failed and cancelled uploads their error.
+pub fn finished_upload(payload: &str) -> Option<(String, Result<Option<FileAccess>, String>)> {
+    let progress = serde_json::from_str::<serde_json::Value>(payload).ok()?;
+    let upload_id = progress["upload_id"].as_str()?.to_string();
+
+    let outcome = match progress["type"].as_str() {
+        Some("Completed") => Ok(serde_json::from_value(progress["file_access"].clone()).ok()),
+        Some("Failed") => Err(progress["error"].as_str().unwrap_or_default().to_string()),
+        Some("Cancelled") => Err("The upload was cancelled".to_string()),
+        _ => return None,
+    };
+
+    Some((upload_id, outcome))
+}
+
 #[derive(ThisError, Debug)]
 pub enum UploadError {
     #[error("Could not connect to the network: {0:?}")]
-    Connect(#[from] autonomi::client::ConnectError),
+    Connect(#[from] crate::ant::client::ClientError),
     #[error("Could not read file: {0:?}")]
     Read(PathBuf),
     #[error("Failed to encrypt data: {0}")]
@@ -118,30 +247,46 @@ pub enum UploadError {
     Serialization(String),
     #[error("Failed to put data: {0}")]
     Put(String),
+    #[error("Could not load archive: {0}")]
+    ArchiveGet(String),
+    #[error("Could not read the vault: {0}")]
+    UserDataGet(String),
 }
 
 #[derive(ThisError, Debug)]
 pub enum VaultError {
     #[error("Could not connect to the network: {0:?}")]
-    Connect(#[from] autonomi::client::ConnectError),
+    Connect(#[from] crate::ant::client::ClientError),
     #[error("Could not retrieve user data: {0:?}")]
     UserDataGet(#[from] UserDataVaultError),
     #[error("Could not retrieve data: {0:?}")]
     DataGet(#[from] GetError),
     #[error("File not found in vault")]
     FileNotFound,
+    #[error("Could not update vault: {0}")]
+    Update(String),
 }
 
 #[derive(ThisError, Debug)]
 pub enum DownloadError {
     #[error("Could not connect to the network: {0:?}")]
-    Connect(#[from] autonomi::client::ConnectError),
+    Connect(#[from] crate::ant::client::ClientError),
     #[error("Could not download file: {0:?}")]
     Download(#[from] autonomi::client::files::DownloadError),
     #[error("Could not get data: {0:?}")]
     Get(#[from] autonomi::client::GetError),
     #[error("Could not analyze address: {0:?}")]
     Analysis(#[from] autonomi::client::analyze::AnalysisError),
+    #[error("No archive entries match {0:?}")]
+    NoMatchingEntries(Vec<String>),
+    #[error("Select the files or folders of the archive to download")]
+    EmptySelection,
+    #[error("Could not verify download: {0}")]
+    Verification(String),
+    #[error("Refusing to write through a link: {0:?}")]
+    UnsafePath(PathBuf),
+    #[error("{0}")]
+    ChunkCache(#[from] chunk_cache::ChunkCacheError),
 }
 
 pub async fn read_file_to_bytes(file_path: PathBuf) -> Result<Bytes, UploadError> {
@@ -178,47 +323,12 @@ pub async fn calculate_total_size(files: &[File]) -> Result<u64, UploadError> {
 
 /// Collects files from a directory and its subdirectories, preserving relative paths.
 /// Returns a vector of tuples containing (relative_path, absolute_path).
+///
+/// Only regular files are returned, see [`scan_directory`] for what is skipped.
 pub async fn collect_files_from_directory(
     dir_path: PathBuf,
 ) -> Result<Vec<(PathBuf, PathBuf)>, UploadError> {
-    let mut files = Vec::new();
-    let mut queue = VecDeque::new();
-
-    // Get the parent directory to calculate relative paths correctly
-    let base_dir = dir_path.parent().unwrap_or(&dir_path);
-    let _dir_name = dir_path.file_name().unwrap_or_default();
-
-    // Start with the root directory
-    queue.push_back(dir_path.clone());
-
-    while let Some(current_dir) = queue.pop_front() {
-        let mut entries = fs::read_dir(&current_dir)
-            .await
-            .map_err(|_| UploadError::Read(current_dir.clone()))?;
-
-        while let Some(entry) = entries
-            .next_entry()
-            .await
-            .map_err(|_| UploadError::Read(current_dir.clone()))?
-        {
-            let path = entry.path();
-
-            if path.is_dir() {
-                // Add directory to the queue for processing
-                queue.push_back(path);
-            } else {
-                // Calculate relative path including the root folder name
-                if let Ok(rel_path) = path.strip_prefix(base_dir) {
-                    files.push((rel_path.to_path_buf(), path));
-                } else {
-                    // Fallback if strip_prefix fails
-                    files.push((path.clone(), path));
-                }
-            }
-        }
-    }
-
-    Ok(files)
+    Ok(scan_directory(dir_path).await?.files)
 }
 
 pub async fn start_private_single_file_upload(
@@ -231,12 +341,12 @@ pub async fn start_private_single_file_upload(
     shared_client: State<'_, SharedClient>,
     pending_uploads: Option<&tokio::sync::Mutex<crate::PendingUploads>>,
 ) -> Result<(), UploadError> {
-    println!(
+    info!(
         ">>> start_single_file_upload called with upload_id: {}, use_cached_receipts: {}",
         upload_id, use_cached_receipts
     );
     let client = shared_client.get_client().await.map_err(|e| {
-        println!(">>> Failed to get client: {:?}", e);
+        error!(">>> Failed to get client: {:?}", e);
         e
     })?;
 
@@ -404,9 +514,22 @@ pub async fn start_private_single_file_upload(
         // Merge vault quote with store quote
         total_store_quote.0.extend(vault_quote_result.quote.0);
 
+        let file_metadata = file_metadata::quote_file_metadata(
+            &client,
+            secret_key,
+            FileAccess::Private(data_map_chunk.clone()),
+            &file.path,
+        )
+        .await
+        .map(|(quote, pending)| {
+            total_store_quote.0.extend(quote.0);
+            Box::new(pending)
+        });
+
         vault_update = vault::VaultUpdate {
             new_graph_entries: vault_quote_result.new_graph_entries,
             new_scratchpad_derivations: vault_quote_result.new_scratchpad_derivations,
+            file_metadata,
         };
     }
 
@@ -466,6 +589,7 @@ pub async fn start_private_single_file_upload(
     // If cost is 0, this is a duplicate file - skip upload and mark as completed
     if total_cost == Amount::ZERO {
         info!(">>> Duplicate file detected (cost=0), marking as completed immediately for upload_id: {}", upload_id);
+        record_upload_metadata(&FileAccess::Private(data_map_chunk.clone()), &file.path);
         // Emit completion immediately for duplicate files
         app.emit(
             "upload-progress",
@@ -492,7 +616,7 @@ pub async fn start_private_single_file_upload(
             data_map_chunk,
             total_store_quote,
             vault_update,
-            vault_secret_key.cloned(),
+            vault_secret_key.map(SessionKey::from),
             add_to_vault,
             cached_receipt_opt,
         );
@@ -584,15 +708,27 @@ pub async fn execute_private_single_file_upload(
                         &client,
                         vault_data,
                         &secret_key,
-                        receipt,
+                        receipt.clone(),
                         vault_update.new_graph_entries,
                         vault_update.new_scratchpad_derivations,
                     )
                     .await
                     .map_err(|err| UploadError::StoreQuote(err.to_string()))?;
+
+                    file_metadata::store_file_metadata(
+                        &client,
+                        secret_key,
+                        FileAccess::Private(datamap.clone()),
+                        &file.path,
+                        vault_update.file_metadata,
+                        receipt,
+                    )
+                    .await;
                 }
             }
 
+            record_upload_metadata(&FileAccess::Private(datamap.clone()), &file.path);
+
             // Store file locally
             local_storage::write_local_private_file(
                 datamap.to_hex(),
@@ -780,7 +916,7 @@ pub async fn start_public_single_file_upload(
             .get_store_quotes(DataTypes::Chunk, all_content_addresses.into_iter())
             .await
             .map_err(|err| {
-                println!(">>> Failed to get store quotes: {}", err);
+                error!(">>> Failed to get store quotes: {}", err);
                 UploadError::StoreQuote(err.to_string())
             })?
     };
@@ -825,9 +961,22 @@ pub async fn start_public_single_file_upload(
         // Merge vault quote with store quote
         total_store_quote.0.extend(vault_quote_result.quote.0);
 
+        let file_metadata = file_metadata::quote_file_metadata(
+            &client,
+            secret_key,
+            FileAccess::Public(DataAddress::new(*data_address.xorname())),
+            &file.path,
+        )
+        .await
+        .map(|(quote, pending)| {
+            total_store_quote.0.extend(quote.0);
+            Box::new(pending)
+        });
+
         vault_update = vault::VaultUpdate {
             new_graph_entries: vault_quote_result.new_graph_entries,
             new_scratchpad_derivations: vault_quote_result.new_scratchpad_derivations,
+            file_metadata,
         };
     }
 
@@ -884,11 +1033,15 @@ pub async fn start_public_single_file_upload(
         error!(">>> Failed to emit upload-quote event: {}", err);
         UploadError::EmitEvent(err.to_string())
     })?;
-    println!(">>> Successfully emitted upload-quote event");
+    info!(">>> Successfully emitted upload-quote event");
 
     // If no payment required, proceed with upload
     if total_cost == Amount::ZERO {
         info!(">>> Duplicate public file detected (cost=0), marking as completed immediately for upload_id: {}", upload_id);
+        record_upload_metadata(
+            &FileAccess::Public(DataAddress::new(*data_map_chunk.0.name())),
+            &file.path,
+        );
         // Emit completion immediately for duplicate files
         app.emit(
             "upload-progress",
@@ -917,7 +1070,7 @@ pub async fn start_public_single_file_upload(
             total_store_quote,
             vault_update,
             add_to_vault,
-            vault_secret_key.cloned(),
+            vault_secret_key.map(SessionKey::from),
             cached_receipt_opt,
         );
     }
@@ -1044,7 +1197,7 @@ pub async fn execute_public_single_file_upload(
                         &client,
                         vault_data,
                         &secret_key,
-                        receipt,
+                        receipt.clone(),
                         vault_update.new_graph_entries,
                         vault_update.new_scratchpad_derivations,
                     )
@@ -1054,12 +1207,24 @@ pub async fn execute_public_single_file_upload(
                         UploadError::Scratchpad(err.to_string())
                     })?;
 
+                    file_metadata::store_file_metadata(
+                        &client,
+                        secret_key,
+                        FileAccess::Public(public_data_address),
+                        &file.path,
+                        vault_update.file_metadata,
+                        receipt,
+                    )
+                    .await;
+
                     info!(">>> Successfully added public file to vault");
                 } else {
                     warn!(">>> Warning: add_to_vault=true but no vault_secret_key provided");
                 }
             }
 
+            record_upload_metadata(&FileAccess::Public(public_data_address), &file.path);
+
             // Store the file locally for future reference
             local_storage::write_local_public_file(
                 hex::encode(public_data_address.xorname().0),
@@ -1127,13 +1292,14 @@ pub async fn start_private_archive_upload(
 
     let client = shared_client.get_client().await?;
 
-    // Calculate total size and collect files
-    let total_size = calculate_total_size(&files).await?;
+    // The size is summed up while the folders are scanned
+    let mut total_size = 0;
     let total_files = files.len();
 
     // Use encryption streaming for private archives
     let mut private_archive = PrivateArchive::new();
     let mut all_content_addresses = Vec::new();
+    let mut file_report: Vec<FileReport> = Vec::new();
 
     for file in &files {
         let path_metadata = fs::metadata(&file.path)
@@ -1141,20 +1307,24 @@ pub async fn start_private_archive_upload(
             .map_err(|_| UploadError::Read(file.path.clone()))?;
 
         if path_metadata.is_dir() {
-            // Handle directory - encrypt_file_or_folder will handle all files in the directory
+            // Scan the directory once, for its files, placeholders and report
             info!(
                 ">>> Creating encryption streams for directory: {:?}",
                 file.path
             );
 
-            let mut encryption_streams = encrypt_file_or_folder(file.path.clone(), false)
+            let scan = scan_directory(file.path.clone()).await?;
+            total_size += scan.total_size().await?;
+
+            let mut encryption_streams = encrypt_scanned_files(&scan.files, false)
                 .await
                 .map_err(|err| UploadError::Encryption(format!("{:?}", err)))?;
 
             // Each stream corresponds to a file in the directory
             for stream in &mut encryption_streams {
                 let file_path = PathBuf::from(&stream.file_path);
-                let file_metadata = stream.metadata.clone();
+                let file_metadata = archive_metadata::metadata_from_disk(&file_path)
+                    .map_err(|_| UploadError::Read(file_path.clone()))?;
 
                 // Get content addresses from stream
                 let content_addresses = content_addresses_from_encryption_stream(stream).await;
@@ -1174,8 +1344,21 @@ pub async fn start_private_archive_upload(
 
                 private_archive.add_file(relative_path, datamap, file_metadata);
             }
+
+            for placeholder in &scan.placeholders {
+                let (datamap, chunks) = placeholder.encrypt(false)?;
+                all_content_addresses
+                    .extend(chunks.iter().map(|chunk| (*chunk.name(), chunk.size())));
+                private_archive.add_file(
+                    placeholder.relative_path.clone(),
+                    datamap,
+                    placeholder.metadata.clone(),
+                );
+            }
+            file_report.extend(scan.report);
         } else {
             // Handle single file
+            total_size += path_metadata.len();
             info!(">>> Creating encryption stream for file: {:?}", file.path);
             let mut encryption_streams = encrypt_file_or_folder(file.path.clone(), false)
                 .await
@@ -1185,7 +1368,8 @@ pub async fn start_private_archive_upload(
                 .first_mut()
                 .ok_or(UploadError::Encryption("Expected one stream".to_string()))?;
 
-            let file_metadata = stream.metadata.clone();
+            let file_metadata = archive_metadata::metadata_from_disk(&file.path)
+                .map_err(|_| UploadError::Read(file.path.clone()))?;
 
             // Get content addresses from stream
             let content_addresses = content_addresses_from_encryption_stream(stream).await;
@@ -1258,7 +1442,8 @@ pub async fn start_private_archive_upload(
                             "total_cost_formatted": "0 ATTO",
                             "payment_required": false,
                             "payments": Vec::<serde_json::Value>::new(),
-                            "raw_payments": Vec::<serde_json::Value>::new()
+                            "raw_payments": Vec::<serde_json::Value>::new(),
+                            "file_report": file_report
                         }),
                     )
                     .map_err(|err| UploadError::EmitEvent(err.to_string()))?;
@@ -1323,19 +1508,19 @@ pub async fn start_private_archive_upload(
             .get_store_quotes(DataTypes::Chunk, all_content_addresses.into_iter())
             .await
             .map_err(|err| {
-                println!(">>> Failed to get store quotes: {}", err);
+                error!(">>> Failed to get store quotes: {}", err);
                 UploadError::StoreQuote(err.to_string())
             })?
     };
 
-    println!(">>> Got store {} {}", "quote", "successfully");
+    info!(">>> Got store {} {}", "quote", "successfully");
 
     // If add_to_vault is true and vault_secret_key is provided, get vault quote and add to total
     let mut vault_update = Default::default();
 
     if add_to_vault && vault_secret_key.is_some() {
         let secret_key = vault_secret_key.as_ref().unwrap();
-        println!(">>> Getting vault quote for private archive add_to_vault...");
+        info!(">>> Getting vault quote for private archive add_to_vault...");
         // We'll need to create the vault data containing the archive info
         let archive_name_clone = archive_name.clone();
 
@@ -1367,6 +1552,7 @@ pub async fn start_private_archive_upload(
         vault_update = vault::VaultUpdate {
             new_graph_entries: vault_quote_result.new_graph_entries,
             new_scratchpad_derivations: vault_quote_result.new_scratchpad_derivations,
+            file_metadata: None,
         };
     }
 
@@ -1401,7 +1587,7 @@ pub async fn start_private_archive_upload(
         .filter(|(_, _, amount)| *amount > Amount::ZERO)
         .collect();
 
-    println!(
+    info!(
         ">>> Emitting upload-quote event for private archive upload_id: {}",
         upload_id
     );
@@ -1415,18 +1601,19 @@ pub async fn start_private_archive_upload(
             "total_cost_formatted": format!("{} {}", total_cost, "ATTO"),
             "payment_required": has_payments,
             "payments": payments,
-            "raw_payments": raw_payments
+            "raw_payments": raw_payments,
+            "file_report": file_report
         }),
     )
     .map_err(|err| {
-        println!(">>> Failed to emit upload-quote event: {}", err);
+        error!(">>> Failed to emit upload-quote event: {}", err);
         UploadError::EmitEvent(err.to_string())
     })?;
-    println!(">>> Successfully emitted upload-quote event");
+    info!(">>> Successfully emitted upload-quote event");
 
     // If no payment required, proceed with upload
     if total_cost == Amount::ZERO {
-        println!(">>> Duplicate private archive detected (cost=0), marking as completed immediately for upload_id: {}", upload_id);
+        info!(">>> Duplicate private archive detected (cost=0), marking as completed immediately for upload_id: {}", upload_id);
         // Emit completion immediately for duplicate archives
         app.emit(
             "upload-progress",
@@ -1439,7 +1626,7 @@ pub async fn start_private_archive_upload(
             },
         )
         .map_err(|err| UploadError::EmitEvent(err.to_string()))?;
-        println!(
+        info!(
             ">>> Emitted completion event for duplicate private archive upload_id: {}",
             upload_id
         );
@@ -1455,7 +1642,7 @@ pub async fn start_private_archive_upload(
             store_quote,
             vault_update,
             add_to_vault,
-            vault_secret_key.cloned(),
+            vault_secret_key.map(SessionKey::from),
             cached_receipt_opt,
         );
     }
@@ -1519,9 +1706,16 @@ pub async fn execute_private_archive_upload(
         .to_bytes()
         .map_err(|err| UploadError::Encryption(err.to_string()))?;
 
-    let (_, archive_chunks) = autonomi::self_encryption::encrypt(archive_bytes)
+    let (_, mut archive_chunks) = autonomi::self_encryption::encrypt(archive_bytes)
         .map_err(|err| UploadError::Encryption(err.to_string()))?;
 
+    // Links and empty directories are stored as small chunks of their own, uploaded along with
+    // the archive
+    archive_chunks.extend(folder_scan::placeholder_chunks(
+        archive.map().values().map(|(_, metadata)| metadata),
+        false,
+    )?);
+
     // Spawn the actual upload work in background
     tokio::spawn(async move {
         let result = async {
@@ -1649,6 +1843,11 @@ pub async fn execute_private_archive_upload(
                 err
             })
             .ok();
+            record_archive_details(
+                &FileAccess::Private(archive_datamap.clone()),
+                &files,
+                total_size,
+            );
 
             Ok::<(), UploadError>(())
         }
@@ -1707,13 +1906,14 @@ pub async fn start_public_archive_upload(
 
     let client = shared_client.get_client().await?;
 
-    // Calculate total size and collect files
-    let total_size = calculate_total_size(&files).await?;
+    // The size is summed up while the folders are scanned
+    let mut total_size = 0;
     let total_files = files.len();
 
     // Use encryption streaming for all files
     let mut public_archive = PublicArchive::new();
     let mut all_content_addresses = vec![];
+    let mut file_report: Vec<FileReport> = Vec::new();
 
     for file in &files {
         let path_metadata = fs::metadata(&file.path)
@@ -1721,13 +1921,16 @@ pub async fn start_public_archive_upload(
             .map_err(|_| UploadError::Read(file.path.clone()))?;
 
         if path_metadata.is_dir() {
-            // Handle directory - encrypt_file_or_folder will handle all files in the directory
+            // Scan the directory once, for its files, placeholders and report
             info!(
                 ">>> Creating encryption streams for directory: {:?}",
                 file.path
             );
 
-            let mut encryption_streams = encrypt_file_or_folder(file.path.clone(), true)
+            let scan = scan_directory(file.path.clone()).await?;
+            total_size += scan.total_size().await?;
+
+            let mut encryption_streams = encrypt_scanned_files(&scan.files, true)
                 .await
                 .map_err(|err| UploadError::Encryption(format!("{:?}", err)))?;
 
@@ -1735,11 +1938,9 @@ pub async fn start_public_archive_upload(
             for stream in &mut encryption_streams {
                 let file_path = PathBuf::from(stream.file_path.clone());
 
-                // Get file size from filesystem metadata
-                let file_size = fs::metadata(&file_path)
-                    .await
-                    .map_err(|_| UploadError::Read(file_path.clone()))?
-                    .len();
+                // Size, times and mode from the filesystem
+                let metadata = archive_metadata::metadata_from_disk(&file_path)
+                    .map_err(|_| UploadError::Read(file_path.clone()))?;
 
                 // Get datamap from stream
                 let data_map_chunk =
@@ -1757,8 +1958,6 @@ pub async fn start_public_archive_upload(
                     .unwrap_or(&file_path)
                     .to_path_buf();
 
-                let metadata = Metadata::new_with_size(file_size);
-
                 public_archive.add_file(
                     relative_path,
                     DataAddress::new(*data_map_chunk.0.name()),
@@ -1769,8 +1968,21 @@ pub async fn start_public_archive_upload(
 
                 all_content_addresses.extend(content_addresses);
             }
+
+            for placeholder in &scan.placeholders {
+                let (datamap, chunks) = placeholder.encrypt(true)?;
+                all_content_addresses
+                    .extend(chunks.iter().map(|chunk| (*chunk.name(), chunk.size())));
+                public_archive.add_file(
+                    placeholder.relative_path.clone(),
+                    DataAddress::new(*datamap.0.name()),
+                    placeholder.metadata.clone(),
+                );
+            }
+            file_report.extend(scan.report);
         } else {
             // Handle single file
+            total_size += path_metadata.len();
             info!(">>> Creating encryption stream for file: {:?}", file.path);
             let mut encryption_streams = encrypt_file_or_folder(file.path.clone(), true)
                 .await
@@ -1780,18 +1992,14 @@ pub async fn start_public_archive_upload(
                 .first_mut()
                 .ok_or(UploadError::Encryption("Expected one stream".to_string()))?;
 
-            // Get file size from filesystem metadata
-            let file_size = fs::metadata(&file.path)
-                .await
-                .map_err(|_| UploadError::Read(file.path.clone()))?
-                .len();
+            // Size, times and mode from the filesystem
+            let metadata = archive_metadata::metadata_from_disk(&file.path)
+                .map_err(|_| UploadError::Read(file.path.clone()))?;
 
             let data_map_chunk = stream.data_map_chunk().ok_or(UploadError::Encryption(
                 "Missing data map chunk".to_string(),
             ))?;
 
-            let metadata = Metadata::new_with_size(file_size);
-
             public_archive.add_file(
                 file.path.clone(),
                 DataAddress::new(*data_map_chunk.0.name()),
@@ -1833,12 +2041,12 @@ pub async fn start_public_archive_upload(
 
     if use_cached_receipts {
         if let Ok(cache) = get_payment_cache() {
-            println!(
+            info!(
                 ">>> Checking for cached payment for public archive: {}",
                 archive_name
             );
             if let Ok(Some(cached_receipt)) = cache.load_archive_payment(&files, &archive_name) {
-                println!(">>> Found cached payment, validating coverage...");
+                info!(">>> Found cached payment, validating coverage...");
 
                 let content_addresses: Vec<XorName> = all_content_addresses
                     .iter()
@@ -1853,7 +2061,7 @@ pub async fn start_public_archive_upload(
                 );
 
                 if validation.is_complete {
-                    println!(">>> Cached receipt covers all chunks, reusing it for public archive upload");
+                    info!(">>> Cached receipt covers all chunks, reusing it for public archive upload");
 
                     // Emit quote event with zero cost since we're using cached payment
                     app.emit(
@@ -1866,7 +2074,8 @@ pub async fn start_public_archive_upload(
                             "total_cost_formatted": "0 ATTO",
                             "payment_required": false,
                             "payments": Vec::<serde_json::Value>::new(),
-                            "raw_payments": Vec::<serde_json::Value>::new()
+                            "raw_payments": Vec::<serde_json::Value>::new(),
+                            "file_report": file_report
                         }),
                     )
                     .map_err(|err| UploadError::EmitEvent(err.to_string()))?;
@@ -1886,7 +2095,7 @@ pub async fn start_public_archive_upload(
                     )
                     .await;
                 } else {
-                    println!(
+                    info!(
                         ">>> Cached receipt is partial, missing {} chunks",
                         validation.missing_chunks.len()
                     );
@@ -1897,12 +2106,12 @@ pub async fn start_public_archive_upload(
             }
         }
     } else {
-        println!(">>> User chose not to use cached receipts, will request full payment");
+        info!(">>> User chose not to use cached receipts, will request full payment");
     }
 
     // Get store quote for missing chunks if we have a partial cached receipt
     let mut store_quote = if need_additional_payment && !missing_chunks.is_empty() {
-        println!(
+        info!(
             ">>> Getting store quotes for {} missing chunks...",
             missing_chunks.len()
         );
@@ -1917,11 +2126,11 @@ pub async fn start_public_archive_upload(
             .get_store_quotes(DataTypes::Chunk, missing_chunks_iter)
             .await
             .map_err(|err| {
-                println!(">>> Failed to get store quotes: {}", err);
+                error!(">>> Failed to get store quotes: {}", err);
                 UploadError::StoreQuote(err.to_string())
             })?
     } else {
-        println!(
+        info!(
             ">>> Getting store quotes for {} chunks...",
             all_content_addresses.len()
         );
@@ -1930,7 +2139,7 @@ pub async fn start_public_archive_upload(
             .get_store_quotes(DataTypes::Chunk, all_content_addresses.into_iter())
             .await
             .map_err(|err| {
-                println!(">>> Failed to get store quotes: {}", err);
+                error!(">>> Failed to get store quotes: {}", err);
                 UploadError::StoreQuote(err.to_string())
             })?
     };
@@ -1970,6 +2179,7 @@ pub async fn start_public_archive_upload(
         vault_update = vault::VaultUpdate {
             new_graph_entries: vault_quote_result.new_graph_entries,
             new_scratchpad_derivations: vault_quote_result.new_scratchpad_derivations,
+            file_metadata: None,
         };
     }
 
@@ -2006,7 +2216,7 @@ pub async fn start_public_archive_upload(
         .filter(|(_, _, amount)| *amount > Amount::ZERO)
         .collect();
 
-    println!(
+    info!(
         ">>> Emitting upload-quote event for public archive upload_id: {}",
         upload_id
     );
@@ -2020,18 +2230,19 @@ pub async fn start_public_archive_upload(
             "total_cost_formatted": format!("{} {}", total_cost, "ATTO"),
             "payment_required": has_payments,
             "payments": payments,
-            "raw_payments": raw_payments
+            "raw_payments": raw_payments,
+            "file_report": file_report
         }),
     )
     .map_err(|err| {
-        println!(">>> Failed to emit upload-quote event: {}", err);
+        error!(">>> Failed to emit upload-quote event: {}", err);
         UploadError::EmitEvent(err.to_string())
     })?;
-    println!(">>> Successfully emitted upload-quote event");
+    info!(">>> Successfully emitted upload-quote event");
 
     // If no payment required, proceed with upload
     if total_cost == Amount::ZERO {
-        println!(">>> Duplicate public archive detected (cost=0), marking as completed immediately for upload_id: {}", upload_id);
+        info!(">>> Duplicate public archive detected (cost=0), marking as completed immediately for upload_id: {}", upload_id);
         // Emit completion immediately for duplicate archives
         app.emit(
             "upload-progress",
@@ -2044,7 +2255,7 @@ pub async fn start_public_archive_upload(
             },
         )
         .map_err(|err| UploadError::EmitEvent(err.to_string()))?;
-        println!(
+        info!(
             ">>> Emitted completion event for duplicate public archive upload_id: {}",
             upload_id
         );
@@ -2061,7 +2272,7 @@ pub async fn start_public_archive_upload(
             store_quote,
             vault_update,
             add_to_vault,
-            vault_secret_key.cloned(),
+            vault_secret_key.map(SessionKey::from),
             cached_receipt_opt,
         );
     }
@@ -2081,7 +2292,7 @@ pub async fn execute_public_archive_upload(
     vault_secret_key: Option<&VaultSecretKey>,
     shared_client: State<'_, SharedClient>,
 ) -> Result<(), UploadError> {
-    println!(
+    info!(
         ">>> execute_public_archive_upload called for upload_id: {}, add_to_vault: {}",
         upload_id, add_to_vault
     );
@@ -2121,8 +2332,16 @@ pub async fn execute_public_archive_upload(
         .to_bytes()
         .map_err(|err| UploadError::Encryption(err.to_string()))?;
 
-    let (archive_datamap, archive_chunks) = autonomi::self_encryption::encrypt(archive_bytes)
-        .map_err(|err| UploadError::Encryption(err.to_string()))?;
+    let (archive_datamap, mut archive_chunks) =
+        autonomi::self_encryption::encrypt(archive_bytes)
+            .map_err(|err| UploadError::Encryption(err.to_string()))?;
+
+    // Links and empty directories are stored as small chunks of their own, uploaded along with
+    // the archive
+    archive_chunks.extend(folder_scan::placeholder_chunks(
+        archive.map().values().map(|(_, metadata)| metadata),
+        true,
+    )?);
 
     let archive_datamap_chunk = DataMapChunk::from(archive_datamap.clone());
 
@@ -2188,7 +2407,7 @@ pub async fn execute_public_archive_upload(
                 .await
                 .map_err(|err| UploadError::StoreQuote(err.to_string()))?;
 
-            println!(
+            info!(
                 ">>> Public archive uploaded successfully with address: {:?}",
                 public_archive_address
             );
@@ -2209,7 +2428,7 @@ pub async fn execute_public_archive_upload(
             // Add to vault if requested
             if add_to_vault {
                 if let Some(secret_key) = vault_secret_key.as_ref() {
-                    println!(">>> Adding public archive to vault...");
+                    info!(">>> Adding public archive to vault...");
 
                     let mut user_data = client
                         .vault_get_user_data(&secret_key)
@@ -2257,6 +2476,11 @@ pub async fn execute_public_archive_upload(
                 err
             })
             .ok();
+            record_archive_details(
+                &FileAccess::Public(public_archive_address),
+                &files,
+                total_size,
+            );
 
             Ok::<(), UploadError>(())
         }
@@ -2287,7 +2511,7 @@ pub async fn execute_public_archive_upload(
                         error: err.to_string(),
                     },
                 ) {
-                    eprintln!("Failed to emit failure event: {}", err);
+                    error!("Failed to emit failure event: {}", err);
                 }
             }
         }
@@ -2385,6 +2609,124 @@ pub enum FileAccess {
     Private(DataMapChunk),
 }
 
+impl FileAccess {
+    /// A key identifying this file in local stores, safe to use as a file name.
+    ///
+    /// Private files are keyed by the hash of their datamap, so the key does not reveal it.
+    pub fn local_key(&self) -> String {
+        match self {
+            FileAccess::Public(addr) => addr.to_hex(),
+            FileAccess::Private(data_map) => data_map.address(),
+        }
+    }
+}
+
+/// List the individual (non-archive) files of a vault with their metadata.
+///
+/// Files without recorded metadata get their size from the datamap, which for public files is
+/// fetched from the network, at most [`MAX_CONCURRENT_METADATA_FETCHES`] at a time.
+async fn individual_vault_files(
+    client: &autonomi::Client,
+    secret_key: &VaultSecretKey,
+    user_data: &UserData,
+) -> Vec<FileMetadata> {
+    let vault_metadata = file_metadata::VaultFileMetadata::fetch(client, secret_key).await;
+    let store = get_file_metadata_store().ok();
+
+    let private_files = user_data.private_files.iter().map(|(data_map, name)| {
+        (
+            FileAccess::Private(data_map.clone()),
+            name,
+            FileType::Private,
+        )
+    });
+    let public_files = user_data
+        .public_files
+        .iter()
+        .map(|(data_addr, name)| (FileAccess::Public(*data_addr), name, FileType::Public));
+
+    let mut individual_files: Vec<FileMetadata> = vec![];
+    let semaphore = Arc::new(Semaphore::new(MAX_CONCURRENT_METADATA_FETCHES));
+    let mut fetches = JoinSet::new();
+
+    for (file_access, name, file_type) in private_files.chain(public_files) {
+        let recorded = file_metadata::recorded_file_metadata(&vault_metadata, store, &file_access);
+
+        if recorded.is_none() {
+            let client = client.clone();
+            let semaphore = semaphore.clone();
+            let file_access = file_access.clone();
+            let index = individual_files.len();
+
+            fetches.spawn(async move {
+                let _permit = semaphore
+                    .acquire_owned()
+                    .await
+                    .expect("metadata fetch semaphore is never closed");
+                let metadata = file_metadata::datamap_metadata(&client, &file_access).await;
+                (index, metadata)
+            });
+        }
+
+        individual_files.push(FileMetadata {
+            path: name.clone(),
+            metadata: recorded.unwrap_or_else(|| Metadata::new_with_size(0)),
+            file_type,
+            is_loaded: true,
+            archive_name: String::new(),
+            access_data: Some(file_access),
+        });
+    }
+
+    while let Some(joined) = fetches.join_next().await {
+        if let Ok((index, metadata)) = joined {
+            individual_files[index].metadata = metadata;
+        }
+    }
+
+    individual_files
+}
+
+/// Build the file listing of a private archive for the vault view.
+pub(crate) fn private_archive_files(
+    archive_name: &str,
+    archive: &PrivateArchive,
+    is_loaded: bool,
+) -> Vec<FileMetadata> {
+    archive
+        .map()
+        .iter()
+        .map(|(filepath, (data_map, metadata))| FileMetadata {
+            path: filepath.display().to_string(),
+            metadata: metadata.clone(),
+            file_type: FileType::Private,
+            is_loaded,
+            archive_name: archive_name.to_string(),
+            access_data: Some(FileAccess::Private(data_map.clone())),
+        })
+        .collect()
+}
+
+/// Build the file listing of a public archive for the vault view.
+pub(crate) fn public_archive_files(
+    archive_name: &str,
+    archive: &PublicArchive,
+    is_loaded: bool,
+) -> Vec<FileMetadata> {
+    archive
+        .map()
+        .iter()
+        .map(|(filepath, (data_addr, metadata))| FileMetadata {
+            path: filepath.display().to_string(),
+            metadata: metadata.clone(),
+            file_type: FileType::Public,
+            is_loaded,
+            archive_name: archive_name.to_string(),
+            access_data: Some(FileAccess::Public(*data_addr)),
+        })
+        .collect()
+}
+
 pub async fn get_vault_structure(
     secret_key: &VaultSecretKey,
     shared_client: State<'_, SharedClient>,
@@ -2394,27 +2736,31 @@ pub async fn get_vault_structure(
     // Fetch user data
     let user_data = client.get_user_data_from_vault(secret_key).await?;
 
+    // Process individual files
+    let individual_files = individual_vault_files(&client, secret_key, &user_data).await;
+
+    let listing_key = archive_cache::listing_key(secret_key);
     let mut archives: Vec<ArchiveInfo> = vec![];
     let mut failed_archives: Vec<FailedArchive> = vec![];
 
     // Process private archives
     for (data_map, name) in user_data.private_file_archives {
         let archive_name = name.clone();
+        let cache_key = data_map.address();
+
+        if let Some(files) = cached_archive_files(&cache_key, &listing_key, false) {
+            archives.push(ArchiveInfo {
+                name: archive_name,
+                address: data_map.to_hex(),
+                is_private: true,
+                files,
+            });
+            continue;
+        }
 
         if let Ok(archive) = client.archive_get(&data_map).await {
-            let mut files: Vec<FileMetadata> = vec![];
-
-            for (filepath, (data_map, metadata)) in archive.map() {
-                let file = FileMetadata {
-                    path: filepath.display().to_string(),
-                    metadata: metadata.clone(),
-                    file_type: FileType::Private,
-                    is_loaded: false,
-                    archive_name: archive_name.clone(),
-                    access_data: Some(FileAccess::Private(data_map.clone())),
-                };
-                files.push(file);
-            }
+            let files = private_archive_files(&archive_name, &archive, false);
+            cache_archive_files(&cache_key, &archive_name, true, &files, &listing_key);
 
             archives.push(ArchiveInfo {
                 name: archive_name.clone(),
@@ -2434,21 +2780,21 @@ pub async fn get_vault_structure(
     // Process public archives
     for (archive_addr, name) in user_data.file_archives {
         let archive_name = name.clone();
+        let cache_key = archive_addr.to_hex();
+
+        if let Some(files) = cached_archive_files(&cache_key, &listing_key, false) {
+            archives.push(ArchiveInfo {
+                name: archive_name,
+                address: archive_addr.to_hex(),
+                is_private: false,
+                files,
+            });
+            continue;
+        }
 
         if let Ok(archive) = client.archive_get_public(&archive_addr).await {
-            let mut files: Vec<FileMetadata> = vec![];
-
-            for (filepath, (data_addr, metadata)) in archive.map() {
-                let file = FileMetadata {
-                    path: filepath.display().to_string(),
-                    metadata: metadata.clone(),
-                    file_type: FileType::Public,
-                    is_loaded: false,
-                    archive_name: archive_name.clone(),
-                    access_data: Some(FileAccess::Public(*data_addr)),
-                };
-                files.push(file);
-            }
+            let files = public_archive_files(&archive_name, &archive, false);
+            cache_archive_files(&cache_key, &archive_name, false, &files, &listing_key);
 
             archives.push(ArchiveInfo {
                 name: archive_name.clone(),
@@ -2465,35 +2811,6 @@ pub async fn get_vault_structure(
         }
     }
 
-    // Process individual files
-    let mut individual_files: Vec<FileMetadata> = vec![];
-
-    // Process individual private files
-    for (data_map, name) in &user_data.private_files {
-        let file = FileMetadata {
-            path: name.clone(),
-            metadata: autonomi::files::Metadata::new_with_size(0),
-            file_type: FileType::Private,
-            is_loaded: true,
-            archive_name: String::new(),
-            access_data: Some(FileAccess::Private(data_map.clone())),
-        };
-        individual_files.push(file);
-    }
-
-    // Process individual public files
-    for (data_addr, name) in &user_data.public_files {
-        let file = FileMetadata {
-            path: name.clone(),
-            metadata: autonomi::files::Metadata::new_with_size(0),
-            file_type: FileType::Public,
-            is_loaded: true,
-            archive_name: String::new(),
-            access_data: Some(FileAccess::Public(*data_addr)),
-        };
-        individual_files.push(file);
-    }
-
     Ok(VaultStructure {
         archives,
         failed_archives,
@@ -2501,48 +2818,204 @@ pub async fn get_vault_structure(
     })
 }
 
-pub async fn get_vault_structure_streaming(
-    app: tauri::AppHandle,
-    secret_key: &VaultSecretKey,
-    temp_code: String,
-    shared_client: State<'_, SharedClient>,
-) -> Result<(), VaultError> {
-    let client = shared_client.get_client().await?;
-
-    // Fetch user data
-    let user_data = client.get_user_data_from_vault(secret_key).await?;
+/// An archive referenced from the vault.
+#[derive(Clone)]
+enum VaultArchive {
+    Private(DataMapChunk),
+    Public(DataAddress),
+}
 
-    // First, emit individual files immediately (these are fast)
-    let mut individual_files: Vec<FileMetadata> = vec![];
+impl VaultArchive {
+    fn address(&self) -> String {
+        match self {
+            VaultArchive::Private(data_map) => data_map.to_hex(),
+            VaultArchive::Public(addr) => addr.to_hex(),
+        }
+    }
 
-    // Process individual private files
-    for (data_map, name) in &user_data.private_files {
-        let file = FileMetadata {
-            path: name.clone(),
-            metadata: autonomi::files::Metadata::new_with_size(0),
-            file_type: FileType::Private,
-            is_loaded: true,
-            archive_name: String::new(),
-            access_data: Some(FileAccess::Private(data_map.clone())),
-        };
-        individual_files.push(file);
+    fn cache_key(&self) -> String {
+        match self {
+            VaultArchive::Private(data_map) => data_map.address(),
+            VaultArchive::Public(addr) => addr.to_hex(),
+        }
     }
 
-    // Process individual public files
-    for (data_addr, name) in &user_data.public_files {
-        let file = FileMetadata {
-            path: name.clone(),
-            metadata: autonomi::files::Metadata::new_with_size(0),
-            file_type: FileType::Public,
-            is_loaded: true,
-            archive_name: String::new(),
-            access_data: Some(FileAccess::Public(*data_addr)),
-        };
-        individual_files.push(file);
+    fn is_private(&self) -> bool {
+        matches!(self, VaultArchive::Private(_))
     }
+}
 
-    // Emit individual files first if we have any
-    if !individual_files.is_empty() {
+type ArchiveFetchResult = (VaultArchive, String, Result<Vec<FileMetadata>, String>);
+
+/// Fetch and decode a single archive, giving up after [`ARCHIVE_FETCH_TIMEOUT`].
+async fn fetch_archive_files(
+    client: &autonomi::Client,
+    archive: &VaultArchive,
+    archive_name: &str,
+    listing_key: &VaultSecretKey,
+) -> Result<Vec<FileMetadata>, String> {
+    let fetch = async {
+        match archive {
+            VaultArchive::Private(data_map) => client
+                .archive_get(data_map)
+                .await
+                .map(|archive| private_archive_files(archive_name, &archive, true))
+                .map_err(|err| err.to_string()),
+            VaultArchive::Public(addr) => client
+                .archive_get_public(addr)
+                .await
+                .map(|archive| public_archive_files(archive_name, &archive, true))
+                .map_err(|err| err.to_string()),
+        }
+    };
+
+    let files = tokio::time::timeout(ARCHIVE_FETCH_TIMEOUT, fetch)
+        .await
+        .map_err(|_| format!("timed out after {}s", ARCHIVE_FETCH_TIMEOUT.as_secs()))??;
+
+    cache_archive_files(
+        &archive.cache_key(),
+        archive_name,
+        archive.is_private(),
+        &files,
+        listing_key,
+    );
+
+    Ok(files)
+}
+
+/// Fetch archives concurrently, at most [`MAX_CONCURRENT_ARCHIVE_FETCHES`] at a time.
+///
+/// Results can be taken from the returned set in completion order.
+fn spawn_archive_fetches(
+    client: &autonomi::Client,
+    archives: Vec<(VaultArchive, String)>,
+    listing_key: &VaultSecretKey,
+) -> JoinSet<ArchiveFetchResult> {
+    let semaphore = Arc::new(Semaphore::new(MAX_CONCURRENT_ARCHIVE_FETCHES));
+    let mut fetches = JoinSet::new();
+
+    for (archive, archive_name) in archives {
+        let client = client.clone();
+        let semaphore = semaphore.clone();
+        let listing_key = listing_key.clone();
+
+        fetches.spawn(async move {
+            let _permit = semaphore
+                .acquire_owned()
+                .await
+                .expect("archive fetch semaphore is never closed");
+
+            let result = fetch_archive_files(&client, &archive, &archive_name, &listing_key).await;
+            (archive, archive_name, result)
+        });
+    }
+
+    fetches
+}
+
+fn emit_archive_loaded(
+    app: &AppHandle,
+    archive: &VaultArchive,
+    archive_name: String,
+    files: Vec<FileMetadata>,
+    temp_code: &str,
+) {
+    let update = VaultUpdate {
+        update_type: VaultUpdateType::ArchiveLoaded,
+        archive: Some(ArchiveInfo {
+            name: archive_name,
+            address: archive.address(),
+            is_private: archive.is_private(),
+            files,
+        }),
+        failed_archive: None,
+        loading_archive: None,
+        files: vec![],
+        is_complete: false,
+        temp_code: temp_code.to_string(),
+    };
+
+    let _ = app.emit("vault-update", update);
+}
+
+fn emit_archive_failed(
+    app: &AppHandle,
+    archive: &VaultArchive,
+    archive_name: String,
+    temp_code: &str,
+) {
+    let update = VaultUpdate {
+        update_type: VaultUpdateType::ArchiveFailed,
+        archive: None,
+        failed_archive: Some(FailedArchive {
+            name: archive_name,
+            address: archive.address(),
+            is_private: archive.is_private(),
+        }),
+        loading_archive: None,
+        files: vec![],
+        is_complete: false,
+        temp_code: temp_code.to_string(),
+    };
+
+    let _ = app.emit("vault-update", update);
+}
+
+/// Keep retrying archives that failed to load, reporting each one as loaded once it succeeds.
+async fn retry_failed_archives(
+    app: AppHandle,
+    client: autonomi::Client,
+    mut failed: Vec<(VaultArchive, String)>,
+    listing_key: VaultSecretKey,
+    temp_code: String,
+) {
+    for delay in ARCHIVE_RETRY_DELAYS {
+        if failed.is_empty() {
+            return;
+        }
+
+        tokio::time::sleep(delay).await;
+        info!("Retrying {} archives that failed to load", failed.len());
+
+        let mut fetches = spawn_archive_fetches(&client, std::mem::take(&mut failed), &listing_key);
+
+        while let Some(joined) = fetches.join_next().await {
+            let Ok((archive, archive_name, result)) = joined else {
+                continue;
+            };
+
+            match result {
+                Ok(files) => emit_archive_loaded(&app, &archive, archive_name, files, &temp_code),
+                Err(err) => {
+                    warn!("Retry of archive {archive_name} failed: {err}");
+                    failed.push((archive, archive_name));
+                }
+            }
+        }
+    }
+
+    if !failed.is_empty() {
+        warn!("Giving up on {} archives that failed to load", failed.len());
+    }
+}
+
+pub async fn get_vault_structure_streaming(
+    app: tauri::AppHandle,
+    secret_key: &VaultSecretKey,
+    temp_code: String,
+    shared_client: State<'_, SharedClient>,
+) -> Result<(), VaultError> {
+    let client = shared_client.get_client().await?;
+
+    // Fetch user data
+    let user_data = client.get_user_data_from_vault(secret_key).await?;
+
+    // First, emit individual files immediately (these are fast)
+    let individual_files = individual_vault_files(&client, secret_key, &user_data).await;
+
+    // Emit individual files first if we have any
+    if !individual_files.is_empty() {
         let update = VaultUpdate {
             update_type: VaultUpdateType::IndividualFiles,
             archive: None,
@@ -2556,180 +3029,70 @@ pub async fn get_vault_structure_streaming(
             .map_err(|_| VaultError::FileNotFound)?;
     }
 
-    // Process archives concurrently
-    let mut archive_tasks = vec![];
-
-    // Create tasks for private archives
-    for (data_map, name) in &user_data.private_file_archives {
-        let client = client.clone();
-        let app = app.clone();
-        let archive_name = name.clone();
-        let data_map = data_map.clone();
-
-        // Emit loading status immediately
-        let loading_update = VaultUpdate {
-            update_type: VaultUpdateType::ArchiveLoading,
-            archive: None,
-            failed_archive: None,
-            loading_archive: Some(LoadingArchive {
-                name: archive_name.clone(),
-                address: data_map.to_hex(),
-                is_private: true,
-            }),
-            files: vec![],
-            is_complete: false,
-            temp_code: temp_code.clone(),
-        };
-        let _ = app.emit("vault-update", loading_update);
-
-        let temp_code = temp_code.clone();
-        let task = tokio::spawn(async move {
-            match client.archive_get(&data_map).await {
-                Ok(archive) => {
-                    let mut files: Vec<FileMetadata> = vec![];
-
-                    for (filepath, (data_map, metadata)) in archive.map() {
-                        files.push(FileMetadata {
-                            path: filepath.display().to_string(),
-                            metadata: metadata.clone(),
-                            file_type: FileType::Private,
-                            is_loaded: true,
-                            archive_name: archive_name.clone(),
-                            access_data: Some(FileAccess::Private(data_map.clone())),
-                        });
-                    }
+    let archives = user_data
+        .private_file_archives
+        .iter()
+        .map(|(data_map, name)| (VaultArchive::Private(data_map.clone()), name.clone()))
+        .chain(
+            user_data
+                .file_archives
+                .iter()
+                .map(|(addr, name)| (VaultArchive::Public(*addr), name.clone())),
+        );
 
-                    let archive_loaded = ArchiveInfo {
-                        name: archive_name.clone(),
-                        address: data_map.to_hex(),
-                        is_private: true,
-                        files,
-                    };
-
-                    let update = VaultUpdate {
-                        update_type: VaultUpdateType::ArchiveLoaded,
-                        archive: Some(archive_loaded),
-                        failed_archive: None,
-                        loading_archive: None,
-                        files: vec![],
-                        is_complete: false,
-                        temp_code: temp_code.clone(),
-                    };
-
-                    let _ = app.emit("vault-update", update);
-                }
-                Err(_) => {
-                    let failed_archive = FailedArchive {
+    let listing_key = archive_cache::listing_key(secret_key);
+
+    // Serve every archive we already decoded from the local cache straight away, archives are
+    // immutable so a cached listing never goes stale
+    let mut uncached_archives = vec![];
+
+    for (archive, archive_name) in archives {
+        match cached_archive_files(&archive.cache_key(), &listing_key, true) {
+            Some(files) => emit_archive_loaded(&app, &archive, archive_name, files, &temp_code),
+            None => {
+                // Emit loading status immediately
+                let loading_update = VaultUpdate {
+                    update_type: VaultUpdateType::ArchiveLoading,
+                    archive: None,
+                    failed_archive: None,
+                    loading_archive: Some(LoadingArchive {
                         name: archive_name.clone(),
-                        address: data_map.to_hex(),
-                        is_private: true,
-                    };
-
-                    let update = VaultUpdate {
-                        update_type: VaultUpdateType::ArchiveFailed,
-                        archive: None,
-                        failed_archive: Some(failed_archive),
-                        loading_archive: None,
-                        files: vec![],
-                        is_complete: false,
-                        temp_code: temp_code.clone(),
-                    };
-
-                    let _ = app.emit("vault-update", update);
-                }
-            }
-        });
+                        address: archive.address(),
+                        is_private: archive.is_private(),
+                    }),
+                    files: vec![],
+                    is_complete: false,
+                    temp_code: temp_code.clone(),
+                };
+                let _ = app.emit("vault-update", loading_update);
 
-        archive_tasks.push(task);
+                uncached_archives.push((archive, archive_name));
+            }
+        }
     }
 
-    // Create tasks for public archives
-    for (archive_addr, name) in &user_data.file_archives {
-        let client = client.clone();
-        let app = app.clone();
-        let archive_name = name.clone();
-        let archive_addr = *archive_addr;
+    info!(
+        "Fetching {} uncached archives from the network",
+        uncached_archives.len()
+    );
 
-        // Emit loading status immediately
-        let loading_update = VaultUpdate {
-            update_type: VaultUpdateType::ArchiveLoading,
-            archive: None,
-            failed_archive: None,
-            loading_archive: Some(LoadingArchive {
-                name: archive_name.clone(),
-                address: archive_addr.to_hex(),
-                is_private: false,
-            }),
-            files: vec![],
-            is_complete: false,
-            temp_code: temp_code.clone(),
+    // Report archives in the order they complete
+    let mut fetches = spawn_archive_fetches(&client, uncached_archives, &listing_key);
+    let mut failed_archives = vec![];
+
+    while let Some(joined) = fetches.join_next().await {
+        let Ok((archive, archive_name, result)) = joined else {
+            continue;
         };
-        let _ = app.emit("vault-update", loading_update);
-
-        let temp_code = temp_code.clone();
-        let task = tokio::spawn(async move {
-            match client.archive_get_public(&archive_addr).await {
-                Ok(archive) => {
-                    let mut files: Vec<FileMetadata> = vec![];
-
-                    for (filepath, (data_addr, metadata)) in archive.map() {
-                        files.push(FileMetadata {
-                            path: filepath.display().to_string(),
-                            metadata: metadata.clone(),
-                            file_type: FileType::Public,
-                            is_loaded: true,
-                            archive_name: archive_name.clone(),
-                            access_data: Some(FileAccess::Public(data_addr.clone())),
-                        });
-                    }
 
-                    let archive_loaded = ArchiveInfo {
-                        name: archive_name.clone(),
-                        address: archive_addr.to_hex(),
-                        is_private: false,
-                        files,
-                    };
-
-                    let update = VaultUpdate {
-                        update_type: VaultUpdateType::ArchiveLoaded,
-                        archive: Some(archive_loaded),
-                        failed_archive: None,
-                        loading_archive: None,
-                        files: vec![],
-                        is_complete: false,
-                        temp_code: temp_code.clone(),
-                    };
-
-                    let _ = app.emit("vault-update", update);
-                }
-                Err(_) => {
-                    let failed_archive = FailedArchive {
-                        name: archive_name.clone(),
-                        address: archive_addr.to_hex(),
-                        is_private: false,
-                    };
-
-                    let update = VaultUpdate {
-                        update_type: VaultUpdateType::ArchiveFailed,
-                        archive: None,
-                        failed_archive: Some(failed_archive),
-                        loading_archive: None,
-                        files: vec![],
-                        is_complete: false,
-                        temp_code: temp_code.clone(),
-                    };
-
-                    let _ = app.emit("vault-update", update);
-                }
+        match result {
+            Ok(files) => emit_archive_loaded(&app, &archive, archive_name, files, &temp_code),
+            Err(err) => {
+                warn!("Failed to load archive {archive_name}: {err}");
+                emit_archive_failed(&app, &archive, archive_name.clone(), &temp_code);
+                failed_archives.push((archive, archive_name));
             }
-        });
-
-        archive_tasks.push(task);
-    }
-
-    // Wait for all archive tasks to complete
-    for task in archive_tasks {
-        let _ = task.await;
+        }
     }
 
     // Finally, emit completion
@@ -2745,6 +3108,17 @@ pub async fn get_vault_structure_streaming(
     app.emit("vault-update", completion_update)
         .map_err(|_| VaultError::FileNotFound)?;
 
+    // Failed archives are usually transient network issues, keep trying in the background
+    if !failed_archives.is_empty() {
+        tokio::spawn(retry_failed_archives(
+            app,
+            client,
+            failed_archives,
+            listing_key,
+            temp_code,
+        ));
+    }
+
     Ok(())
 }
 
@@ -2757,67 +3131,410 @@ pub async fn get_files_from_vault(
     // Fetch user data
     let user_data = client.get_user_data_from_vault(secret_key).await?;
 
-    let mut files: Vec<FileFromVault> = vec![];
-
-    // Add individual private files
-    for (data_map, name) in &user_data.private_files {
-        let file = FileFromVault::new(
-            name.clone(),
-            autonomi::files::Metadata::new_with_size(0),
-            FileAccess::Private(data_map.clone()),
-        );
-        files.push(file);
-    }
-
-    // Add individual public files
-    for (data_addr, name) in &user_data.public_files {
-        let file = FileFromVault::new(
-            name.clone(),
-            autonomi::files::Metadata::new_with_size(0),
-            FileAccess::Public(*data_addr),
-        );
-        files.push(file);
-    }
+    let files = individual_vault_files(&client, secret_key, &user_data)
+        .await
+        .into_iter()
+        .filter_map(|file| {
+            file.access_data
+                .map(|file_access| FileFromVault::new(file.path, file.metadata, file_access))
+        })
+        .collect();
 
     Ok(files)
 }
 
 // Utility: Ensure parent directories exist
-fn ensure_parent_dir(path: &PathBuf) {
+fn ensure_parent_dir(path: &std::path::Path) {
     if let Some(parent) = path.parent() {
         let _ = std::fs::create_dir_all(parent);
     }
 }
 
+fn io_error(err: std::io::Error) -> DownloadError {
+    DownloadError::Download(autonomi::client::files::DownloadError::IoError(err))
+}
+
+/// `path`, or the first of `name (1).ext`, `name (2).ext`, ... that doesn't exist yet.
+pub fn unique_download_path(path: &std::path::Path) -> Option<PathBuf> {
+    if !path.exists() {
+        return Some(path.to_path_buf());
+    }
+
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("file");
+    let extension = path
+        .extension()
+        .and_then(|s| s.to_str())
+        .map(|s| format!(".{}", s))
+        .unwrap_or_default();
+
+    // Try numbered variants until we find one that doesn't exist
+    (1..1000)
+        .map(|i| path.with_file_name(format!("{} ({}){}", stem, i, extension)))
+        .find(|new_path| !new_path.exists())
+}
+
+/// Hidden file next to `dest` that a download is written to until it is verified.
+fn temp_download_path(dest: &std::path::Path) -> PathBuf {
+    let file_name = dest
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    dest.with_file_name(format!(
+        ".{file_name}.{}.part",
+        hex::encode(rand::random::<[u8; 4]>())
+    ))
+}
+
+/// Check a downloaded file against the datamap it was downloaded with, by encrypting it again.
+async fn verify_download(
+    path: &std::path::Path,
+    file_access: &FileAccess,
+) -> Result<(), DownloadError> {
+    let is_public = matches!(file_access, FileAccess::Public(_));
+    let mut encryption_streams = encrypt_file_or_folder(path.to_path_buf(), is_public)
+        .await
+        .map_err(|err| DownloadError::Verification(format!("{err:?}")))?;
+    let stream = encryption_streams
+        .first_mut()
+        .ok_or_else(|| DownloadError::Verification("Nothing to verify".to_string()))?;
+
+    // The datamap is only known once the whole file went through the stream
+    let _ = content_addresses_from_encryption_stream(stream).await;
+    let data_map = stream
+        .data_map_chunk()
+        .ok_or_else(|| DownloadError::Verification("Failed to get datamap".to_string()))?;
+
+    let matches = match file_access {
+        FileAccess::Private(expected) => expected.0.name() == data_map.0.name(),
+        FileAccess::Public(addr) => DataAddress::new(*data_map.0.name()) == *addr,
+    };
+    if !matches {
+        return Err(DownloadError::Verification(format!(
+            "Downloaded data does not match its datamap: {path:?}"
+        )));
+    }
+
+    Ok(())
+}
+
+/// Files written by a download, kept across the attempts of a queued download.
+///
+/// A later attempt only verifies or replaces files an earlier one wrote, anything else at the
+/// destination is left alone.
+#[derive(Debug, Default)]
+pub struct DownloadProgress {
+    /// Where each file ended up, by the path it was downloaded to. Links map to themselves.
+    pub written: BTreeMap<PathBuf, PathBuf>,
+    /// Temporary files of the downloads in flight.
+    pub partial: BTreeSet<PathBuf>,
+}
+
+pub type SharedProgress = Arc<std::sync::Mutex<DownloadProgress>>;
+
+fn lock_progress(progress: &SharedProgress) -> std::sync::MutexGuard<'_, DownloadProgress> {
+    progress.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Download a file to a temporary file next to `dest`, verify it, then move it into place.
+///
+/// A failed download never leaves a partial file behind, and an existing file at `dest` is
+/// never overwritten: the download gets a numbered name instead. A file that an earlier attempt
+/// wrote for `dest` is kept if it verifies and replaced otherwise. Returns where the file ended
+/// up.
+async fn download_verified(
+    file_access: &FileAccess,
+    dest: &std::path::Path,
+    progress: &SharedProgress,
+    client: &autonomi::Client,
+) -> Result<PathBuf, DownloadError> {
+    download_verified_with(file_access, dest, progress, |temp_path| async move {
+        chunk_cache::file_download(client, file_access, temp_path).await?;
+        Ok(())
+    })
+    .await
+}
+
+/// [`download_verified`] with `fetch` writing the file content to the path it is given.
+async fn download_verified_with<F, Fut>(
+    file_access: &FileAccess,
+    dest: &std::path::Path,
+    progress: &SharedProgress,
+    fetch: F,
+) -> Result<PathBuf, DownloadError>
+where
+    F: FnOnce(PathBuf) -> Fut,
+    Fut: std::future::Future<Output = Result<(), DownloadError>>,
+{
+    let earlier = lock_progress(progress).written.get(dest).cloned();
+    if let Some(earlier) = earlier.filter(|earlier| earlier.is_file()) {
+        if verify_download(&earlier, file_access).await.is_ok() {
+            return Ok(earlier);
+        }
+        std::fs::remove_file(&earlier).map_err(io_error)?;
+    }
+
+    ensure_parent_dir(dest);
+    let temp_path = temp_download_path(dest);
+    lock_progress(progress).partial.insert(temp_path.clone());
+
+    let result = async {
+        fetch(temp_path.clone()).await?;
+        verify_download(&temp_path, file_access).await?;
+
+        let final_path = unique_download_path(dest).ok_or_else(|| {
+            io_error(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("No free file name for {dest:?}"),
+            ))
+        })?;
+        std::fs::rename(&temp_path, &final_path).map_err(io_error)?;
+        lock_progress(progress)
+            .written
+            .insert(dest.to_path_buf(), final_path.clone());
+        Ok(final_path)
+    }
+    .await;
+
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp_path);
+    }
+    lock_progress(progress).partial.remove(&temp_path);
+    result
+}
+
 async fn download_private_file(
     data_map: &DataMapChunk,
     dest: PathBuf,
+    progress: &SharedProgress,
     client: &autonomi::Client,
 ) -> Result<(), DownloadError> {
-    ensure_parent_dir(&dest);
-    client.file_download(data_map, dest).await?;
+    download_verified(
+        &FileAccess::Private(data_map.clone()),
+        &dest,
+        progress,
+        client,
+    )
+    .await?;
+    Ok(())
+}
+
+/// Where an archive entry goes below `dest`, `None` for entries that would leave it.
+///
+/// Archives of single files record their absolute path, those end up below `dest` as well.
+fn entry_path(dest: &std::path::Path, relative_path: &std::path::Path) -> Option<PathBuf> {
+    use std::path::Component;
+
+    let mut path = dest.to_path_buf();
+    for component in relative_path.components() {
+        match component {
+            Component::Normal(name) => path.push(name),
+            Component::RootDir | Component::Prefix(_) | Component::CurDir => {}
+            Component::ParentDir => return None,
+        }
+    }
+
+    (path != dest).then_some(path)
+}
+
+/// Whether a link at `path` below `dest` pointing to `target` stays below `dest`, judged by its
+/// path alone. Absolute targets are never kept.
+fn link_stays_inside(
+    dest: &std::path::Path,
+    path: &std::path::Path,
+    target: &std::path::Path,
+) -> bool {
+    use std::path::Component;
+
+    let Some(mut depth) = path
+        .parent()
+        .and_then(|parent| parent.strip_prefix(dest).ok())
+        .map(|parent| parent.components().count())
+    else {
+        return false;
+    };
+
+    for component in target.components() {
+        match component {
+            Component::Normal(_) => depth += 1,
+            Component::CurDir => {}
+            Component::ParentDir => match depth.checked_sub(1) {
+                Some(parent_depth) => depth = parent_depth,
+                None => return false,
+            },
+            Component::RootDir | Component::Prefix(_) => return false,
+        }
+    }
+
+    true
+}
+
+/// Whether a folder between `dest` and `path` is a link, writing to `path` would follow it.
+fn has_linked_parent(dest: &std::path::Path, path: &std::path::Path) -> bool {
+    path.ancestors()
+        .skip(1)
+        .take_while(|ancestor| *ancestor != dest && ancestor.starts_with(dest))
+        .any(|ancestor| ancestor.is_symlink())
+}
+
+/// Create an archive entry that has no content to download: an empty directory, or a link when
+/// metadata is restored. Returns `false` for entries that have to be downloaded.
+///
+/// Links have to stay below `dest`, other links are downloaded as plain files describing their
+/// target. Links are created after all other entries, so nothing is written through them.
+fn create_placeholder_entry(
+    dest: &std::path::Path,
+    path: &std::path::Path,
+    metadata: &Metadata,
+    restore_metadata: bool,
+    progress: &SharedProgress,
+) -> Result<bool, DownloadError> {
+    if archive_metadata::is_directory(metadata) {
+        std::fs::create_dir_all(path).map_err(io_error)?;
+        return Ok(true);
+    }
+
+    // Elsewhere links are downloaded as plain files describing their target
+    #[cfg(unix)]
+    if restore_metadata {
+        if let Some(target) = archive_metadata::symlink_target(metadata) {
+            if !link_stays_inside(dest, path, &target) {
+                warn!("Not creating link {path:?}, its target {target:?} is outside {dest:?}");
+                return Ok(false);
+            }
+
+            // A link from an earlier attempt
+            let mut progress = lock_progress(progress);
+            if progress.written.get(path).is_some_and(|p| p == path) && path.is_symlink() {
+                return Ok(true);
+            }
+            ensure_parent_dir(path);
+            std::os::unix::fs::symlink(&target, path).map_err(io_error)?;
+            progress
+                .written
+                .insert(path.to_path_buf(), path.to_path_buf());
+            return Ok(true);
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = (dest, restore_metadata, progress);
+
+    Ok(false)
+}
+
+/// Write archive entries below `dest`, keeping their relative layout. Files are fetched with
+/// `download`, which returns where it put a file.
+///
+/// Entries that would leave `dest` are skipped, and nothing is written through a link below
+/// `dest`. Links are created last, and links that resolve outside `dest` through other links are
+/// replaced by plain files.
+pub(crate) async fn write_archive_entries<F, Fut>(
+    entries: Vec<(PathBuf, FileAccess, Metadata)>,
+    dest: &std::path::Path,
+    restore_metadata: bool,
+    progress: &SharedProgress,
+    mut download: F,
+) -> Result<(), DownloadError>
+where
+    F: FnMut(FileAccess, PathBuf) -> Fut,
+    Fut: std::future::Future<Output = Result<PathBuf, DownloadError>>,
+{
+    let _ = std::fs::create_dir_all(dest);
+
+    let (links, others): (Vec<_>, Vec<_>) = entries.into_iter().partition(|(_, _, metadata)| {
+        cfg!(unix) && restore_metadata && archive_metadata::symlink_target(metadata).is_some()
+    });
+
+    let mut written = vec![];
+    let mut created_links = vec![];
+    for (file_path, file_access, metadata) in others.into_iter().chain(links) {
+        let Some(mut full_path) = entry_path(dest, &file_path) else {
+            warn!("Skipping archive entry {file_path:?}, it is outside the download folder");
+            continue;
+        };
+        if has_linked_parent(dest, &full_path) {
+            return Err(DownloadError::UnsafePath(full_path));
+        }
+
+        if !create_placeholder_entry(dest, &full_path, &metadata, restore_metadata, progress)? {
+            full_path = download(file_access, full_path).await?;
+        } else if full_path.is_symlink() {
+            created_links.push((written.len(), file_access));
+        }
+        written.push((full_path, metadata));
+    }
+
+    // Only once all links exist can they be followed to where they really lead
+    if !created_links.is_empty() {
+        let canonical_dest = std::fs::canonicalize(dest).map_err(io_error)?;
+        for (index, file_access) in created_links {
+            let path = written[index].0.clone();
+            if std::fs::canonicalize(&path)
+                .is_ok_and(|resolved| !resolved.starts_with(&canonical_dest))
+            {
+                warn!("Replacing link {path:?}, it resolves outside {dest:?}");
+                std::fs::remove_file(&path).map_err(io_error)?;
+                written[index].0 = download(file_access, path).await?;
+            }
+        }
+    }
+
+    if restore_metadata {
+        archive_metadata::restore_metadata(&written);
+    }
     Ok(())
 }
 
+/// Download archive entries below `dest`, see [`write_archive_entries`].
+///
+/// Entries that earlier attempts of the download wrote are verified and kept, see
+/// [`download_verified`].
+async fn download_archive_entries_to(
+    entries: Vec<(PathBuf, FileAccess, Metadata)>,
+    dest: &std::path::Path,
+    client: &autonomi::Client,
+    restore_metadata: bool,
+    progress: &SharedProgress,
+) -> Result<(), DownloadError> {
+    write_archive_entries(
+        entries,
+        dest,
+        restore_metadata,
+        progress,
+        |file_access, path| async move {
+            download_verified(&file_access, &path, progress, client).await
+        },
+    )
+    .await
+}
+
 async fn download_private_archive(
     data_map: &DataMapChunk,
     dest: PathBuf,
     client: &autonomi::Client,
+    restore_metadata: bool,
+    progress: &SharedProgress,
 ) -> Result<(), DownloadError> {
     let archive = client.archive_get(data_map).await?;
-    let _ = std::fs::create_dir_all(&dest);
+    let entries = archive
+        .map()
+        .iter()
+        .map(|(file_path, (file_data_map, metadata))| {
+            (
+                file_path.clone(),
+                FileAccess::Private(file_data_map.clone()),
+                metadata.clone(),
+            )
+        })
+        .collect();
 
-    for (file_path, (file_data_map, _)) in archive.map() {
-        let full_path = dest.join(file_path);
-        ensure_parent_dir(&full_path);
-        client.file_download(file_data_map, full_path).await?;
-    }
-    Ok(())
+    download_archive_entries_to(entries, &dest, client, restore_metadata, progress).await
 }
 
 pub async fn download_private(
     data_map: &DataMapChunk,
     dest: PathBuf,
+    restore_metadata: bool,
+    progress: &SharedProgress,
     shared_client: State<'_, SharedClient>,
 ) -> Result<(), DownloadError> {
     let client = shared_client.get_client().await?;
@@ -2826,10 +3543,10 @@ pub async fn download_private(
     match client.analyze_address(&hex_addr, true).await {
         Ok(autonomi::client::analyze::Analysis::RawDataMap { .. })
         | Ok(autonomi::client::analyze::Analysis::DataMap { .. }) => {
-            download_private_file(data_map, dest, &client).await
+            download_private_file(data_map, dest, progress, &client).await
         }
         Ok(autonomi::client::analyze::Analysis::PrivateArchive { .. }) => {
-            download_private_archive(data_map, dest, &client).await
+            download_private_archive(data_map, dest, &client, restore_metadata, progress).await
         }
         Ok(_) => Err(DownloadError::Download(
             autonomi::client::files::DownloadError::IoError(std::io::Error::new(
@@ -2844,26 +3561,19 @@ pub async fn download_private(
 async fn download_public_file(
     addr: &DataAddress,
     dest: PathBuf,
+    progress: &SharedProgress,
     client: &autonomi::Client,
 ) -> Result<(), DownloadError> {
-    ensure_parent_dir(&dest);
-
-    // Try streaming first, fallback to direct data if needed
-    match client.file_download_public(addr, dest.clone()).await {
-        Ok(()) => Ok(()),
-        Err(_) => {
-            let data = client.data_get_public(addr).await?;
-            std::fs::write(&dest, data).map_err(|e| {
-                DownloadError::Download(autonomi::client::files::DownloadError::IoError(e))
-            })
-        }
-    }
+    download_verified(&FileAccess::Public(*addr), &dest, progress, client).await?;
+    Ok(())
 }
 
 async fn download_public_archive(
     addr: &DataAddress,
     dest: PathBuf,
     client: &autonomi::Client,
+    restore_metadata: bool,
+    progress: &SharedProgress,
 ) -> Result<(), DownloadError> {
     use autonomi::files::PublicArchive;
 
@@ -2877,20 +3587,26 @@ async fn download_public_archive(
         ))
     })?;
 
-    let _ = std::fs::create_dir_all(&dest);
-
-    for (file_path, (file_addr, _)) in archive.map() {
-        let full_path = dest.join(file_path);
-        ensure_parent_dir(&full_path);
-        client.file_download_public(file_addr, full_path).await?;
-    }
+    let entries = archive
+        .map()
+        .iter()
+        .map(|(file_path, (file_addr, metadata))| {
+            (
+                file_path.clone(),
+                FileAccess::Public(*file_addr),
+                metadata.clone(),
+            )
+        })
+        .collect();
 
-    Ok(())
+    download_archive_entries_to(entries, &dest, client, restore_metadata, progress).await
 }
 
 pub async fn download_public(
     addr: &DataAddress,
     dest: PathBuf,
+    restore_metadata: bool,
+    progress: &SharedProgress,
     shared_client: State<'_, SharedClient>,
 ) -> Result<(), DownloadError> {
     let client = shared_client.get_client().await?;
@@ -2899,10 +3615,10 @@ pub async fn download_public(
     match client.analyze_address(&hex_addr, false).await {
         Ok(autonomi::client::analyze::Analysis::RawDataMap { .. })
         | Ok(autonomi::client::analyze::Analysis::DataMap { .. }) => {
-            download_public_file(addr, dest, &client).await
+            download_public_file(addr, dest, progress, &client).await
         }
         Ok(autonomi::client::analyze::Analysis::PublicArchive { .. }) => {
-            download_public_archive(addr, dest, &client).await
+            download_public_archive(addr, dest, &client, restore_metadata, progress).await
         }
         Ok(_) => Err(DownloadError::Download(
             autonomi::client::files::DownloadError::IoError(std::io::Error::new(
@@ -2914,24 +3630,125 @@ pub async fn download_public(
     }
 }
 
+/// Download a file or a whole archive, see [`download_private`] and [`download_public`].
+pub async fn download(
+    file_access: &FileAccess,
+    dest: PathBuf,
+    restore_metadata: bool,
+    progress: &SharedProgress,
+    shared_client: State<'_, SharedClient>,
+) -> Result<(), DownloadError> {
+    match file_access {
+        FileAccess::Private(data_map) => {
+            download_private(data_map, dest, restore_metadata, progress, shared_client).await
+        }
+        FileAccess::Public(addr) => {
+            download_public(addr, dest, restore_metadata, progress, shared_client).await
+        }
+    }
+}
+
+/// Selected paths relative to the archive root. Paths may come with a leading or trailing
+/// separator from the vault view, paths that select the whole archive are rejected.
+fn parse_selection(paths: &[String]) -> Result<Vec<PathBuf>, DownloadError> {
+    let selection: Vec<PathBuf> = paths
+        .iter()
+        .map(|path| PathBuf::from(path.trim_matches('/')))
+        .collect();
+
+    if selection.is_empty()
+        || selection
+            .iter()
+            .any(|selected| selected.as_os_str().is_empty())
+    {
+        return Err(DownloadError::EmptySelection);
+    }
+    Ok(selection)
+}
+
+/// Whether an archive entry is one of the selected paths, or inside one of them.
+fn is_selected(entry: &std::path::Path, selection: &[PathBuf]) -> bool {
+    selection.iter().any(|selected| entry.starts_with(selected))
+}
+
+/// Download only the entries of an archive that are, or are inside, one of `paths`.
+///
+/// Paths are relative to the archive root, a folder selects everything below it. Entries keep
+/// their relative layout under `dest`. Returns the number of entries downloaded.
+pub async fn download_archive_entries(
+    archive: &FileAccess,
+    paths: &[String],
+    dest: PathBuf,
+    restore_metadata: bool,
+    shared_client: State<'_, SharedClient>,
+) -> Result<usize, DownloadError> {
+    let selection = parse_selection(paths)?;
+    let client = shared_client.get_client().await?;
+
+    let entries: Vec<_> = match archive {
+        FileAccess::Private(data_map) => client
+            .archive_get(data_map)
+            .await?
+            .map()
+            .iter()
+            .filter(|(file_path, _)| is_selected(file_path, &selection))
+            .map(|(file_path, (file_data_map, metadata))| {
+                (
+                    file_path.clone(),
+                    FileAccess::Private(file_data_map.clone()),
+                    metadata.clone(),
+                )
+            })
+            .collect(),
+        FileAccess::Public(addr) => client
+            .archive_get_public(addr)
+            .await?
+            .map()
+            .iter()
+            .filter(|(file_path, _)| is_selected(file_path, &selection))
+            .map(|(file_path, (file_addr, metadata))| {
+                (
+                    file_path.clone(),
+                    FileAccess::Public(*file_addr),
+                    metadata.clone(),
+                )
+            })
+            .collect(),
+    };
+
+    if entries.is_empty() {
+        return Err(DownloadError::NoMatchingEntries(paths.to_vec()));
+    }
+
+    let count = entries.len();
+    info!("Downloading {count} selected archive entries to {dest:?}");
+    let progress = SharedProgress::default();
+    download_archive_entries_to(entries, &dest, &client, restore_metadata, &progress).await?;
+
+    Ok(count)
+}
+
 pub async fn get_single_file_data(
-    vault_key_signature: &str,
+    secret_key: &VaultSecretKey,
     file_path: &str,
     shared_client: State<'_, SharedClient>,
 ) -> Result<FileFromVault, VaultError> {
-    let prefix = &format!("{}{}", "0", "x");
-    let secret_key = vault_key_from_signature_hex(vault_key_signature.trim_start_matches(prefix))
-        .expect(&format!("Invalid vault key {}", "signature"));
     let client = shared_client.get_client().await?;
-    let user_data = client.get_user_data_from_vault(&secret_key).await?;
+    let user_data = client.get_user_data_from_vault(secret_key).await?;
+    let vault_metadata = file_metadata::VaultFileMetadata::fetch(&client, secret_key).await;
+    let store = get_file_metadata_store().ok();
 
     // Try to find the file in individual private files
     for (data_map, name) in &user_data.private_files {
         if name == file_path {
+            let file_access = FileAccess::Private(data_map.clone());
+            let metadata =
+                file_metadata::resolve_file_metadata(&client, &vault_metadata, store, &file_access)
+                    .await;
             return Ok(FileFromVault::new(
                 file_path.to_string(),
-                autonomi::files::Metadata::new_with_size(0),
-                FileAccess::Private(data_map.clone()),
+                metadata,
+                file_access,
             ));
         }
     }
@@ -2939,10 +3756,14 @@ pub async fn get_single_file_data(
     // Try to find the file in individual public files
     for (data_addr, name) in &user_data.public_files {
         if name == file_path {
+            let file_access = FileAccess::Public(*data_addr);
+            let metadata =
+                file_metadata::resolve_file_metadata(&client, &vault_metadata, store, &file_access)
+                    .await;
             return Ok(FileFromVault::new(
                 file_path.to_string(),
-                autonomi::files::Metadata::new_with_size(0),
-                FileAccess::Public(*data_addr),
+                metadata,
+                file_access,
             ));
         }
     }
@@ -3045,27 +3866,24 @@ pub async fn add_local_archive_to_vault(
 ) -> Result<(), VaultError> {
     let client = shared_client.get_client().await?;
 
-    // Debug logging
-    eprintln!("=== ADD LOCAL ARCHIVE TO VAULT DEBUG ===");
-    eprintln!("archive_access: {:?}", archive_access);
-    eprintln!("archive_name: {}", archive_name);
+    info!("Adding local archive to vault: {archive_name}");
 
     // Get current user data from vault
     let mut user_data = match client.get_user_data_from_vault(secret_key).await {
         Ok(data) => {
-            eprintln!("Successfully retrieved user data from vault");
+            info!("Successfully retrieved user data from vault");
             data
         }
         Err(e) => {
-            eprintln!("Failed to get user data from vault: {:?}", e);
+            error!("Failed to get user data from vault: {:?}", e);
             // Check if this is a case where the vault doesn't exist yet
             match &e {
                 UserDataVaultError::GetError(_) | UserDataVaultError::Vault(_) => {
-                    eprintln!("Vault might not exist yet, creating new user data");
+                    info!("Vault might not exist yet, creating new user data");
                     UserData::new()
                 }
                 _ => {
-                    eprintln!("Other vault error, returning error");
+                    error!("Other vault error, returning error");
                     return Err(VaultError::UserDataGet(e));
                 }
             }
@@ -3079,14 +3897,14 @@ pub async fn add_local_archive_to_vault(
             user_data
                 .private_file_archives
                 .insert(data_map, archive_name.to_string());
-            eprintln!("Added private archive to vault: {}", archive_name);
+            info!("Added private archive to vault: {}", archive_name);
         }
         FileAccess::Public(data_addr) => {
             // Add to public archives
             user_data
                 .file_archives
                 .insert(data_addr, archive_name.to_string());
-            eprintln!("Added public archive to vault: {}", archive_name);
+            info!("Added public archive to vault: {}", archive_name);
         }
     }
 
@@ -3099,11 +3917,11 @@ pub async fn add_local_archive_to_vault(
         )
         .await
         .map_err(|e| {
-            eprintln!("Failed to put user data to vault: {:?}", e);
+            error!("Failed to put user data to vault: {:?}", e);
             VaultError::FileNotFound
         })?;
 
-    eprintln!("Successfully updated vault with new archive");
+    info!("Successfully updated vault with new archive");
     Ok(())
 }
 
@@ -3115,48 +3933,49 @@ pub async fn add_local_file_to_vault(
 ) -> Result<(), VaultError> {
     let client = shared_client.get_client().await?;
 
-    // Debug logging
-    eprintln!("=== ADD LOCAL FILE TO VAULT DEBUG ===");
-    eprintln!("file_access: {:?}", file_access);
-    eprintln!("file_name: {}", file_name);
+    info!("Adding local file to vault: {file_name}");
 
     // Get current user data from vault
     let mut user_data = match client.get_user_data_from_vault(secret_key).await {
         Ok(data) => {
-            eprintln!("Successfully retrieved user data from vault");
+            info!("Successfully retrieved user data from vault");
             data
         }
         Err(e) => {
-            eprintln!("Failed to get user data from vault: {:?}", e);
+            error!("Failed to get user data from vault: {:?}", e);
             // Check if this is a case where the vault doesn't exist yet
             match &e {
                 UserDataVaultError::GetError(_) | UserDataVaultError::Vault(_) => {
-                    eprintln!("Vault might not exist yet, creating new user data");
+                    info!("Vault might not exist yet, creating new user data");
                     UserData::new()
                 }
                 _ => {
-                    eprintln!("Other vault error, returning error");
+                    error!("Other vault error, returning error");
                     return Err(VaultError::UserDataGet(e));
                 }
             }
         }
     };
 
+    let recorded_metadata = get_file_metadata_store()
+        .ok()
+        .and_then(|store| store.load(&file_access));
+
     // Handle both private and public files
-    match file_access {
+    match file_access.clone() {
         FileAccess::Private(data_map) => {
             // Add to private files
             user_data
                 .private_files
                 .insert(data_map, file_name.to_string());
-            eprintln!("Added private file to vault: {}", file_name);
+            info!("Added private file to vault: {}", file_name);
         }
         FileAccess::Public(data_addr) => {
             // Add to public files
             user_data
                 .public_files
                 .insert(data_addr, file_name.to_string());
-            eprintln!("Added public file to vault: {}", file_name);
+            info!("Added public file to vault: {}", file_name);
         }
     }
 
@@ -3169,10 +3988,289 @@ pub async fn add_local_file_to_vault(
         )
         .await
         .map_err(|e| {
-            eprintln!("Failed to put user data to vault: {:?}", e);
+            error!("Failed to put user data to vault: {:?}", e);
             VaultError::FileNotFound
         })?;
 
-    eprintln!("Successfully updated vault with new file");
+    if let Some(metadata) = recorded_metadata {
+        file_metadata::write_vault_metadata_if_free(&client, secret_key, file_access, metadata)
+            .await;
+    }
+
+    info!("Successfully updated vault with new file");
+    Ok(())
+}
+
+/// A vault entry, identified the same way as in [`remove_from_vault`].
+#[derive(Debug, Deserialize)]
+pub struct VaultEntryRef {
+    pub file_path: String,
+    pub archive_address: Option<String>,
+}
+
+/// Copy entries from one vault to another, keeping their names.
+pub async fn copy_vault_entries(
+    from_key: &VaultSecretKey,
+    to_key: &VaultSecretKey,
+    entries: &[VaultEntryRef],
+    shared_client: State<'_, SharedClient>,
+) -> Result<(), VaultError> {
+    let client = shared_client.get_client().await?;
+
+    let source = client.get_user_data_from_vault(from_key).await?;
+
+    let mut target = match client.get_user_data_from_vault(to_key).await {
+        Ok(data) => data,
+        // The target vault might not exist yet
+        Err(UserDataVaultError::GetError(_) | UserDataVaultError::Vault(_)) => UserData::new(),
+        Err(err) => return Err(VaultError::UserDataGet(err)),
+    };
+
+    for entry in entries {
+        let found = match &entry.archive_address {
+            Some(address) => {
+                if let Some((data_map, name)) = source
+                    .private_file_archives
+                    .iter()
+                    .find(|(data_map, _)| data_map.to_hex() == *address)
+                {
+                    target
+                        .private_file_archives
+                        .insert(data_map.clone(), name.clone());
+                    true
+                } else if let Some((data_addr, name)) = source
+                    .file_archives
+                    .iter()
+                    .find(|(data_addr, _)| data_addr.to_hex() == *address)
+                {
+                    target.file_archives.insert(*data_addr, name.clone());
+                    true
+                } else {
+                    false
+                }
+            }
+            None => {
+                if let Some((data_map, name)) = source
+                    .private_files
+                    .iter()
+                    .find(|(_, name)| **name == entry.file_path)
+                {
+                    target.private_files.insert(data_map.clone(), name.clone());
+                    true
+                } else if let Some((data_addr, name)) = source
+                    .public_files
+                    .iter()
+                    .find(|(_, name)| **name == entry.file_path)
+                {
+                    target.public_files.insert(*data_addr, name.clone());
+                    true
+                } else {
+                    false
+                }
+            }
+        };
+
+        if !found {
+            warn!("Vault entry not found: {}", entry.file_path);
+            return Err(VaultError::FileNotFound);
+        }
+    }
+
+    info!("Copying {} entries between vaults", entries.len());
+
+    client
+        .put_user_data_to_vault(to_key, PaymentOption::Receipt(Default::default()), target)
+        .await
+        .map_err(|err| VaultError::Update(err.to_string()))?;
+
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    fn selection(paths: &[&str]) -> Result<Vec<PathBuf>, DownloadError> {
+        let paths: Vec<String> = paths.iter().map(|path| path.to_string()).collect();
+        parse_selection(&paths)
+    }
+
+    #[test]
+    fn test_parse_selection() {
+        assert_eq!(
+            selection(&["/photos/", "docs/notes.txt"]).unwrap(),
+            vec![PathBuf::from("photos"), PathBuf::from("docs/notes.txt")]
+        );
+
+        for paths in [&[][..], &[""], &["/"], &["photos", "//"]] {
+            assert!(
+                matches!(selection(paths), Err(DownloadError::EmptySelection)),
+                "{paths:?} selects everything"
+            );
+        }
+    }
+
+    #[test]
+    fn test_is_selected() {
+        let selection = selection(&["photos/2024", "docs/notes.txt"]).unwrap();
+
+        assert!(is_selected(Path::new("photos/2024"), &selection));
+        assert!(is_selected(Path::new("photos/2024/beach.jpg"), &selection));
+        assert!(is_selected(Path::new("docs/notes.txt"), &selection));
+
+        assert!(!is_selected(Path::new("photos"), &selection));
+        assert!(!is_selected(Path::new("photos/2023/beach.jpg"), &selection));
+        // Whole components only
+        assert!(!is_selected(Path::new("photos/2024-old/a.jpg"), &selection));
+        assert!(!is_selected(Path::new("docs/notes.txt.bak"), &selection));
+    }
+
+    #[test]
+    fn test_unique_download_path() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("photo.jpg");
+        assert_eq!(unique_download_path(&path), Some(path.clone()));
+
+        std::fs::write(&path, b"taken").unwrap();
+        assert_eq!(
+            unique_download_path(&path),
+            Some(dir.path().join("photo (1).jpg"))
+        );
+
+        std::fs::write(dir.path().join("photo (1).jpg"), b"taken").unwrap();
+        assert_eq!(
+            unique_download_path(&path),
+            Some(dir.path().join("photo (2).jpg"))
+        );
+
+        let notes = dir.path().join("notes");
+        std::fs::write(&notes, b"taken").unwrap();
+        assert_eq!(
+            unique_download_path(&notes),
+            Some(dir.path().join("notes (1)"))
+        );
+    }
+
+    /// The access a download of `content` is verified against.
+    fn access_for(content: &[u8]) -> FileAccess {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("content");
+        std::fs::write(&path, content).unwrap();
+
+        tauri::async_runtime::block_on(async {
+            let mut streams = encrypt_file_or_folder(path, false).await.unwrap();
+            let stream = &mut streams[0];
+            content_addresses_from_encryption_stream(stream).await;
+            FileAccess::Private(stream.data_map_chunk().unwrap())
+        })
+    }
+
+    fn download_writing(
+        dest: &Path,
+        file_access: &FileAccess,
+        content: &'static [u8],
+        fail: bool,
+    ) -> Result<PathBuf, DownloadError> {
+        download_resuming(dest, file_access, content, fail, &SharedProgress::default())
+    }
+
+    fn download_resuming(
+        dest: &Path,
+        file_access: &FileAccess,
+        content: &'static [u8],
+        fail: bool,
+        progress: &SharedProgress,
+    ) -> Result<PathBuf, DownloadError> {
+        tauri::async_runtime::block_on(download_verified_with(
+            file_access,
+            dest,
+            progress,
+            |temp_path| async move {
+                std::fs::write(temp_path, content).map_err(io_error)?;
+                if fail {
+                    return Err(io_error(std::io::ErrorKind::ConnectionReset.into()));
+                }
+                Ok(())
+            },
+        ))
+    }
+
+    fn dir_entries(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_download_verified() {
+        let dir = TempDir::new().unwrap();
+        let dest = dir.path().join("report.txt");
+        let content: &[u8] = b"quarterly numbers";
+        let file_access = access_for(content);
+
+        let path = download_writing(&dest, &file_access, content, false).unwrap();
+        assert_eq!(path, dest);
+        assert_eq!(std::fs::read(&dest).unwrap(), content);
+
+        // An existing file is kept, the download gets a numbered name
+        let path = download_writing(&dest, &file_access, content, false).unwrap();
+        assert_eq!(path, dir.path().join("report (1).txt"));
+        assert_eq!(dir_entries(dir.path()), ["report (1).txt", "report.txt"]);
+    }
+
+    #[test]
+    fn test_download_verified_leaves_no_partial_file() {
+        let dir = TempDir::new().unwrap();
+        let dest = dir.path().join("report.txt");
+        let file_access = access_for(b"quarterly numbers");
+
+        let result = download_writing(&dest, &file_access, b"quarterly", true);
+        assert!(matches!(result, Err(DownloadError::Download(_))));
+        assert!(dir_entries(dir.path()).is_empty());
+    }
+
+    #[test]
+    fn test_download_verified_reports_mismatch() {
+        let dir = TempDir::new().unwrap();
+        let dest = dir.path().join("report.txt");
+        let file_access = access_for(b"quarterly numbers");
+
+        let result = download_writing(&dest, &file_access, b"tampered numbers", false);
+        assert!(matches!(result, Err(DownloadError::Verification(_))));
+        assert!(dir_entries(dir.path()).is_empty());
+    }
+
+    #[test]
+    fn test_download_verified_resumes_only_its_own_files() {
+        let dir = TempDir::new().unwrap();
+        let dest = dir.path().join("report.txt");
+        let content: &[u8] = b"quarterly numbers";
+        let file_access = access_for(content);
+        std::fs::write(&dest, b"the user's own report").unwrap();
+
+        let progress = SharedProgress::default();
+        let path = download_resuming(&dest, &file_access, content, false, &progress).unwrap();
+        let downloaded = dir.path().join("report (1).txt");
+        assert_eq!(path, downloaded);
+
+        // A file of an earlier attempt that verifies is kept
+        let path = download_resuming(&dest, &file_access, content, false, &progress).unwrap();
+        assert_eq!(path, downloaded);
+        assert_eq!(dir_entries(dir.path()), ["report (1).txt", "report.txt"]);
+
+        // One that doesn't is replaced, the user's file is never touched
+        std::fs::write(&downloaded, b"truncated").unwrap();
+        let path = download_resuming(&dest, &file_access, content, false, &progress).unwrap();
+        assert_eq!(path, downloaded);
+        assert_eq!(std::fs::read(&downloaded).unwrap(), content);
+        assert_eq!(std::fs::read(&dest).unwrap(), b"the user's own report");
+
+        // Partial files are tracked while in flight only
+        assert!(lock_progress(&progress).partial.is_empty());
+    }
+}
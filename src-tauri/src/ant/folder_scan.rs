@@ -0,0 +1,548 @@
+//! Walking folders for upload.
+//!
+//! Only regular files are uploaded. Symbolic links follow the [`SymlinkPolicy`] from the
+//! settings, special files such as sockets and FIFOs are skipped, and files or folders that can't
+//! be read are skipped instead of failing the whole upload. Everything that wasn't uploaded as a
+//! regular file ends up in the [`FileReport`]s of the scan.
+
+use crate::ant::archive_metadata;
+use crate::ant::files::UploadError;
+use crate::ant::ignore_rules::IgnoreRules;
+use autonomi::chunk::{Chunk, DataMapChunk};
+use autonomi::files::Metadata;
+use autonomi::Bytes;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use tokio::fs;
+use tracing::info;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SymlinkPolicy {
+    /// Upload what the link points to, links that lead back into a visited folder are skipped.
+    #[default]
+    Follow,
+    /// Upload the link itself, its target is kept in the archive entry's metadata.
+    Link,
+    Skip,
+}
+
+static SYMLINK_POLICY: RwLock<SymlinkPolicy> = RwLock::new(SymlinkPolicy::Follow);
+
+/// Use the symlink policy from the settings, `None` for the default.
+pub fn set_symlink_policy(policy: Option<SymlinkPolicy>) {
+    if let Ok(mut symlink_policy) = SYMLINK_POLICY.write() {
+        *symlink_policy = policy.unwrap_or_default();
+    }
+}
+
+fn symlink_policy() -> SymlinkPolicy {
+    SYMLINK_POLICY
+        .read()
+        .map(|policy| *policy)
+        .unwrap_or_default()
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub enum SkipReason {
+    Symlink,
+    SymlinkLoop,
+    BrokenSymlink,
+    SpecialFile,
+    Unreadable(String),
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type")]
+pub enum FileOutcome {
+    Link { target: PathBuf },
+    Skipped { reason: SkipReason },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileReport {
+    /// Path relative to the parent of the scanned folder, as it appears in the archive.
+    pub path: PathBuf,
+    #[serde(flatten)]
+    pub outcome: FileOutcome,
+}
+
+/// An archive entry without file content of its own: a symbolic link stored as a link, or an
+/// empty directory.
+#[derive(Debug, Clone)]
+pub struct Placeholder {
+    pub relative_path: PathBuf,
+    pub metadata: Metadata,
+}
+
+/// Stored content of a placeholder, so it still reads sensibly when downloaded as a plain file.
+pub(crate) fn placeholder_content(metadata: &Metadata) -> Option<Bytes> {
+    if let Some(target) = archive_metadata::symlink_target(metadata) {
+        Some(Bytes::from(format!("symlink to {}\n", target.display())))
+    } else if archive_metadata::is_directory(metadata) {
+        Some(Bytes::from_static(b"empty directory\n"))
+    } else {
+        None
+    }
+}
+
+impl Placeholder {
+    fn new(relative_path: PathBuf, mut metadata: Metadata) -> Self {
+        metadata.size = placeholder_content(&metadata).map_or(0, |content| content.len() as u64);
+        Self {
+            relative_path,
+            metadata,
+        }
+    }
+
+    pub fn link(relative_path: PathBuf, target: &Path) -> Self {
+        let mut metadata = Metadata::new_with_size(0);
+        archive_metadata::set_symlink_target(&mut metadata, target);
+        Self::new(relative_path, metadata)
+    }
+
+    /// An empty directory, keeping the directory's own times and mode.
+    pub fn empty_directory(relative_path: PathBuf, path: &Path) -> Self {
+        let mut metadata = archive_metadata::metadata_from_disk(path)
+            .unwrap_or_else(|_| Metadata::new_with_size(0));
+        archive_metadata::set_directory(&mut metadata);
+        Self::new(relative_path, metadata)
+    }
+
+    /// Encrypt the placeholder's content, returns its datamap and the chunks to upload.
+    pub fn encrypt(&self, is_public: bool) -> Result<(DataMapChunk, Vec<Chunk>), UploadError> {
+        encrypt_placeholder(&self.metadata, is_public)
+    }
+}
+
+fn encrypt_placeholder(
+    metadata: &Metadata,
+    is_public: bool,
+) -> Result<(DataMapChunk, Vec<Chunk>), UploadError> {
+    let content = placeholder_content(metadata)
+        .ok_or_else(|| UploadError::Encryption("Entry is not a placeholder".to_string()))?;
+    let (data_map, mut chunks) = autonomi::self_encryption::encrypt(content)
+        .map_err(|err| UploadError::Encryption(err.to_string()))?;
+
+    // Public data is found through its datamap, which is stored as a chunk as well
+    if is_public {
+        chunks.push(data_map.clone());
+    }
+
+    Ok((DataMapChunk::from(data_map), chunks))
+}
+
+/// Chunks of the placeholders in an archive, to upload them along with the archive's files.
+pub fn placeholder_chunks<'a>(
+    entries: impl Iterator<Item = &'a Metadata>,
+    is_public: bool,
+) -> Result<Vec<Chunk>, UploadError> {
+    let mut chunks = vec![];
+    for metadata in entries.filter(|metadata| placeholder_content(metadata).is_some()) {
+        chunks.extend(encrypt_placeholder(metadata, is_public)?.1);
+    }
+
+    Ok(chunks)
+}
+
+#[derive(Debug, Default)]
+pub struct FolderScan {
+    /// Regular files as (relative_path, absolute_path).
+    pub files: Vec<(PathBuf, PathBuf)>,
+    /// Links stored as links and empty directories.
+    pub placeholders: Vec<Placeholder>,
+    pub report: Vec<FileReport>,
+}
+
+impl FolderScan {
+    /// Total size of the regular files that were found.
+    pub async fn total_size(&self) -> Result<u64, UploadError> {
+        let mut total_size = 0;
+        for (_, path) in &self.files {
+            total_size += fs::metadata(path)
+                .await
+                .map_err(|_| UploadError::Read(path.clone()))?
+                .len();
+        }
+
+        Ok(total_size)
+    }
+
+    fn skip(&mut self, path: PathBuf, reason: SkipReason) {
+        info!("Skipping {path:?}: {reason:?}");
+        self.report.push(FileReport {
+            path,
+            outcome: FileOutcome::Skipped { reason },
+        });
+    }
+}
+
+/// Walk a folder and its subfolders, skipping what matches the folder's [`IgnoreRules`].
+///
+/// Paths are relative to the parent of the folder, so they include the folder's name. Folders
+/// without anything to upload become empty directory placeholders. Only an unreadable root folder
+/// is an error.
+pub async fn scan_directory(dir_path: PathBuf) -> Result<FolderScan, UploadError> {
+    scan_with_policy(dir_path, symlink_policy()).await
+}
+
+async fn scan_with_policy(
+    dir_path: PathBuf,
+    policy: SymlinkPolicy,
+) -> Result<FolderScan, UploadError> {
+    let mut scan = FolderScan::default();
+    let ignore_rules = IgnoreRules::for_folder(&dir_path);
+
+    let base_dir = dir_path.parent().unwrap_or(&dir_path).to_path_buf();
+    let relative = |path: &Path| {
+        path.strip_prefix(&base_dir)
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|_| path.to_path_buf())
+    };
+
+    let mut visited = HashSet::new();
+    if let Ok(canonical) = fs::canonicalize(&dir_path).await {
+        visited.insert(canonical);
+    }
+
+    let mut queue = VecDeque::new();
+    queue.push_back(dir_path.clone());
+
+    while let Some(current_dir) = queue.pop_front() {
+        let mut entries = match fs::read_dir(&current_dir).await {
+            Ok(entries) => entries,
+            Err(_) if current_dir == dir_path => return Err(UploadError::Read(current_dir)),
+            Err(err) => {
+                scan.skip(
+                    relative(&current_dir),
+                    SkipReason::Unreadable(err.to_string()),
+                );
+                continue;
+            }
+        };
+
+        // Folders where nothing is kept are stored as empty directories
+        let mut is_empty = true;
+
+        loop {
+            let entry = match entries.next_entry().await {
+                Ok(Some(entry)) => entry,
+                Ok(None) => break,
+                Err(err) => {
+                    scan.skip(
+                        relative(&current_dir),
+                        SkipReason::Unreadable(err.to_string()),
+                    );
+                    is_empty = false;
+                    break;
+                }
+            };
+            let path = entry.path();
+
+            let file_type = match entry.file_type().await {
+                Ok(file_type) => file_type,
+                Err(err) => {
+                    scan.skip(relative(&path), SkipReason::Unreadable(err.to_string()));
+                    continue;
+                }
+            };
+
+            let is_symlink = file_type.is_symlink();
+            // Symlinks are judged by what they point to when followed
+            let (is_dir, is_file) = if is_symlink && policy == SymlinkPolicy::Follow {
+                match fs::metadata(&path).await {
+                    Ok(metadata) => (metadata.is_dir(), metadata.is_file()),
+                    Err(_) => {
+                        scan.skip(relative(&path), SkipReason::BrokenSymlink);
+                        continue;
+                    }
+                }
+            } else {
+                (file_type.is_dir(), file_type.is_file())
+            };
+
+            if ignore_rules.is_ignored(&path, is_dir) {
+                continue;
+            }
+
+            if is_symlink {
+                match policy {
+                    SymlinkPolicy::Skip => {
+                        scan.skip(relative(&path), SkipReason::Symlink);
+                        continue;
+                    }
+                    SymlinkPolicy::Link => {
+                        match fs::read_link(&path).await {
+                            Ok(target) => {
+                                scan.report.push(FileReport {
+                                    path: relative(&path),
+                                    outcome: FileOutcome::Link {
+                                        target: target.clone(),
+                                    },
+                                });
+                                scan.placeholders
+                                    .push(Placeholder::link(relative(&path), &target));
+                                is_empty = false;
+                            }
+                            Err(err) => {
+                                scan.skip(relative(&path), SkipReason::Unreadable(err.to_string()))
+                            }
+                        }
+                        continue;
+                    }
+                    SymlinkPolicy::Follow => {}
+                }
+            }
+
+            if is_dir {
+                // Don't walk into a folder twice, followed links can lead back up the tree
+                match fs::canonicalize(&path).await {
+                    Ok(canonical) if !visited.contains(&canonical) => {
+                        visited.insert(canonical);
+                        queue.push_back(path);
+                        is_empty = false;
+                    }
+                    Ok(_) => scan.skip(relative(&path), SkipReason::SymlinkLoop),
+                    Err(err) => scan.skip(relative(&path), SkipReason::Unreadable(err.to_string())),
+                }
+            } else if !is_file {
+                // Sockets, FIFOs and devices can block or never end when read
+                scan.skip(relative(&path), SkipReason::SpecialFile);
+            } else if let Err(err) = fs::File::open(&path).await {
+                scan.skip(relative(&path), SkipReason::Unreadable(err.to_string()));
+            } else {
+                scan.files.push((relative(&path), path));
+                is_empty = false;
+            }
+        }
+
+        if is_empty {
+            scan.placeholders.push(Placeholder::empty_directory(
+                relative(&current_dir),
+                &current_dir,
+            ));
+        }
+    }
+
+    Ok(scan)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    /// A folder `root` with `root/file.txt` and `root/sub/nested.txt`.
+    fn folder() -> (TempDir, PathBuf) {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().join("root");
+        fs::create_dir_all(root.join("sub")).unwrap();
+        fs::write(root.join("file.txt"), b"file").unwrap();
+        fs::write(root.join("sub/nested.txt"), b"nested").unwrap();
+        (dir, root)
+    }
+
+    fn scan(root: &Path, policy: SymlinkPolicy) -> FolderScan {
+        tauri::async_runtime::block_on(scan_with_policy(root.to_path_buf(), policy)).unwrap()
+    }
+
+    fn files(scan: &FolderScan) -> Vec<PathBuf> {
+        let mut files: Vec<_> = scan.files.iter().map(|(path, _)| path.clone()).collect();
+        files.sort();
+        files
+    }
+
+    fn skipped(scan: &FolderScan, path: &str) -> Option<SkipReason> {
+        scan.report.iter().find_map(|report| match &report.outcome {
+            FileOutcome::Skipped { reason } if report.path == Path::new(path) => {
+                Some(reason.clone())
+            }
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn test_regular_files() {
+        let (_dir, root) = folder();
+
+        let scan = scan(&root, SymlinkPolicy::Follow);
+
+        assert_eq!(
+            files(&scan),
+            vec![
+                PathBuf::from("root/file.txt"),
+                PathBuf::from("root/sub/nested.txt")
+            ]
+        );
+        assert!(scan.placeholders.is_empty());
+        assert!(scan.report.is_empty());
+    }
+
+    #[test]
+    fn test_empty_directory_placeholder() {
+        let (_dir, root) = folder();
+        fs::create_dir(root.join("empty")).unwrap();
+
+        let scan = scan(&root, SymlinkPolicy::Follow);
+
+        assert_eq!(scan.placeholders.len(), 1);
+        assert_eq!(
+            scan.placeholders[0].relative_path,
+            PathBuf::from("root/empty")
+        );
+        assert!(archive_metadata::is_directory(
+            &scan.placeholders[0].metadata
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_follow_symlinks() {
+        let (dir, root) = folder();
+        let outside = dir.path().join("outside.txt");
+        fs::write(&outside, b"outside").unwrap();
+        std::os::unix::fs::symlink(&outside, root.join("link.txt")).unwrap();
+        std::os::unix::fs::symlink(dir.path().join("missing"), root.join("broken")).unwrap();
+
+        let scan = scan(&root, SymlinkPolicy::Follow);
+
+        assert!(files(&scan).contains(&PathBuf::from("root/link.txt")));
+        assert!(matches!(
+            skipped(&scan, "root/broken"),
+            Some(SkipReason::BrokenSymlink)
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_loop() {
+        let (_dir, root) = folder();
+        // Leads back up to the root folder
+        std::os::unix::fs::symlink(&root, root.join("sub/loop")).unwrap();
+
+        let scan = scan(&root, SymlinkPolicy::Follow);
+
+        assert_eq!(files(&scan).len(), 2);
+        assert!(matches!(
+            skipped(&scan, "root/sub/loop"),
+            Some(SkipReason::SymlinkLoop)
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_link_symlinks() {
+        let (_dir, root) = folder();
+        std::os::unix::fs::symlink("sub/nested.txt", root.join("link.txt")).unwrap();
+        std::os::unix::fs::symlink(&root, root.join("sub/loop")).unwrap();
+
+        let scan = scan(&root, SymlinkPolicy::Link);
+
+        assert_eq!(files(&scan).len(), 2);
+        let mut links: Vec<_> = scan
+            .placeholders
+            .iter()
+            .map(|placeholder| {
+                (
+                    placeholder.relative_path.clone(),
+                    archive_metadata::symlink_target(&placeholder.metadata).unwrap(),
+                )
+            })
+            .collect();
+        links.sort();
+        assert_eq!(
+            links,
+            vec![
+                (
+                    PathBuf::from("root/link.txt"),
+                    PathBuf::from("sub/nested.txt")
+                ),
+                (PathBuf::from("root/sub/loop"), root.clone()),
+            ]
+        );
+        assert!(scan
+            .report
+            .iter()
+            .all(|report| matches!(report.outcome, FileOutcome::Link { .. })));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_skip_symlinks() {
+        let (_dir, root) = folder();
+        std::os::unix::fs::symlink("sub/nested.txt", root.join("link.txt")).unwrap();
+        std::os::unix::fs::symlink(&root, root.join("sub/loop")).unwrap();
+
+        let scan = scan(&root, SymlinkPolicy::Skip);
+
+        assert_eq!(files(&scan).len(), 2);
+        assert!(scan.placeholders.is_empty());
+        assert!(matches!(
+            skipped(&scan, "root/link.txt"),
+            Some(SkipReason::Symlink)
+        ));
+        assert!(matches!(
+            skipped(&scan, "root/sub/loop"),
+            Some(SkipReason::Symlink)
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_fifo_is_skipped() {
+        let (_dir, root) = folder();
+        let fifo = root.join("fifo");
+        let status = std::process::Command::new("mkfifo")
+            .arg(&fifo)
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let scan = scan(&root, SymlinkPolicy::Follow);
+
+        assert_eq!(files(&scan).len(), 2);
+        assert!(matches!(
+            skipped(&scan, "root/fifo"),
+            Some(SkipReason::SpecialFile)
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unreadable_file_is_skipped() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let (_dir, root) = folder();
+        let secret = root.join("secret.txt");
+        fs::write(&secret, b"secret").unwrap();
+        fs::set_permissions(&secret, fs::Permissions::from_mode(0o000)).unwrap();
+
+        // Permissions don't apply to root
+        if fs::File::open(&secret).is_ok() {
+            return;
+        }
+
+        let scan = scan(&root, SymlinkPolicy::Follow);
+
+        assert_eq!(files(&scan).len(), 2);
+        assert!(matches!(
+            skipped(&scan, "root/secret.txt"),
+            Some(SkipReason::Unreadable(_))
+        ));
+    }
+
+    #[test]
+    fn test_unreadable_root_is_an_error() {
+        let dir = TempDir::new().unwrap();
+
+        let result = tauri::async_runtime::block_on(scan_with_policy(
+            dir.path().join("missing"),
+            SymlinkPolicy::Follow,
+        ));
+
+        assert!(matches!(result, Err(UploadError::Read(_))));
+    }
+}
@@ -0,0 +1,132 @@
+//! Connection health monitoring.
+//!
+//! A background task checks the connection on an interval by looking up the peers closest to a
+//! random address, and emits a `network-status` event whenever the status changes. A lost
+//! connection is dropped and re-established with exponential backoff, while commands fail fast
+//! with [`ClientError::Offline`](crate::ant::client::ClientError::Offline) in the meantime.
+
+use crate::ant::client::SharedClient;
+use autonomi::chunk::ChunkAddress;
+use autonomi::XorName;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::time::{sleep, timeout};
+use tracing::{info, warn};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+const CHECK_TIMEOUT: Duration = Duration::from_secs(20);
+pub const CONNECT_TIMEOUT: Duration = Duration::from_secs(60);
+const MIN_BACKOFF: Duration = Duration::from_secs(5);
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+/// Below this many peers answering a lookup the connection is considered degraded.
+const MIN_HEALTHY_PEERS: usize = 5;
+
+/// Failed checks in a row after which the client is dropped and the network considered offline.
+const MAX_FAILED_CHECKS: u32 = 3;
+
+/// Status of the connection. `Connected` and `Degraded` hold the number of peers that answered the
+/// last lookup of the peers closest to an address, not the number of connected peers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", content = "closestPeers")]
+pub enum NetworkStatus {
+    #[default]
+    Connecting,
+    Connected(usize),
+    Degraded(usize),
+    Offline,
+}
+
+/// Record a new status and let the frontend know if it changed.
+pub async fn set_status(app: &AppHandle, shared_client: &SharedClient, status: NetworkStatus) {
+    if shared_client.set_status(status).await {
+        info!("network status: {status:?}");
+        if let Err(err) = app.emit("network-status", status) {
+            warn!("Failed to emit network status: {err}");
+        }
+    }
+}
+
+/// Number of peers a closest-peers lookup of a random address returned, or `None` if the lookup
+/// failed.
+async fn closest_peers(shared_client: &SharedClient) -> Option<usize> {
+    let client = shared_client.current_client().await?;
+    let address = ChunkAddress::new(XorName::from_content(&rand::random::<[u8; 32]>()));
+
+    match timeout(CHECK_TIMEOUT, client.get_closest_to_address(address, None)).await {
+        Ok(Ok(peers)) if !peers.is_empty() => Some(peers.len()),
+        Ok(Ok(_)) => None,
+        Ok(Err(err)) => {
+            warn!("Health check failed: {err:?}");
+            None
+        }
+        Err(_) => {
+            warn!("Health check timed out");
+            None
+        }
+    }
+}
+
+/// Check the connection once, `None` if the check failed.
+pub async fn check(shared_client: &SharedClient) -> Option<NetworkStatus> {
+    closest_peers(shared_client).await.map(|peers| {
+        if peers >= MIN_HEALTHY_PEERS {
+            NetworkStatus::Connected(peers)
+        } else {
+            NetworkStatus::Degraded(peers)
+        }
+    })
+}
+
+pub async fn monitor(app: AppHandle) {
+    let shared_client = app.state::<SharedClient>();
+    let mut backoff = MIN_BACKOFF;
+    let mut failed_checks = 0;
+
+    loop {
+        if shared_client.current_client().await.is_none() {
+            set_status(&app, &shared_client, NetworkStatus::Connecting).await;
+
+            match timeout(CONNECT_TIMEOUT, shared_client.connect()).await {
+                Ok(Ok(_)) => failed_checks = 0,
+                Ok(Err(err)) => warn!("Failed to connect: {err}"),
+                Err(_) => warn!("Connecting timed out"),
+            }
+
+            if shared_client.current_client().await.is_none() {
+                set_status(&app, &shared_client, NetworkStatus::Offline).await;
+                info!("retrying connection in {backoff:?}");
+                sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+                continue;
+            }
+        }
+
+        let status = match check(&shared_client).await {
+            Some(status) => {
+                failed_checks = 0;
+                backoff = MIN_BACKOFF;
+                status
+            }
+            None => {
+                failed_checks += 1;
+                if failed_checks >= MAX_FAILED_CHECKS {
+                    warn!("{failed_checks} health checks failed in a row, dropping the connection");
+                    shared_client.disconnect().await;
+                    NetworkStatus::Offline
+                } else {
+                    NetworkStatus::Degraded(0)
+                }
+            }
+        };
+        set_status(&app, &shared_client, status).await;
+
+        if status == NetworkStatus::Offline {
+            sleep(backoff).await;
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        } else {
+            sleep(CHECK_INTERVAL).await;
+        }
+    }
+}
@@ -0,0 +1,135 @@
+//! Ignore rules for folder uploads.
+//!
+//! Rules use the `.gitignore` syntax and come from the global patterns in
+//! [`AppData`](crate::ant::app_data::AppData) and from a `.autonomiignore` file at the root of the
+//! uploaded folder. They apply wherever a folder is walked: when computing sizes, encrypting,
+//! building archives and hashing for the payment cache.
+
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use tracing::warn;
+
+pub const IGNORE_FILE_NAME: &str = ".autonomiignore";
+
+/// Global patterns used when none are configured.
+pub const DEFAULT_IGNORE_PATTERNS: &[&str] = &[
+    ".git/",
+    "node_modules/",
+    ".DS_Store",
+    "Thumbs.db",
+    "*.swp",
+    "*.swo",
+    "*~",
+];
+
+static GLOBAL_PATTERNS: RwLock<Option<Vec<String>>> = RwLock::new(None);
+
+/// Use the global patterns from the settings, `None` for the defaults.
+pub fn set_global_patterns(patterns: Option<Vec<String>>) {
+    if let Ok(mut global_patterns) = GLOBAL_PATTERNS.write() {
+        *global_patterns = patterns;
+    }
+}
+
+pub struct IgnoreRules {
+    root: PathBuf,
+    matcher: Gitignore,
+}
+
+impl IgnoreRules {
+    /// The rules for a folder, from the global patterns and the folder's `.autonomiignore`.
+    pub fn for_folder(root: &Path) -> Self {
+        let mut builder = GitignoreBuilder::new(root);
+
+        let global_patterns = GLOBAL_PATTERNS
+            .read()
+            .ok()
+            .and_then(|patterns| patterns.clone())
+            .unwrap_or_else(|| {
+                DEFAULT_IGNORE_PATTERNS
+                    .iter()
+                    .map(|pattern| pattern.to_string())
+                    .collect()
+            });
+        for pattern in &global_patterns {
+            if let Err(err) = builder.add_line(None, pattern) {
+                warn!("Skipping invalid ignore pattern {pattern:?}: {err}");
+            }
+        }
+
+        let ignore_file = root.join(IGNORE_FILE_NAME);
+        if ignore_file.is_file() {
+            if let Some(err) = builder.add(&ignore_file) {
+                warn!("Failed to read {}: {err}", ignore_file.display());
+            }
+        }
+
+        let matcher = builder.build().unwrap_or_else(|err| {
+            warn!("Invalid ignore rules for {}: {err}", root.display());
+            Gitignore::empty()
+        });
+
+        Self {
+            root: root.to_path_buf(),
+            matcher,
+        }
+    }
+
+    /// Whether a path inside the folder is ignored, either itself or through one of its parents.
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let Ok(relative_path) = path.strip_prefix(&self.root) else {
+            return false;
+        };
+        if relative_path.as_os_str().is_empty() {
+            return false;
+        }
+
+        self.matcher
+            .matched_path_or_any_parents(relative_path, is_dir)
+            .is_ignore()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_default_patterns() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let rules = IgnoreRules::for_folder(root);
+
+        assert!(rules.is_ignored(&root.join(".git"), true));
+        assert!(rules.is_ignored(&root.join(".git/config"), false));
+        assert!(rules.is_ignored(&root.join("web/node_modules/pkg/index.js"), false));
+        assert!(rules.is_ignored(&root.join("photos/.DS_Store"), false));
+        assert!(rules.is_ignored(&root.join("notes.txt.swp"), false));
+        assert!(!rules.is_ignored(&root.join("notes.txt"), false));
+        assert!(!rules.is_ignored(root, true));
+    }
+
+    #[test]
+    fn test_ignore_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::write(root.join(IGNORE_FILE_NAME), "*.log\nbuild/\n!keep.log\n").unwrap();
+        let rules = IgnoreRules::for_folder(root);
+
+        assert!(rules.is_ignored(&root.join("debug.log"), false));
+        assert!(rules.is_ignored(&root.join("build/out.bin"), false));
+        assert!(!rules.is_ignored(&root.join("keep.log"), false));
+        assert!(!rules.is_ignored(&root.join("src/main.rs"), false));
+    }
+
+    #[test]
+    fn test_paths_outside_folder() {
+        let temp_dir = TempDir::new().unwrap();
+        let rules = IgnoreRules::for_folder(&temp_dir.path().join("folder"));
+
+        assert!(!rules.is_ignored(&temp_dir.path().join(".git"), true));
+    }
+}
@@ -0,0 +1,236 @@
+//! Importing addresses and datamaps that others shared.
+//!
+//! A reference is either the hex of a public data address, or the hex of a private datamap. It is
+//! classified as a file or an archive, previewed, and then stored under a chosen name in local
+//! storage or the vault, the same way the app's own uploads are.
+
+use crate::ant::client::{ClientError, SharedClient};
+use crate::ant::file_metadata;
+use crate::ant::files::{self, FileAccess, FileMetadata, VaultError};
+use crate::ant::local_storage::{self, LocalStorageError};
+use autonomi::chunk::DataMapChunk;
+use autonomi::client::analyze::{Analysis, AnalysisError};
+use autonomi::client::vault::VaultSecretKey;
+use autonomi::data::DataAddress;
+use autonomi::files::Metadata;
+use serde::{Deserialize, Serialize};
+use tauri::State;
+use thiserror::Error as ThisError;
+use tracing::info;
+
+/// Length of the hex of a public data address.
+const ADDRESS_HEX_LEN: usize = 64;
+
+#[derive(ThisError, Debug)]
+pub enum ImportError {
+    #[error("Not a valid address or datamap: {0}")]
+    InvalidReference(String),
+    #[error("Could not connect to the network: {0:?}")]
+    Connect(#[from] ClientError),
+    #[error("Could not analyze address: {0:?}")]
+    Analysis(#[from] AnalysisError),
+    #[error("Only files and archives can be imported")]
+    UnsupportedType,
+    #[error("Could not get data: {0:?}")]
+    Get(#[from] autonomi::client::GetError),
+    #[error("Could not store reference: {0}")]
+    LocalStorage(#[from] LocalStorageError),
+    #[error("Could not add reference to the vault: {0}")]
+    Vault(#[from] Box<VaultError>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReferenceKind {
+    File,
+    Archive,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReferencePreview {
+    pub file_access: FileAccess,
+    pub kind: ReferenceKind,
+    /// Size of a file, as far as it can be told without downloading it.
+    pub metadata: Option<Metadata>,
+    /// Contents of an archive.
+    pub files: Vec<FileMetadata>,
+}
+
+/// Parse the hex of a public address or a private datamap.
+pub fn parse_reference(reference: &str) -> Result<FileAccess, ImportError> {
+    let hex = reference.trim();
+    let hex = hex.strip_prefix("0x").unwrap_or(hex);
+    let invalid = || ImportError::InvalidReference(reference.trim().to_string());
+
+    if hex.is_empty() || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(invalid());
+    }
+
+    if hex.len() == ADDRESS_HEX_LEN {
+        DataAddress::from_hex(hex)
+            .map(FileAccess::Public)
+            .map_err(|_| invalid())
+    } else {
+        DataMapChunk::from_hex(hex)
+            .map(FileAccess::Private)
+            .map_err(|_| invalid())
+    }
+}
+
+async fn classify(
+    client: &autonomi::Client,
+    file_access: &FileAccess,
+) -> Result<ReferenceKind, ImportError> {
+    let (hex, is_private) = match file_access {
+        FileAccess::Public(addr) => (addr.to_hex(), false),
+        FileAccess::Private(data_map) => (data_map.to_hex(), true),
+    };
+
+    match client.analyze_address(&hex, is_private).await? {
+        Analysis::RawDataMap { .. } | Analysis::DataMap { .. } => Ok(ReferenceKind::File),
+        Analysis::PublicArchive { .. } | Analysis::PrivateArchive { .. } => {
+            Ok(ReferenceKind::Archive)
+        }
+        _ => Err(ImportError::UnsupportedType),
+    }
+}
+
+/// Validate and classify a reference, and list what it contains.
+pub async fn preview_reference(
+    reference: &str,
+    shared_client: &SharedClient,
+) -> Result<ReferencePreview, ImportError> {
+    preview(parse_reference(reference)?, shared_client).await
+}
+
+/// Classify a file or archive and list what it contains.
+pub async fn preview(
+    file_access: FileAccess,
+    shared_client: &SharedClient,
+) -> Result<ReferencePreview, ImportError> {
+    let client = shared_client.get_client().await?;
+    let kind = classify(&client, &file_access).await?;
+
+    let (metadata, files) = match (kind, &file_access) {
+        (ReferenceKind::File, _) => {
+            let store = files::get_file_metadata_store().ok();
+            let metadata = file_metadata::resolve_file_metadata(
+                &client,
+                &Default::default(),
+                store,
+                &file_access,
+            )
+            .await;
+            (Some(metadata), vec![])
+        }
+        (ReferenceKind::Archive, FileAccess::Private(data_map)) => {
+            let archive = client.archive_get(data_map).await?;
+            (None, files::private_archive_files("", &archive, true))
+        }
+        (ReferenceKind::Archive, FileAccess::Public(addr)) => {
+            let archive = client.archive_get_public(addr).await?;
+            (None, files::public_archive_files("", &archive, true))
+        }
+    };
+
+    Ok(ReferencePreview {
+        file_access,
+        kind,
+        metadata,
+        files,
+    })
+}
+
+fn store_locally(preview: &ReferencePreview, name: &str) -> Result<(), LocalStorageError> {
+    match (preview.kind, &preview.file_access) {
+        (ReferenceKind::File, FileAccess::Private(data_map)) => {
+            local_storage::write_local_private_file(data_map.to_hex(), data_map.address(), name)
+        }
+        (ReferenceKind::File, FileAccess::Public(addr)) => {
+            local_storage::write_local_public_file(hex::encode(addr.xorname().0), name)
+        }
+        (ReferenceKind::Archive, FileAccess::Private(data_map)) => {
+            local_storage::write_local_private_file_archive(
+                data_map.to_hex(),
+                data_map.address(),
+                name,
+            )
+        }
+        (ReferenceKind::Archive, FileAccess::Public(addr)) => {
+            local_storage::write_local_public_file_archive(hex::encode(addr.xorname().0), name)
+        }
+    }
+}
+
+/// Preview a reference and store it under `name`, in the vault if a key is given, otherwise in
+/// local storage.
+pub async fn import_reference(
+    reference: &str,
+    name: &str,
+    vault_secret_key: Option<&VaultSecretKey>,
+    shared_client: State<'_, SharedClient>,
+) -> Result<ReferencePreview, ImportError> {
+    import(
+        parse_reference(reference)?,
+        name,
+        vault_secret_key,
+        shared_client,
+    )
+    .await
+}
+
+/// Preview a file or archive and store it under `name`, see [`import_reference`].
+pub async fn import(
+    file_access: FileAccess,
+    name: &str,
+    vault_secret_key: Option<&VaultSecretKey>,
+    shared_client: State<'_, SharedClient>,
+) -> Result<ReferencePreview, ImportError> {
+    let preview = preview(file_access, &shared_client).await?;
+    let file_access = preview.file_access.clone();
+
+    match (vault_secret_key, preview.kind) {
+        (Some(secret_key), ReferenceKind::Archive) => {
+            files::add_local_archive_to_vault(secret_key, file_access, name, shared_client)
+                .await
+                .map_err(Box::new)?
+        }
+        (Some(secret_key), ReferenceKind::File) => {
+            files::add_local_file_to_vault(secret_key, file_access, name, shared_client)
+                .await
+                .map_err(Box::new)?
+        }
+        (None, _) => store_locally(&preview, name)?,
+    }
+
+    info!("Imported {:?} as {name}", preview.kind);
+    Ok(preview)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use autonomi::XorName;
+
+    #[test]
+    fn test_parse_reference() {
+        let address = DataAddress::new(XorName::from_content(b"shared"));
+        let hex = address.to_hex();
+
+        assert!(matches!(
+            parse_reference(&format!("  {hex}\n")),
+            Ok(FileAccess::Public(parsed)) if parsed == address
+        ));
+        assert!(matches!(
+            parse_reference(&format!("0x{hex}")),
+            Ok(FileAccess::Public(_))
+        ));
+        assert!(matches!(
+            parse_reference(""),
+            Err(ImportError::InvalidReference(_))
+        ));
+        assert!(matches!(
+            parse_reference("not a reference"),
+            Err(ImportError::InvalidReference(_))
+        ));
+    }
+}
@@ -0,0 +1,461 @@
+//! Index of the local storage directories, kept in an embedded database.
+//!
+//! [`local_storage`](crate::ant::local_storage) keeps one file per entry in the ant-cli
+//! directories, and those stay the source of truth so both tools see the same uploads. The index
+//! mirrors them: a listing only reads the directory entries and parses just the files that are
+//! new or changed since the last listing. Files that can't be parsed are reported instead of
+//! being dropped, and entries whose files were removed are dropped from the index.
+//!
+//! Details ant-cli doesn't keep (size, source path and tags) are stored by address, so they
+//! survive an entry being rewritten.
+
+use crate::ant::app_data;
+use crate::ant::files::FileAccess;
+use crate::ant::local_storage::{self, EntryKind};
+use redb::{Database, ReadableTable, TableDefinition};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::UNIX_EPOCH;
+use thiserror::Error as ThisError;
+use tracing::warn;
+
+const INDEX_FILE: &str = "local_index.redb";
+
+/// Entry file (`<dir>/<file name>`) -> [`StoredEntry`].
+const ENTRIES: TableDefinition<&str, &[u8]> = TableDefinition::new("entries");
+/// Entry file -> [`StoredCorrupt`].
+const CORRUPT: TableDefinition<&str, &[u8]> = TableDefinition::new("corrupt");
+/// [`FileAccess::local_key`] -> [`EntryDetails`].
+const DETAILS: TableDefinition<&str, &[u8]> = TableDefinition::new("details");
+
+static LOCAL_INDEX: OnceLock<Result<LocalIndex, String>> = OnceLock::new();
+
+#[derive(ThisError, Debug)]
+pub enum LocalIndexError {
+    #[error("Could not read local storage: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Could not serialize index entry: {0}")]
+    Serialization(#[from] serde_json::Error),
+    #[error("Could not open local index: {0}")]
+    Database(#[from] redb::DatabaseError),
+    #[error("Local index transaction failed: {0}")]
+    Transaction(#[from] Box<redb::TransactionError>),
+    #[error("Could not open local index table: {0}")]
+    Table(#[from] redb::TableError),
+    #[error("Local index storage error: {0}")]
+    Storage(#[from] redb::StorageError),
+    #[error("Could not commit local index: {0}")]
+    Commit(#[from] redb::CommitError),
+}
+
+/// Details of an upload that the ant-cli entry files don't hold.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EntryDetails {
+    /// Size in bytes, the total for archives.
+    pub size: Option<u64>,
+    /// The file or folder that was uploaded.
+    pub source_path: Option<PathBuf>,
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct IndexedEntry {
+    pub kind: EntryKind,
+    pub name: String,
+    pub file_access: FileAccess,
+    /// When the entry was first indexed, seconds since the Unix epoch.
+    pub uploaded: u64,
+    pub details: EntryDetails,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CorruptEntry {
+    pub path: PathBuf,
+    pub error: String,
+}
+
+#[derive(Debug, Default)]
+pub struct Listing {
+    pub entries: Vec<IndexedEntry>,
+    pub corrupt: Vec<CorruptEntry>,
+}
+
+/// Identifies the version of an entry file, without reading it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct FileStamp {
+    modified_ms: u64,
+    len: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredEntry {
+    stamp: FileStamp,
+    name: String,
+    file_access: FileAccess,
+    uploaded: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredCorrupt {
+    stamp: FileStamp,
+    error: String,
+}
+
+/// A change a sync makes to the index, applied once the directories were compared.
+enum IndexChange {
+    /// Key and [`StoredEntry`] of an entry that was added or changed.
+    Entry(String, Vec<u8>),
+    /// Key and [`StoredCorrupt`] of an entry that can't be parsed.
+    Corrupt(String, Vec<u8>),
+    /// Key of an entry whose file was removed.
+    Removed(String),
+}
+
+pub struct LocalIndex {
+    db: Database,
+}
+
+/// The shared local index, `None` if it can't be opened, e.g. while another instance holds it.
+pub fn get_local_index() -> Option<&'static LocalIndex> {
+    LOCAL_INDEX
+        .get_or_init(|| {
+            app_data::data_dir()
+                .ok_or_else(|| "Could not get app data directory".to_string())
+                .and_then(|dir| {
+                    fs::create_dir_all(&dir)
+                        .map_err(LocalIndexError::from)
+                        .and_then(|_| LocalIndex::open(&dir.join(INDEX_FILE)))
+                        .map_err(|e| format!("Failed to open local index: {}", e))
+                })
+        })
+        .as_ref()
+        .inspect_err(|err| warn!("{err}"))
+        .ok()
+}
+
+/// Files in an entry directory with their stamps, nothing if the directory doesn't exist.
+fn list_dir(dir: &Path) -> Result<Vec<(String, PathBuf, FileStamp)>, std::io::Error> {
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut files = vec![];
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if !metadata.is_file() {
+            continue;
+        }
+
+        let modified_ms = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|duration| duration.as_millis() as u64)
+            .unwrap_or(0);
+        files.push((
+            entry.file_name().to_string_lossy().into_owned(),
+            entry.path(),
+            FileStamp {
+                modified_ms,
+                len: metadata.len(),
+            },
+        ));
+    }
+
+    Ok(files)
+}
+
+fn read_entry(
+    kind: EntryKind,
+    file_name: &str,
+    path: &Path,
+) -> Result<(String, FileAccess), String> {
+    let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    local_storage::parse_entry(kind, file_name, &content)
+}
+
+/// List the entries by reading every entry file, for when the index can't be used.
+pub fn scan(user_data_dir: &Path) -> Result<Listing, std::io::Error> {
+    let mut listing = Listing::default();
+
+    for kind in EntryKind::ALL {
+        for (file_name, path, stamp) in list_dir(&user_data_dir.join(kind.dir()))? {
+            match read_entry(kind, &file_name, &path) {
+                Ok((name, file_access)) => listing.entries.push(IndexedEntry {
+                    kind,
+                    name,
+                    file_access,
+                    uploaded: stamp.modified_ms / 1000,
+                    details: EntryDetails::default(),
+                }),
+                Err(error) => listing.corrupt.push(CorruptEntry { path, error }),
+            }
+        }
+    }
+
+    Ok(listing)
+}
+
+/// All values of a table, values that no longer deserialize are left out.
+fn read_all<T: DeserializeOwned>(
+    table: &impl ReadableTable<&'static str, &'static [u8]>,
+) -> Result<HashMap<String, T>, LocalIndexError> {
+    let mut values = HashMap::new();
+    for item in table.iter()? {
+        let (key, value) = item?;
+        if let Ok(value) = serde_json::from_slice(value.value()) {
+            values.insert(key.value().to_string(), value);
+        }
+    }
+    Ok(values)
+}
+
+fn read_details(
+    table: &impl ReadableTable<&'static str, &'static [u8]>,
+    file_access: &FileAccess,
+) -> Result<EntryDetails, LocalIndexError> {
+    Ok(table
+        .get(file_access.local_key().as_str())?
+        .and_then(|value| serde_json::from_slice(value.value()).ok())
+        .unwrap_or_default())
+}
+
+impl LocalIndex {
+    pub fn open(path: &Path) -> Result<Self, LocalIndexError> {
+        let db = Database::create(path)?;
+
+        // Create the tables, so syncs can start with a read transaction
+        let txn = db.begin_write().map_err(Box::new)?;
+        txn.open_table(ENTRIES)?;
+        txn.open_table(CORRUPT)?;
+        txn.open_table(DETAILS)?;
+        txn.commit()?;
+
+        Ok(Self { db })
+    }
+
+    /// Bring the index up to date with the entry directories and list it.
+    ///
+    /// Only writes to the index when an entry file was added, changed or removed.
+    pub fn sync(&self, user_data_dir: &Path) -> Result<Listing, LocalIndexError> {
+        let mut changes = vec![];
+        let listing = {
+            let txn = self.db.begin_read().map_err(Box::new)?;
+            let entries = txn.open_table(ENTRIES)?;
+            let corrupt = txn.open_table(CORRUPT)?;
+            let details = txn.open_table(DETAILS)?;
+
+            let mut known_entries: HashMap<String, StoredEntry> = read_all(&entries)?;
+            let mut known_corrupt: HashMap<String, StoredCorrupt> = read_all(&corrupt)?;
+            let mut listing = Listing::default();
+
+            for kind in EntryKind::ALL {
+                for (file_name, path, stamp) in list_dir(&user_data_dir.join(kind.dir()))? {
+                    let key = format!("{}/{}", kind.dir(), file_name);
+                    let known_entry = known_entries.remove(&key);
+                    let known_error = known_corrupt.remove(&key);
+
+                    let entry = match (known_entry, known_error) {
+                        (Some(entry), _) if entry.stamp == stamp => Ok(entry),
+                        (_, Some(known)) if known.stamp == stamp => Err(known.error),
+                        (previous, _) => match read_entry(kind, &file_name, &path) {
+                            Ok((name, file_access)) => {
+                                let entry = StoredEntry {
+                                    stamp,
+                                    name,
+                                    file_access,
+                                    uploaded: previous
+                                        .map(|previous| previous.uploaded)
+                                        .unwrap_or(stamp.modified_ms / 1000),
+                                };
+                                changes.push(IndexChange::Entry(key, serde_json::to_vec(&entry)?));
+                                Ok(entry)
+                            }
+                            Err(error) => {
+                                warn!("Corrupt local storage entry {path:?}: {error}");
+                                let stored = StoredCorrupt {
+                                    stamp,
+                                    error: error.clone(),
+                                };
+                                changes
+                                    .push(IndexChange::Corrupt(key, serde_json::to_vec(&stored)?));
+                                Err(error)
+                            }
+                        },
+                    };
+
+                    match entry {
+                        Ok(entry) => listing.entries.push(IndexedEntry {
+                            kind,
+                            details: read_details(&details, &entry.file_access)?,
+                            name: entry.name,
+                            file_access: entry.file_access,
+                            uploaded: entry.uploaded,
+                        }),
+                        Err(error) => listing.corrupt.push(CorruptEntry { path, error }),
+                    }
+                }
+            }
+
+            // What is left was removed from the directories, e.g. by ant-cli
+            changes.extend(
+                known_entries
+                    .into_keys()
+                    .chain(known_corrupt.into_keys())
+                    .map(IndexChange::Removed),
+            );
+
+            listing
+        };
+
+        if !changes.is_empty() {
+            self.apply(changes)?;
+        }
+
+        Ok(listing)
+    }
+
+    fn apply(&self, changes: Vec<IndexChange>) -> Result<(), LocalIndexError> {
+        let txn = self.db.begin_write().map_err(Box::new)?;
+        {
+            let mut entries = txn.open_table(ENTRIES)?;
+            let mut corrupt = txn.open_table(CORRUPT)?;
+
+            for change in changes {
+                match change {
+                    IndexChange::Entry(key, value) => {
+                        entries.insert(key.as_str(), value.as_slice())?;
+                        corrupt.remove(key.as_str())?;
+                    }
+                    IndexChange::Corrupt(key, value) => {
+                        corrupt.insert(key.as_str(), value.as_slice())?;
+                        entries.remove(key.as_str())?;
+                    }
+                    IndexChange::Removed(key) => {
+                        entries.remove(key.as_str())?;
+                        corrupt.remove(key.as_str())?;
+                    }
+                }
+            }
+        }
+        txn.commit()?;
+
+        Ok(())
+    }
+
+    fn update_details(
+        &self,
+        file_access: &FileAccess,
+        f: impl FnOnce(&mut EntryDetails),
+    ) -> Result<EntryDetails, LocalIndexError> {
+        let txn = self.db.begin_write().map_err(Box::new)?;
+        let details = {
+            let mut table = txn.open_table(DETAILS)?;
+            let mut details = read_details(&table, file_access)?;
+            f(&mut details);
+            table.insert(
+                file_access.local_key().as_str(),
+                serde_json::to_vec(&details)?.as_slice(),
+            )?;
+            details
+        };
+        txn.commit()?;
+
+        Ok(details)
+    }
+
+    /// Replace the tags of an entry, blank and duplicate tags are dropped.
+    pub fn set_tags(
+        &self,
+        file_access: &FileAccess,
+        tags: Vec<String>,
+    ) -> Result<EntryDetails, LocalIndexError> {
+        let tags: BTreeSet<String> = tags
+            .into_iter()
+            .map(|tag| tag.trim().to_string())
+            .filter(|tag| !tag.is_empty())
+            .collect();
+        self.update_details(file_access, |details| {
+            details.tags = tags.into_iter().collect()
+        })
+    }
+}
+
+/// Remember the size and source of an upload, if the index is available.
+pub fn record_details(file_access: &FileAccess, source_path: Option<&Path>, size: u64) {
+    let Some(index) = get_local_index() else {
+        return;
+    };
+
+    let result = index.update_details(file_access, |details| {
+        details.size = Some(size);
+        if let Some(source_path) = source_path {
+            details.source_path = Some(source_path.to_path_buf());
+        }
+    });
+    if let Err(err) = result {
+        warn!("Failed to record upload details in the local index: {err}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use autonomi::data::DataAddress;
+    use autonomi::XorName;
+
+    fn write_public_archive(user_data_dir: &Path, content: &[u8], name: &str) -> DataAddress {
+        let address = DataAddress::new(XorName::from_content(content));
+        let dir = user_data_dir.join(EntryKind::PublicArchive.dir());
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join(address.to_hex()), name).unwrap();
+        address
+    }
+
+    #[test]
+    fn test_sync_mirrors_directories() {
+        let tmp = tempfile::tempdir().unwrap();
+        let user_data_dir = tmp.path().join("user_data");
+        let index = LocalIndex::open(&tmp.path().join(INDEX_FILE)).unwrap();
+
+        let photos = write_public_archive(&user_data_dir, b"photos", "Photos");
+        let notes = write_public_archive(&user_data_dir, b"notes", "Notes");
+        let private_dir = user_data_dir.join(EntryKind::PrivateFile.dir());
+        fs::create_dir_all(&private_dir).unwrap();
+        fs::write(private_dir.join("broken"), "not json").unwrap();
+
+        let listing = index.sync(&user_data_dir).unwrap();
+        assert_eq!(listing.entries.len(), 2);
+        assert_eq!(listing.corrupt.len(), 1);
+        assert_eq!(listing.corrupt[0].path, private_dir.join("broken"));
+
+        index
+            .set_tags(
+                &FileAccess::Public(photos),
+                vec![
+                    " holiday ".into(),
+                    "".into(),
+                    "holiday".into(),
+                    "2024".into(),
+                ],
+            )
+            .unwrap();
+        fs::remove_file(
+            user_data_dir
+                .join(EntryKind::PublicArchive.dir())
+                .join(notes.to_hex()),
+        )
+        .unwrap();
+
+        let listing = index.sync(&user_data_dir).unwrap();
+        assert_eq!(listing.entries.len(), 1);
+        assert_eq!(listing.entries[0].name, "Photos");
+        assert_eq!(listing.entries[0].details.tags, vec!["2024", "holiday"]);
+        assert_eq!(listing.corrupt.len(), 1);
+    }
+}
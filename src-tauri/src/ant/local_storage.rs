@@ -1,16 +1,20 @@
 //! Local storage for file archives and addresses, similar to ant-cli
+//!
+//! Entries are listed through the [`local_index`], which mirrors these directories.
 
 use crate::ant::client::SharedClient;
 use crate::ant::files::FileAccess;
+use crate::ant::local_index::{self, CorruptEntry, EntryDetails};
 use autonomi::chunk::DataMapChunk;
 use autonomi::client::files::archive_public::ArchiveAddress;
 use autonomi::data::DataAddress;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::cmp::Reverse;
 use std::fs;
 use std::path::PathBuf;
 use tauri::{AppHandle, Emitter, State};
 use thiserror::Error as ThisError;
+use tracing::{error, warn};
 
 #[derive(ThisError, Debug)]
 pub enum LocalStorageError {
@@ -44,6 +48,65 @@ struct PublicFile {
     data_address: String,
 }
 
+/// The kinds of entries, each kept in its own directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EntryKind {
+    PublicArchive,
+    PrivateArchive,
+    PublicFile,
+    PrivateFile,
+}
+
+impl EntryKind {
+    pub const ALL: [EntryKind; 4] = [
+        EntryKind::PublicArchive,
+        EntryKind::PrivateArchive,
+        EntryKind::PublicFile,
+        EntryKind::PrivateFile,
+    ];
+
+    pub fn dir(self) -> &'static str {
+        match self {
+            EntryKind::PublicArchive => "file_archives",
+            EntryKind::PrivateArchive => "private_file_archives",
+            EntryKind::PublicFile => "public_files",
+            EntryKind::PrivateFile => "private_files",
+        }
+    }
+}
+
+/// Parse an entry file the way ant-cli writes it, returns the name and access data.
+pub(crate) fn parse_entry(
+    kind: EntryKind,
+    file_name: &str,
+    content: &str,
+) -> Result<(String, FileAccess), String> {
+    match kind {
+        // The file is named by the address and only contains the name
+        EntryKind::PublicArchive => {
+            let address = DataAddress::from_hex(file_name).map_err(|e| e.to_string())?;
+            Ok((content.to_string(), FileAccess::Public(address)))
+        }
+        EntryKind::PrivateArchive => {
+            let archive: PrivateFileArchive =
+                serde_json::from_str(content).map_err(|e| e.to_string())?;
+            let datamap =
+                DataMapChunk::from_hex(&archive.secret_access).map_err(|e| e.to_string())?;
+            Ok((archive.name, FileAccess::Private(datamap)))
+        }
+        EntryKind::PublicFile => {
+            let file: PublicFile = serde_json::from_str(content).map_err(|e| e.to_string())?;
+            let address = DataAddress::from_hex(&file.data_address).map_err(|e| e.to_string())?;
+            Ok((file.name, FileAccess::Public(address)))
+        }
+        EntryKind::PrivateFile => {
+            let file: PrivateFile = serde_json::from_str(content).map_err(|e| e.to_string())?;
+            let datamap = DataMapChunk::from_hex(&file.secret_access).map_err(|e| e.to_string())?;
+            Ok((file.name, FileAccess::Private(datamap)))
+        }
+    }
+}
+
 /// Get the local user data directory path - same as ant-cli
 fn get_user_data_dir() -> Result<PathBuf, LocalStorageError> {
     let mut home_dirs = dirs_next::data_dir().ok_or(LocalStorageError::NoDataDir)?;
@@ -126,139 +189,34 @@ pub fn write_local_public_file(data_address: String, name: &str) -> Result<(), L
     Ok(())
 }
 
-/// Get all local public file archives
-pub fn get_local_public_file_archives() -> Result<HashMap<ArchiveAddress, String>, LocalStorageError>
-{
-    let user_data_path = get_user_data_dir()?;
-    let file_archives_path = user_data_path.join("file_archives");
-    fs::create_dir_all(&file_archives_path).map_err(LocalStorageError::WriteError)?;
-
-    let mut file_archives = HashMap::new();
-
-    for entry in walkdir::WalkDir::new(file_archives_path)
-        .min_depth(1)
-        .max_depth(1)
-    {
-        let entry = entry.map_err(|e| {
-            LocalStorageError::ReadError(std::io::Error::new(std::io::ErrorKind::Other, e))
-        })?;
-        let file_name = entry.file_name().to_string_lossy();
-        if let Ok(file_archive_address) = DataAddress::from_hex(&file_name) {
-            if let Ok(file_archive_name) = fs::read_to_string(entry.path()) {
-                file_archives.insert(file_archive_address, file_archive_name);
-            }
-        }
-    }
-
-    Ok(file_archives)
-}
-
-/// Get all local private file archives
-pub fn get_local_private_file_archives() -> Result<HashMap<DataMapChunk, String>, LocalStorageError>
-{
-    let user_data_path = get_user_data_dir()?;
-    let private_file_archives_path = user_data_path.join("private_file_archives");
-    fs::create_dir_all(&private_file_archives_path).map_err(LocalStorageError::WriteError)?;
-
-    let mut private_file_archives = HashMap::new();
-
-    for entry in walkdir::WalkDir::new(private_file_archives_path)
-        .min_depth(1)
-        .max_depth(1)
-    {
-        let entry = entry.map_err(|e| {
-            LocalStorageError::ReadError(std::io::Error::new(std::io::ErrorKind::Other, e))
-        })?;
-        if let Ok(file_content) = fs::read_to_string(entry.path()) {
-            if let Ok(private_file_archive) =
-                serde_json::from_str::<PrivateFileArchive>(&file_content)
-            {
-                if let Ok(datamap) = DataMapChunk::from_hex(&private_file_archive.secret_access) {
-                    private_file_archives.insert(datamap, private_file_archive.name);
-                }
-            }
-        }
-    }
-
-    Ok(private_file_archives)
-}
-
-/// Get all local public files
-pub fn get_local_public_files() -> Result<HashMap<DataAddress, String>, LocalStorageError> {
-    let user_data_path = get_user_data_dir()?;
-    let public_files_path = user_data_path.join("public_files");
-    let mut files = HashMap::new();
-
-    if !public_files_path.exists() {
-        return Ok(files);
-    }
-
-    for entry in walkdir::WalkDir::new(public_files_path)
-        .min_depth(1)
-        .max_depth(1)
-    {
-        let entry = entry.map_err(|e| {
-            LocalStorageError::ReadError(std::io::Error::new(std::io::ErrorKind::Other, e))
-        })?;
-        if let Ok(content) = fs::read_to_string(entry.path()) {
-            if let Ok(public_file) = serde_json::from_str::<PublicFile>(&content) {
-                if let Ok(data_address) = DataAddress::from_hex(&public_file.data_address) {
-                    files.insert(data_address, public_file.name);
-                }
-            }
-        }
-    }
-
-    Ok(files)
-}
-
-/// Get all local private files
-pub fn get_local_private_files() -> Result<HashMap<DataMapChunk, String>, LocalStorageError> {
-    let user_data_path = get_user_data_dir()?;
-    let private_files_path = user_data_path.join("private_files");
-    let mut files = HashMap::new();
-
-    if !private_files_path.exists() {
-        return Ok(files);
-    }
-
-    for entry in walkdir::WalkDir::new(private_files_path)
-        .min_depth(1)
-        .max_depth(1)
-    {
-        let entry = entry.map_err(|e| {
-            LocalStorageError::ReadError(std::io::Error::new(std::io::ErrorKind::Other, e))
-        })?;
-        if let Ok(content) = fs::read_to_string(entry.path()) {
-            if let Ok(private_file) = serde_json::from_str::<PrivateFile>(&content) {
-                if let Ok(datamap) = DataMapChunk::from_hex(&private_file.secret_access) {
-                    files.insert(datamap, private_file.name);
-                }
-            }
-        }
-    }
-
-    Ok(files)
-}
-
 #[derive(Serialize, Deserialize, Debug)]
 pub struct LocalFileData {
     pub public_file_archives: Vec<LocalArchive>,
     pub private_file_archives: Vec<LocalArchive>,
     pub public_files: Vec<LocalFile>,
     pub private_files: Vec<LocalFile>,
+    /// Entry files that could not be parsed.
+    pub corrupt_entries: Vec<CorruptEntry>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct LocalArchive {
     pub name: String,
     pub file_access: FileAccess,
+    /// When the entry was first indexed, seconds since the Unix epoch.
+    pub uploaded: u64,
+    #[serde(flatten)]
+    pub details: EntryDetails,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct LocalFile {
     pub name: String,
     pub file_access: FileAccess,
+    /// When the entry was first indexed, seconds since the Unix epoch.
+    pub uploaded: u64,
+    #[serde(flatten)]
+    pub details: EntryDetails,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -357,46 +315,61 @@ pub fn get_local_private_file_access(local_addr: &str) -> Result<DataMapChunk, L
     Ok(private_file_access)
 }
 
-/// Get all local file data in a structured format
+/// Get all local file data in a structured format, newest first
 pub fn get_all_local_files() -> Result<LocalFileData, LocalStorageError> {
-    let mut public_file_archives = Vec::new();
-    for (addr, name) in get_local_public_file_archives()? {
-        public_file_archives.push(LocalArchive {
-            name,
-            file_access: FileAccess::Public(addr),
-        });
-    }
-
-    let mut private_file_archives = Vec::new();
-    for (datamap, name) in get_local_private_file_archives()? {
-        private_file_archives.push(LocalArchive {
-            name,
-            file_access: FileAccess::Private(datamap),
-        });
-    }
+    let user_data_path = get_user_data_dir()?;
 
-    let mut public_files = Vec::new();
-    for (addr, name) in get_local_public_files()? {
-        public_files.push(LocalFile {
-            name,
-            file_access: FileAccess::Public(addr),
-        });
-    }
+    let mut listing = match local_index::get_local_index().map(|index| index.sync(&user_data_path))
+    {
+        Some(Ok(listing)) => listing,
+        Some(Err(err)) => {
+            warn!("Failed to update the local index, reading the directories instead: {err}");
+            local_index::scan(&user_data_path).map_err(LocalStorageError::ReadError)?
+        }
+        None => local_index::scan(&user_data_path).map_err(LocalStorageError::ReadError)?,
+    };
+    listing.entries.sort_by_key(|entry| Reverse(entry.uploaded));
+
+    let mut data = LocalFileData {
+        public_file_archives: Vec::new(),
+        private_file_archives: Vec::new(),
+        public_files: Vec::new(),
+        private_files: Vec::new(),
+        corrupt_entries: listing.corrupt,
+    };
 
-    let mut private_files = Vec::new();
-    for (datamap, name) in get_local_private_files()? {
-        private_files.push(LocalFile {
-            name,
-            file_access: FileAccess::Private(datamap),
-        });
+    for entry in listing.entries {
+        match entry.kind {
+            EntryKind::PublicArchive | EntryKind::PrivateArchive => {
+                let archive = LocalArchive {
+                    name: entry.name,
+                    file_access: entry.file_access,
+                    uploaded: entry.uploaded,
+                    details: entry.details,
+                };
+                if entry.kind == EntryKind::PublicArchive {
+                    data.public_file_archives.push(archive);
+                } else {
+                    data.private_file_archives.push(archive);
+                }
+            }
+            EntryKind::PublicFile | EntryKind::PrivateFile => {
+                let file = LocalFile {
+                    name: entry.name,
+                    file_access: entry.file_access,
+                    uploaded: entry.uploaded,
+                    details: entry.details,
+                };
+                if entry.kind == EntryKind::PublicFile {
+                    data.public_files.push(file);
+                } else {
+                    data.private_files.push(file);
+                }
+            }
+        }
     }
 
-    Ok(LocalFileData {
-        public_file_archives,
-        private_file_archives,
-        public_files,
-        private_files,
-    })
+    Ok(data)
 }
 
 /// Get local file structure with streaming updates similar to vault files
@@ -518,7 +491,7 @@ pub async fn get_local_structure_streaming(
                         let _ = app.emit("local-update", update);
                     }
                     Err(err) => {
-                        println!(">>> Failed to get private archive: {:?}", err);
+                        error!(">>> Failed to get private archive: {:?}", err);
                         let failed_archive = LocalFailedArchive {
                             name: archive_name.clone(),
                             file_access: FileAccess::Private(data_map_chunk),
@@ -1,12 +1,30 @@
 pub mod app_data;
+mod archive_cache;
+mod archive_metadata;
+pub mod archive_update;
 pub mod cached_payments;
+pub mod chunk_cache;
 pub mod client;
+pub mod download_queue;
 mod encryption;
+mod file_metadata;
 pub mod files;
+pub mod folder_scan;
+pub mod health;
+pub mod ignore_rules;
+pub mod import;
+pub mod local_index;
 pub mod local_storage;
+pub mod network;
+pub mod offline_queue;
 pub mod payments;
+pub mod preview;
+pub mod private_share;
 mod quote;
 pub mod receipt_utils;
+pub mod share_link;
 mod stream;
 mod upload;
 pub mod vault;
+pub mod vault_registry;
+pub mod watched_folders;
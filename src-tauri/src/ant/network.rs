@@ -0,0 +1,133 @@
+//! Network selection.
+//!
+//! The network is configured in [`AppData`](crate::ant::app_data::AppData) and resolved into a
+//! [`NetworkConfig`] that the [`SharedClient`](crate::ant::client::SharedClient) connects with.
+
+use autonomi::{BootstrapConfig, Client, ClientConfig, Multiaddr, Network as EvmNetwork};
+use serde::{Deserialize, Serialize};
+use thiserror::Error as ThisError;
+
+const MAINNET_ID: u8 = 1;
+const ALPHA_ID: u8 = 2;
+
+#[derive(ThisError, Debug)]
+pub enum NetworkConfigError {
+    #[error("The {0:?} network needs at least one bootstrap peer")]
+    NoPeers(NetworkKind),
+    #[error("Invalid EVM network: {0}")]
+    EvmNetwork(String),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NetworkKind {
+    #[default]
+    Mainnet,
+    Alpha,
+    Testnet,
+    Local,
+    Custom,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum EvmNetworkConfig {
+    ArbitrumOne,
+    ArbitrumSepoliaTest,
+    Custom {
+        rpc_url: String,
+        payment_token_address: String,
+        data_payments_address: String,
+    },
+}
+
+impl EvmNetworkConfig {
+    fn to_evm_network(&self) -> EvmNetwork {
+        match self {
+            Self::ArbitrumOne => EvmNetwork::ArbitrumOne,
+            Self::ArbitrumSepoliaTest => EvmNetwork::ArbitrumSepoliaTest,
+            Self::Custom {
+                rpc_url,
+                payment_token_address,
+                data_payments_address,
+            } => EvmNetwork::new_custom(rpc_url, payment_token_address, data_payments_address),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NetworkConfig {
+    pub kind: NetworkKind,
+    /// Bootstrap peers, on top of the network's own contacts if it has any.
+    pub peers: Vec<Multiaddr>,
+    /// Network id of testnets and custom networks, mainnet and alpha have a fixed id.
+    pub network_id: Option<u8>,
+    /// EVM network used for payments, defaults to the one belonging to the network.
+    pub evm_network: Option<EvmNetworkConfig>,
+}
+
+impl NetworkConfig {
+    pub fn validate(&self) -> Result<(), NetworkConfigError> {
+        match self.kind {
+            // These networks have no well-known contacts to bootstrap from
+            NetworkKind::Testnet | NetworkKind::Custom if self.peers.is_empty() => {
+                Err(NetworkConfigError::NoPeers(self.kind))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether the config only names a network, so the client's own presets can be used.
+    fn is_preset(&self) -> bool {
+        self.peers.is_empty() && self.network_id.is_none() && self.evm_network.is_none()
+    }
+
+    fn network_id(&self) -> Option<u8> {
+        match self.kind {
+            NetworkKind::Mainnet => Some(MAINNET_ID),
+            NetworkKind::Alpha => Some(ALPHA_ID),
+            NetworkKind::Testnet | NetworkKind::Local | NetworkKind::Custom => self.network_id,
+        }
+    }
+
+    fn evm_network(&self) -> Result<EvmNetwork, NetworkConfigError> {
+        if let Some(evm_network) = &self.evm_network {
+            return Ok(evm_network.to_evm_network());
+        }
+
+        match self.kind {
+            NetworkKind::Mainnet | NetworkKind::Custom => Ok(EvmNetwork::ArbitrumOne),
+            NetworkKind::Alpha | NetworkKind::Testnet => Ok(EvmNetwork::ArbitrumSepoliaTest),
+            // A local EVM testnet advertises itself through the environment
+            NetworkKind::Local => {
+                EvmNetwork::new(true).map_err(|err| NetworkConfigError::EvmNetwork(err.to_string()))
+            }
+        }
+    }
+
+    fn client_config(&self) -> Result<ClientConfig, NetworkConfigError> {
+        let bootstrap_config = BootstrapConfig::new(self.kind == NetworkKind::Local)
+            .with_initial_peers(self.peers.clone());
+
+        Ok(ClientConfig {
+            bootstrap_config,
+            evm_network: self.evm_network()?,
+            network_id: self.network_id(),
+            ..Default::default()
+        })
+    }
+}
+
+/// Connect a client to the configured network.
+pub async fn connect(config: &NetworkConfig) -> Result<Client, crate::ant::client::ClientError> {
+    config.validate()?;
+
+    let client = match config.kind {
+        NetworkKind::Mainnet if config.is_preset() => Client::init().await?,
+        NetworkKind::Alpha if config.is_preset() => Client::init_alpha().await?,
+        NetworkKind::Local if config.is_preset() => Client::init_local().await?,
+        _ => Client::init_with_config(config.client_config()?).await?,
+    };
+
+    Ok(client)
+}
@@ -0,0 +1,249 @@
+//! Persistent queue of uploads and vault edits made while the network is unreachable.
+//!
+//! Operations are kept in the app data directory so they survive a restart, and are run in order
+//! once the client connects again. Vault keys are never written to disk: operations refer to a
+//! vault identity and wait until it is unlocked.
+
+use crate::ant::app_data;
+use crate::ant::files::{File, FileAccess};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use thiserror::Error as ThisError;
+use tokio::sync::{Mutex, MutexGuard};
+use tracing::{error, info};
+
+const QUEUE_FILE: &str = "offline_queue.json";
+
+#[derive(ThisError, Debug)]
+pub enum OfflineQueueError {
+    #[error("Could not determine the app data directory")]
+    NoDataDir,
+    #[error("Could not store the offline queue: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Could not serialize the offline queue: {0}")]
+    Serialization(#[from] serde_json::Error),
+    #[error("Unknown queued operation: {0}")]
+    UnknownOperation(String),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum QueuedOperationKind {
+    /// Uploads start with a quote, payment continues through the regular upload flow.
+    Upload {
+        upload_id: String,
+        files: Vec<File>,
+        archive_name: Option<String>,
+        is_private: bool,
+        add_to_vault: bool,
+        use_cached_receipts: bool,
+        vault_id: Option<String>,
+    },
+    AddFileToVault {
+        vault_id: String,
+        file_access: FileAccess,
+        file_name: String,
+    },
+    AddArchiveToVault {
+        vault_id: String,
+        archive_access: FileAccess,
+        archive_name: String,
+    },
+    RemoveFromVault {
+        vault_id: String,
+        file_path: String,
+        archive_address: Option<String>,
+    },
+}
+
+impl QueuedOperationKind {
+    /// The vault identity that has to be unlocked to run the operation.
+    pub fn vault_id(&self) -> Option<&str> {
+        match self {
+            Self::Upload { vault_id, .. } => vault_id.as_deref(),
+            Self::AddFileToVault { vault_id, .. }
+            | Self::AddArchiveToVault { vault_id, .. }
+            | Self::RemoveFromVault { vault_id, .. } => Some(vault_id),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum QueuedOperationStatus {
+    Pending,
+    Running,
+    Completed,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueuedOperation {
+    pub id: String,
+    pub created: u64,
+    #[serde(flatten)]
+    pub kind: QueuedOperationKind,
+    pub status: QueuedOperationStatus,
+    /// Why the last attempt failed.
+    pub error: Option<String>,
+}
+
+pub struct OfflineQueue {
+    path: Option<PathBuf>,
+    operations: Mutex<Vec<QueuedOperation>>,
+    processing: Mutex<()>,
+}
+
+impl Default for OfflineQueue {
+    fn default() -> Self {
+        Self::load()
+    }
+}
+
+impl OfflineQueue {
+    /// Load the queue from the app data directory.
+    ///
+    /// Completed operations are dropped, and operations that were running when the app exited
+    /// are run again.
+    pub fn load() -> Self {
+        let path = app_data::data_dir().map(|dir| dir.join(QUEUE_FILE));
+
+        let mut operations: Vec<QueuedOperation> = path
+            .as_deref()
+            .and_then(app_data::load_json)
+            .unwrap_or_default();
+
+        operations.retain(|operation| operation.status != QueuedOperationStatus::Completed);
+        for operation in &mut operations {
+            if operation.status == QueuedOperationStatus::Running {
+                operation.status = QueuedOperationStatus::Pending;
+            }
+        }
+
+        if !operations.is_empty() {
+            info!("Loaded {} queued operations", operations.len());
+        }
+
+        Self {
+            path,
+            operations: Mutex::new(operations),
+            processing: Mutex::new(()),
+        }
+    }
+
+    fn save(&self, operations: &[QueuedOperation]) -> Result<(), OfflineQueueError> {
+        let path = self.path.as_ref().ok_or(OfflineQueueError::NoDataDir)?;
+        app_data::save_json(path, operations)?;
+        Ok(())
+    }
+
+    pub async fn push(
+        &self,
+        kind: QueuedOperationKind,
+    ) -> Result<QueuedOperation, OfflineQueueError> {
+        let operation = QueuedOperation {
+            id: hex::encode(rand::random::<[u8; 8]>()),
+            created: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            kind,
+            status: QueuedOperationStatus::Pending,
+            error: None,
+        };
+
+        let mut operations = self.operations.lock().await;
+        operations.push(operation.clone());
+        self.save(&operations)?;
+
+        info!(
+            "Queued operation {} until the network is reachable",
+            operation.id
+        );
+        Ok(operation)
+    }
+
+    pub async fn list(&self) -> Vec<QueuedOperation> {
+        self.operations.lock().await.clone()
+    }
+
+    /// Pending operations, in the order they were queued.
+    pub async fn pending(&self) -> Vec<QueuedOperation> {
+        self.operations
+            .lock()
+            .await
+            .iter()
+            .filter(|operation| operation.status == QueuedOperationStatus::Pending)
+            .cloned()
+            .collect()
+    }
+
+    pub async fn set_status(
+        &self,
+        id: &str,
+        status: QueuedOperationStatus,
+        error: Option<String>,
+    ) -> Result<QueuedOperation, OfflineQueueError> {
+        let mut operations = self.operations.lock().await;
+
+        let operation = operations
+            .iter_mut()
+            .find(|operation| operation.id == id)
+            .ok_or_else(|| OfflineQueueError::UnknownOperation(id.to_string()))?;
+        operation.status = status;
+        operation.error = error;
+        let operation = operation.clone();
+
+        self.save(&operations)?;
+        Ok(operation)
+    }
+
+    /// Record the outcome of a queued upload once it finished, `None` if the upload wasn't queued.
+    ///
+    /// Running a queued upload only hands out its quote, so it stays running until the upload
+    /// itself completes or fails.
+    pub async fn upload_finished(
+        &self,
+        upload_id: &str,
+        error: Option<String>,
+    ) -> Option<QueuedOperation> {
+        let mut operations = self.operations.lock().await;
+
+        let operation = operations
+            .iter_mut()
+            .find(|operation| match &operation.kind {
+                QueuedOperationKind::Upload { upload_id: id, .. } => {
+                    id == upload_id && operation.status == QueuedOperationStatus::Running
+                }
+                _ => false,
+            })?;
+        operation.status = match error {
+            None => QueuedOperationStatus::Completed,
+            Some(_) => QueuedOperationStatus::Failed,
+        };
+        operation.error = error;
+        let operation = operation.clone();
+
+        if let Err(err) = self.save(&operations) {
+            error!("Failed to save the offline queue: {err}");
+        }
+        Some(operation)
+    }
+
+    pub async fn remove(&self, id: &str) -> Result<(), OfflineQueueError> {
+        let mut operations = self.operations.lock().await;
+
+        let len = operations.len();
+        operations.retain(|operation| operation.id != id);
+        if operations.len() == len {
+            return Err(OfflineQueueError::UnknownOperation(id.to_string()));
+        }
+
+        self.save(&operations)
+    }
+
+    /// Claim the queue for processing, `None` if it is already being processed.
+    pub fn try_start_processing(&self) -> Option<MutexGuard<'_, ()>> {
+        self.processing.try_lock().ok()
+    }
+}
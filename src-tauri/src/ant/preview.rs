@@ -0,0 +1,293 @@
+//! Previews of network files for the webview, served through a custom URI scheme.
+//!
+//! Files are addressed as `autonomi-preview://localhost/private/<datamap hex>` or
+//! `autonomi-preview://localhost/public/<address hex>`, which is what `convertFileSrc(path,
+//! "autonomi-preview")` produces for `private/<hex>` and `public/<hex>` on every platform. An
+//! optional `?name=photo.jpg` query sets the content type. Range requests are supported, so
+//! media elements can seek.
+//!
+//! Only the chunks holding a requested range are fetched and decrypted. They go through the
+//! [`chunk_cache`], decrypted content is never written to disk. Files are served as passive
+//! content: anything that could run scripts is sent as text, in a sandbox.
+
+use crate::ant::chunk_cache::{self, ChunkCacheError};
+use crate::ant::client::{ClientError, SharedClient};
+use crate::ant::files::FileAccess;
+use autonomi::chunk::DataMapChunk;
+use autonomi::data::DataAddress;
+use self_encryption::DataMap;
+use std::collections::VecDeque;
+use tauri::http::{header, Request, Response, StatusCode};
+use tauri::{AppHandle, Manager};
+use thiserror::Error as ThisError;
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+pub const PREVIEW_SCHEME: &str = "autonomi-preview";
+
+/// Largest file served in one response, larger files are only served in ranges.
+const MAX_PREVIEW_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Largest range served in one response, media elements ask for the rest as they play.
+const MAX_RANGE_SIZE: u64 = 8 * 1024 * 1024;
+
+/// Datamaps kept for previews, so seeking doesn't resolve them again.
+const CACHE_CAPACITY: usize = 32;
+
+#[derive(ThisError, Debug)]
+pub enum PreviewError {
+    #[error("Invalid preview URL: {0}")]
+    InvalidUrl(String),
+    #[error("Could not connect to the network: {0:?}")]
+    Connect(#[from] ClientError),
+    #[error("{0}")]
+    ChunkCache(#[from] ChunkCacheError),
+}
+
+/// Root datamaps of previewed files by address, most recently used last.
+#[derive(Default)]
+pub struct PreviewCache {
+    entries: Mutex<VecDeque<(String, DataMap)>>,
+}
+
+impl PreviewCache {
+    async fn get(&self, key: &str) -> Option<DataMap> {
+        let mut entries = self.entries.lock().await;
+        let index = entries.iter().position(|(k, _)| k == key)?;
+        let entry = entries.remove(index)?;
+        let data_map = entry.1.clone();
+        entries.push_back(entry);
+        Some(data_map)
+    }
+
+    async fn insert(&self, key: String, data_map: DataMap) {
+        let mut entries = self.entries.lock().await;
+        entries.retain(|(k, _)| *k != key);
+        entries.push_back((key, data_map));
+
+        while entries.len() > CACHE_CAPACITY {
+            entries.pop_front();
+        }
+    }
+}
+
+/// Parse `private/<hex>` or `public/<hex>` from the path of a preview URL.
+fn parse_file_access(path: &str) -> Result<FileAccess, PreviewError> {
+    // `convertFileSrc` encodes the separator
+    let path = path.replace("%2F", "/").replace("%2f", "/");
+    let invalid = || PreviewError::InvalidUrl(path.clone());
+
+    let (kind, hex) = path.trim_matches('/').split_once('/').ok_or_else(invalid)?;
+    match kind {
+        "private" => DataMapChunk::from_hex(hex)
+            .map(FileAccess::Private)
+            .map_err(|_| invalid()),
+        "public" => DataAddress::from_hex(hex)
+            .map(FileAccess::Public)
+            .map_err(|_| invalid()),
+        _ => Err(invalid()),
+    }
+}
+
+/// Content type from the file name in the `name` query parameter.
+///
+/// Markup that could run scripts is served as text.
+fn content_type(query: Option<&str>) -> &'static str {
+    let extension = query
+        .into_iter()
+        .flat_map(|query| query.split('&'))
+        .find_map(|pair| pair.strip_prefix("name="))
+        .and_then(|name| name.rsplit_once('.'))
+        .map(|(_, extension)| extension.to_ascii_lowercase());
+
+    match extension.as_deref() {
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("pdf") => "application/pdf",
+        Some("mp4" | "m4v") => "video/mp4",
+        Some("webm") => "video/webm",
+        Some("mp3") => "audio/mpeg",
+        Some("ogg") => "audio/ogg",
+        Some("wav") => "audio/wav",
+        Some("txt" | "md" | "log" | "csv" | "html" | "htm" | "svg") => "text/plain; charset=utf-8",
+        Some("json") => "application/json",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Parse a single `bytes=` range, returns the inclusive start and end.
+///
+/// `None` for ranges that can't be satisfied, multiple ranges are not supported.
+fn parse_range(range: &str, len: u64) -> Option<(u64, u64)> {
+    let (start, end) = range.trim().strip_prefix("bytes=")?.split_once('-')?;
+    if len == 0 || start.contains(',') || end.contains(',') {
+        return None;
+    }
+
+    let (start, end) = if start.is_empty() {
+        // The last `end` bytes
+        let suffix: u64 = end.parse().ok()?;
+        (len.saturating_sub(suffix), len - 1)
+    } else {
+        let start: u64 = start.parse().ok()?;
+        let end = match end {
+            "" => len - 1,
+            end => end.parse::<u64>().ok()?.min(len - 1),
+        };
+        (start, end)
+    };
+
+    (start <= end && start < len).then_some((start, end))
+}
+
+async fn data_map(app: &AppHandle, file_access: &FileAccess) -> Result<DataMap, PreviewError> {
+    let key = file_access.local_key();
+    let cache = app.state::<PreviewCache>();
+    if let Some(data_map) = cache.get(&key).await {
+        return Ok(data_map);
+    }
+
+    info!("Fetching {key} for preview");
+    let client = app.state::<SharedClient>().get_client().await?;
+    let data_map = chunk_cache::root_data_map(&client, file_access).await?;
+
+    cache.insert(key, data_map.clone()).await;
+    Ok(data_map)
+}
+
+/// The inclusive byte range `start..=end` of a file.
+async fn fetch(
+    app: &AppHandle,
+    data_map: &DataMap,
+    start: u64,
+    end: u64,
+) -> Result<Vec<u8>, PreviewError> {
+    let client = app.state::<SharedClient>().get_client().await?;
+    let data =
+        chunk_cache::data_get_range(&client, data_map, start as usize..end as usize + 1).await?;
+    Ok(data.into())
+}
+
+fn respond(status: StatusCode, body: Vec<u8>) -> Response<Vec<u8>> {
+    Response::builder()
+        .status(status)
+        .body(body)
+        .unwrap_or_default()
+}
+
+/// Answer a request to the preview scheme.
+pub async fn handle_request(app: AppHandle, request: Request<Vec<u8>>) -> Response<Vec<u8>> {
+    let file_access = match parse_file_access(request.uri().path()) {
+        Ok(file_access) => file_access,
+        Err(err) => return respond(StatusCode::BAD_REQUEST, err.to_string().into_bytes()),
+    };
+
+    let data_map = match data_map(&app, &file_access).await {
+        Ok(data_map) => data_map,
+        Err(err) => {
+            warn!("Failed to preview {}: {err}", file_access.local_key());
+            return respond(StatusCode::BAD_GATEWAY, err.to_string().into_bytes());
+        }
+    };
+
+    let len = data_map.original_file_size() as u64;
+    let range = match request
+        .headers()
+        .get(header::RANGE)
+        .and_then(|range| range.to_str().ok())
+    {
+        None if len > MAX_PREVIEW_SIZE => {
+            return respond(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                b"File is too large to preview at once, request a range".to_vec(),
+            )
+        }
+        None => None,
+        // Long ranges are cut short, the client asks again for the rest
+        Some(range) => Some(
+            parse_range(range, len)
+                .map(|(start, end)| (start, end.min(start + MAX_RANGE_SIZE - 1))),
+        ),
+    };
+
+    let builder = Response::builder()
+        .header(header::ACCEPT_RANGES, "bytes")
+        .header(header::CONTENT_TYPE, content_type(request.uri().query()))
+        .header(header::CONTENT_SECURITY_POLICY, "sandbox")
+        .header(header::X_CONTENT_TYPE_OPTIONS, "nosniff");
+
+    let (status, start, end) = match range {
+        None if len == 0 => {
+            return builder
+                .status(StatusCode::OK)
+                .header(header::CONTENT_LENGTH, 0)
+                .body(vec![])
+                .unwrap_or_default()
+        }
+        None => (StatusCode::OK, 0, len - 1),
+        Some(Some((start, end))) => (StatusCode::PARTIAL_CONTENT, start, end),
+        Some(None) => {
+            return builder
+                .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                .header(header::CONTENT_RANGE, format!("bytes */{len}"))
+                .body(vec![])
+                .unwrap_or_default()
+        }
+    };
+
+    let data = match fetch(&app, &data_map, start, end).await {
+        Ok(data) => data,
+        Err(err) => {
+            warn!("Failed to preview {}: {err}", file_access.local_key());
+            return respond(StatusCode::BAD_GATEWAY, err.to_string().into_bytes());
+        }
+    };
+
+    let builder = builder
+        .status(status)
+        .header(header::CONTENT_LENGTH, data.len());
+    let response = match status {
+        StatusCode::PARTIAL_CONTENT => builder
+            .header(header::CONTENT_RANGE, format!("bytes {start}-{end}/{len}"))
+            .body(data),
+        _ => builder.body(data),
+    };
+
+    response.unwrap_or_else(|err| {
+        respond(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            err.to_string().into_bytes(),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range("bytes=0-99", 1000), Some((0, 99)));
+        assert_eq!(parse_range("bytes=900-", 1000), Some((900, 999)));
+        assert_eq!(parse_range("bytes=-100", 1000), Some((900, 999)));
+        assert_eq!(parse_range("bytes=500-5000", 1000), Some((500, 999)));
+        assert_eq!(parse_range("bytes=1000-", 1000), None);
+        assert_eq!(parse_range("bytes=0-1,5-9", 1000), None);
+        assert_eq!(parse_range("items=0-1", 1000), None);
+    }
+
+    #[test]
+    fn test_content_type() {
+        assert_eq!(content_type(Some("name=Photo.JPG")), "image/jpeg");
+        assert_eq!(content_type(Some("x=1&name=doc.pdf")), "application/pdf");
+        assert_eq!(content_type(None), "application/octet-stream");
+        for name in ["page.html", "page.htm", "logo.svg"] {
+            assert_eq!(
+                content_type(Some(&format!("name={name}"))),
+                "text/plain; charset=utf-8"
+            );
+        }
+    }
+}
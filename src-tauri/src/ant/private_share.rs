@@ -0,0 +1,725 @@
+//! Revocable sharing of private files and archives with a specific recipient.
+//!
+//! The recipient hands out the public half of a sharing key derived from their vault key. The
+//! sender encrypts the [`FileAccess`] and a name to that key, stores the ciphertext in a chunk and
+//! publishes a pointer to it. The recipient keeps the pointer address and resolves it with their
+//! vault unlocked every time the share is opened.
+//!
+//! The pointer is owned by a key derived from the sender's vault key and the share id, so no
+//! secrets are written to disk. Updating a share moves the pointer onto a new envelope, so
+//! recipients follow it to the new version. Revoking a share rotates the pointer onto itself:
+//! whatever the recipient already downloaded stays readable, but the share no longer resolves.
+
+use crate::ant::app_data;
+use crate::ant::client::{ClientError, SharedClient};
+use crate::ant::files::{FileAccess, UploadProgress};
+use crate::ant::import::{self, ImportError, ReferencePreview};
+use crate::ant::vault_registry::derive_vault_key;
+use autonomi::chunk::Chunk;
+use autonomi::client::payment::{PaymentOption, Receipt};
+use autonomi::client::quote::{DataTypes, StoreQuote};
+use autonomi::client::vault::VaultSecretKey;
+use autonomi::pointer::PointerTarget;
+use autonomi::{Amount, Bytes, Pointer, PointerAddress, PublicKey, SecretKey};
+use blsttc::Ciphertext;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter, State};
+use thiserror::Error as ThisError;
+use tokio::sync::Mutex;
+use tracing::info;
+
+const SHARES_FILE: &str = "shares.json";
+const RECEIVED_SHARES_FILE: &str = "received_shares.json";
+
+/// Derivation label of the key recipients are shared to.
+const SHARING_KEY_LABEL: &str = "sharing";
+
+#[derive(ThisError, Debug)]
+pub enum ShareError {
+    #[error("Could not determine the app data directory")]
+    NoDataDir,
+    #[error("Could not store the share list: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Could not serialize share: {0}")]
+    Serialization(#[from] serde_json::Error),
+    #[error("Not a valid recipient key: {0}")]
+    InvalidRecipient(String),
+    #[error("Not a valid share address: {0}")]
+    InvalidAddress(String),
+    #[error("Unknown share: {0}")]
+    UnknownShare(String),
+    #[error("Share {0} is revoked")]
+    AlreadyRevoked(String),
+    #[error("Share {0} was created from a different vault")]
+    WrongVault(String),
+    #[error("This share was revoked")]
+    Revoked,
+    #[error("This share is not addressed to this vault")]
+    NotForThisVault,
+    #[error("Unknown received share: {0}")]
+    UnknownReceivedShare(String),
+    #[error("Could not connect to the network: {0:?}")]
+    Connect(#[from] ClientError),
+    #[error("Failed to retrieve store quotes: {0}")]
+    StoreQuote(String),
+    #[error("Network operation failed: {0}")]
+    Network(String),
+    #[error("Failed to emit event: {0}")]
+    EmitEvent(String),
+    #[error("{0}")]
+    Import(#[from] ImportError),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ShareStatus {
+    Active,
+    Revoked,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Share {
+    pub id: String,
+    pub created: u64,
+    pub name: String,
+    pub file_access: FileAccess,
+    /// Public sharing key of the recipient, hex.
+    pub recipient: String,
+    /// Pointer address the recipient opens the share with, hex.
+    pub address: String,
+    pub status: ShareStatus,
+}
+
+/// What the recipient decrypts from a share.
+#[derive(Debug, Serialize, Deserialize)]
+struct Envelope {
+    file_access: FileAccess,
+    name: String,
+}
+
+/// A share received from someone else, resolved through its pointer whenever it is opened.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReceivedShare {
+    /// Pointer address of the share, hex.
+    pub address: String,
+    pub name: String,
+    pub received: u64,
+    /// Public sharing key of the vault the share is addressed to, hex.
+    pub recipient: String,
+}
+
+/// How a paid share gets to the network.
+enum Publish {
+    /// A new share, its pointer is created.
+    Create(Box<Pointer>),
+    /// A new version of a share, the pointer owned by the key is moved onto it.
+    Update(SecretKey),
+}
+
+/// A share waiting for its envelope, and the pointer of a new share, to be paid for.
+pub struct PendingShare {
+    share: Share,
+    chunk: Chunk,
+    publish: Publish,
+}
+
+/// The shares this app created, kept in the app data directory.
+pub struct ShareRegistry {
+    path: Option<PathBuf>,
+    shares: Mutex<Vec<Share>>,
+}
+
+impl Default for ShareRegistry {
+    fn default() -> Self {
+        Self::load()
+    }
+}
+
+impl ShareRegistry {
+    pub fn load() -> Self {
+        let path = app_data::data_dir().map(|dir| dir.join(SHARES_FILE));
+
+        let shares = path
+            .as_deref()
+            .and_then(app_data::load_json)
+            .unwrap_or_default();
+
+        Self {
+            path,
+            shares: Mutex::new(shares),
+        }
+    }
+
+    fn save(&self, shares: &[Share]) -> Result<(), ShareError> {
+        let path = self.path.as_ref().ok_or(ShareError::NoDataDir)?;
+        app_data::save_json(path, shares)?;
+        Ok(())
+    }
+
+    pub async fn list(&self) -> Vec<Share> {
+        self.shares.lock().await.clone()
+    }
+
+    /// Add a share, or replace the share with the same id.
+    async fn put(&self, share: Share) -> Result<(), ShareError> {
+        let mut shares = self.shares.lock().await;
+        match shares.iter_mut().find(|existing| existing.id == share.id) {
+            Some(existing) => *existing = share,
+            None => shares.push(share),
+        }
+        self.save(&shares)
+    }
+
+    async fn get(&self, id: &str) -> Result<Share, ShareError> {
+        self.shares
+            .lock()
+            .await
+            .iter()
+            .find(|share| share.id == id)
+            .cloned()
+            .ok_or_else(|| ShareError::UnknownShare(id.to_string()))
+    }
+
+    async fn set_status(&self, id: &str, status: ShareStatus) -> Result<Share, ShareError> {
+        let mut shares = self.shares.lock().await;
+        let share = shares
+            .iter_mut()
+            .find(|share| share.id == id)
+            .ok_or_else(|| ShareError::UnknownShare(id.to_string()))?;
+        share.status = status;
+        let share = share.clone();
+
+        self.save(&shares)?;
+        Ok(share)
+    }
+}
+
+/// The shares received by the vaults of this app, kept in the app data directory.
+pub struct ReceivedShares {
+    path: Option<PathBuf>,
+    shares: Mutex<Vec<ReceivedShare>>,
+}
+
+impl Default for ReceivedShares {
+    fn default() -> Self {
+        Self::load()
+    }
+}
+
+impl ReceivedShares {
+    pub fn load() -> Self {
+        let path = app_data::data_dir().map(|dir| dir.join(RECEIVED_SHARES_FILE));
+
+        let shares = path
+            .as_deref()
+            .and_then(app_data::load_json)
+            .unwrap_or_default();
+
+        Self {
+            path,
+            shares: Mutex::new(shares),
+        }
+    }
+
+    fn save(&self, shares: &[ReceivedShare]) -> Result<(), ShareError> {
+        let path = self.path.as_ref().ok_or(ShareError::NoDataDir)?;
+        app_data::save_json(path, shares)?;
+        Ok(())
+    }
+
+    /// The shares received by the vault `vault_key` belongs to.
+    pub async fn list(&self, vault_key: &VaultSecretKey) -> Vec<ReceivedShare> {
+        let recipient = sharing_public_key(vault_key);
+        self.shares
+            .lock()
+            .await
+            .iter()
+            .filter(|share| share.recipient == recipient)
+            .cloned()
+            .collect()
+    }
+
+    /// Add a share, or rename the share with the same address.
+    async fn put(&self, share: ReceivedShare) -> Result<(), ShareError> {
+        let mut shares = self.shares.lock().await;
+        match shares
+            .iter_mut()
+            .find(|existing| existing.address == share.address)
+        {
+            Some(existing) => existing.name = share.name,
+            None => shares.push(share),
+        }
+        self.save(&shares)
+    }
+
+    pub async fn remove(&self, address: &str) -> Result<(), ShareError> {
+        let mut shares = self.shares.lock().await;
+        let len = shares.len();
+        shares.retain(|share| share.address != address);
+        if shares.len() == len {
+            return Err(ShareError::UnknownReceivedShare(address.to_string()));
+        }
+        self.save(&shares)
+    }
+}
+
+/// The key shares to this vault are encrypted to.
+fn sharing_key(vault_key: &VaultSecretKey) -> VaultSecretKey {
+    derive_vault_key(vault_key, SHARING_KEY_LABEL)
+}
+
+/// The key that owns the pointer of a share.
+fn share_owner_key(vault_key: &VaultSecretKey, id: &str) -> VaultSecretKey {
+    derive_vault_key(vault_key, &format!("share/{id}"))
+}
+
+/// The public key others share to this vault with, hex.
+pub fn sharing_public_key(vault_key: &VaultSecretKey) -> String {
+    sharing_key(vault_key).public_key().to_hex()
+}
+
+fn encrypt_envelope(recipient: &PublicKey, envelope: &Envelope) -> Result<Chunk, ShareError> {
+    let ciphertext = recipient.encrypt(serde_json::to_vec(envelope)?);
+    Ok(Chunk::new(Bytes::from(ciphertext.to_bytes())))
+}
+
+fn decrypt_envelope(key: &VaultSecretKey, content: &[u8]) -> Result<Envelope, ShareError> {
+    let ciphertext = Ciphertext::from_bytes(content).map_err(|_| ShareError::NotForThisVault)?;
+    let plaintext = key
+        .decrypt(&ciphertext)
+        .ok_or(ShareError::NotForThisVault)?;
+    Ok(serde_json::from_slice(&plaintext)?)
+}
+
+/// Quote a share of `file_access` to `recipient`, it is published once the quote is paid.
+///
+/// Emits the same `upload-quote` event as uploads, the payment is confirmed through
+/// `confirm_upload_payment` with `upload_id`.
+#[allow(clippy::too_many_arguments)]
+pub async fn start_share(
+    app: AppHandle,
+    file_access: FileAccess,
+    name: String,
+    recipient: &str,
+    vault_key: &VaultSecretKey,
+    upload_id: String,
+    shared_client: State<'_, SharedClient>,
+    pending_uploads: &Mutex<crate::PendingUploads>,
+) -> Result<(), ShareError> {
+    let recipient_key = PublicKey::from_hex(recipient.trim().trim_start_matches("0x"))
+        .map_err(|_| ShareError::InvalidRecipient(recipient.to_string()))?;
+
+    let id = hex::encode(rand::random::<[u8; 8]>());
+    let chunk = encrypt_envelope(
+        &recipient_key,
+        &Envelope {
+            file_access: file_access.clone(),
+            name: name.clone(),
+        },
+    )?;
+    let pointer = Pointer::new(
+        &share_owner_key(vault_key, &id),
+        0,
+        PointerTarget::ChunkAddress(chunk.address),
+    );
+    let address = pointer.address();
+
+    let share = Share {
+        id,
+        created: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0),
+        name,
+        file_access,
+        recipient: recipient_key.to_hex(),
+        address: address.to_hex(),
+        status: ShareStatus::Active,
+    };
+    info!("Quoting share {} of {}", share.id, share.name);
+
+    quote_share(
+        app,
+        PendingShare {
+            share,
+            chunk,
+            publish: Publish::Create(Box::new(pointer)),
+        },
+        upload_id,
+        shared_client,
+        pending_uploads,
+    )
+    .await
+}
+
+/// Quote a new version of a share, it replaces what the share points to once the quote is paid.
+///
+/// Needs the vault the share was created from, the payment is confirmed like for
+/// [`start_share`].
+#[allow(clippy::too_many_arguments)]
+pub async fn update_share(
+    app: AppHandle,
+    id: &str,
+    file_access: FileAccess,
+    name: String,
+    vault_key: &VaultSecretKey,
+    upload_id: String,
+    shared_client: State<'_, SharedClient>,
+    shares: State<'_, ShareRegistry>,
+    pending_uploads: &Mutex<crate::PendingUploads>,
+) -> Result<(), ShareError> {
+    let share = shares.get(id).await?;
+    if share.status == ShareStatus::Revoked {
+        return Err(ShareError::AlreadyRevoked(id.to_string()));
+    }
+
+    let owner = share_owner_key(vault_key, id);
+    if PointerAddress::new(owner.public_key()).to_hex() != share.address {
+        return Err(ShareError::WrongVault(id.to_string()));
+    }
+
+    let recipient_key = PublicKey::from_hex(&share.recipient)
+        .map_err(|_| ShareError::InvalidRecipient(share.recipient.clone()))?;
+    let chunk = encrypt_envelope(
+        &recipient_key,
+        &Envelope {
+            file_access: file_access.clone(),
+            name: name.clone(),
+        },
+    )?;
+    info!("Quoting update of share {id} to {name}");
+
+    quote_share(
+        app,
+        PendingShare {
+            share: Share {
+                name,
+                file_access,
+                ..share
+            },
+            chunk,
+            publish: Publish::Update(owner),
+        },
+        upload_id,
+        shared_client,
+        pending_uploads,
+    )
+    .await
+}
+
+/// Quote the envelope of a share, and the pointer of a new share, and keep it until paid.
+async fn quote_share(
+    app: AppHandle,
+    pending: PendingShare,
+    upload_id: String,
+    shared_client: State<'_, SharedClient>,
+    pending_uploads: &Mutex<crate::PendingUploads>,
+) -> Result<(), ShareError> {
+    let chunk = &pending.chunk;
+    let client = shared_client.get_client().await?;
+    let mut store_quote: StoreQuote = client
+        .get_store_quotes(
+            DataTypes::Chunk,
+            std::iter::once((*chunk.address.xorname(), chunk.value.len())),
+        )
+        .await
+        .map_err(|err| ShareError::StoreQuote(err.to_string()))?;
+    if let Publish::Create(pointer) = &pending.publish {
+        let pointer_quote = client
+            .get_store_quotes(
+                DataTypes::Pointer,
+                std::iter::once((pointer.address().xorname(), Pointer::size())),
+            )
+            .await
+            .map_err(|err| ShareError::StoreQuote(err.to_string()))?;
+        store_quote.0.extend(pointer_quote.0);
+    }
+
+    let total_cost: Amount = store_quote
+        .payments()
+        .iter()
+        .map(|(_, _, amount)| *amount)
+        .sum();
+
+    let payments: Vec<serde_json::Value> = store_quote
+        .payments()
+        .iter()
+        .filter(|(_, _, amount)| *amount > Amount::ZERO)
+        .map(|(addr, _, amount)| {
+            serde_json::json!({
+                "address": hex::encode(addr),
+                "amount": amount.to_string(),
+                "amount_formatted": format!("{} {}", amount, "ATTO")
+            })
+        })
+        .collect();
+
+    let raw_payments: Vec<_> = store_quote
+        .payments()
+        .into_iter()
+        .filter(|(_, _, amount)| *amount > Amount::ZERO)
+        .collect();
+
+    app.emit(
+        "upload-quote",
+        serde_json::json!({
+            "upload_id": upload_id.clone(),
+            "total_files": 1,
+            "total_size": chunk.value.len(),
+            "total_cost_nano": total_cost.to_string(),
+            "total_cost_formatted": format!("{} {}", total_cost, "ATTO"),
+            "payment_required": total_cost > Amount::ZERO,
+            "payments": payments,
+            "raw_payments": raw_payments
+        }),
+    )
+    .map_err(|err| ShareError::EmitEvent(err.to_string()))?;
+
+    pending_uploads
+        .lock()
+        .await
+        .store_share(upload_id, pending, store_quote);
+
+    Ok(())
+}
+
+/// Publish a share after its quote was paid, and add it to the share list or update it there.
+pub async fn execute_share(
+    app: AppHandle,
+    pending: PendingShare,
+    receipt: Receipt,
+    upload_id: String,
+    shared_client: State<'_, SharedClient>,
+    shares: State<'_, ShareRegistry>,
+) -> Result<Share, ShareError> {
+    let client = shared_client.get_client().await?;
+    let PendingShare {
+        share,
+        chunk,
+        publish,
+    } = pending;
+    let total_bytes = chunk.value.len() as u64;
+
+    client
+        .chunk_put(&chunk, PaymentOption::Receipt(receipt.clone()))
+        .await
+        .map_err(|err| ShareError::Network(err.to_string()))?;
+    match publish {
+        Publish::Create(pointer) => client
+            .pointer_put(*pointer, PaymentOption::Receipt(receipt))
+            .await
+            .map(|_| ()),
+        Publish::Update(owner) => {
+            client
+                .pointer_update(&owner, PointerTarget::ChunkAddress(chunk.address))
+                .await
+        }
+    }
+    .map_err(|err| ShareError::Network(err.to_string()))?;
+
+    shares.put(share.clone()).await?;
+    info!("Published share {} at {}", share.id, share.address);
+
+    app.emit(
+        "upload-progress",
+        UploadProgress::Completed {
+            upload_id,
+            total_files: 1,
+            total_bytes,
+            add_to_vault: false,
+            file_access: Some(share.file_access.clone()),
+        },
+    )
+    .map_err(|err| ShareError::EmitEvent(err.to_string()))?;
+
+    Ok(share)
+}
+
+/// Revoke a share by pointing its pointer at itself.
+///
+/// Needs the vault the share was created from.
+pub async fn revoke_share(
+    id: &str,
+    vault_key: &VaultSecretKey,
+    shared_client: State<'_, SharedClient>,
+    shares: State<'_, ShareRegistry>,
+) -> Result<Share, ShareError> {
+    let share = shares.get(id).await?;
+    if share.status == ShareStatus::Revoked {
+        return Err(ShareError::AlreadyRevoked(id.to_string()));
+    }
+
+    let owner = share_owner_key(vault_key, id);
+    let address = PointerAddress::new(owner.public_key());
+    if address.to_hex() != share.address {
+        return Err(ShareError::WrongVault(id.to_string()));
+    }
+
+    let client = shared_client.get_client().await?;
+    client
+        .pointer_update(&owner, PointerTarget::PointerAddress(address))
+        .await
+        .map_err(|err| ShareError::Network(err.to_string()))?;
+
+    info!("Revoked share {id}");
+    shares.set_status(id, ShareStatus::Revoked).await
+}
+
+/// What a share addressed to this vault currently points to.
+async fn resolve_share(
+    address: &PointerAddress,
+    vault_key: &VaultSecretKey,
+    shared_client: &SharedClient,
+) -> Result<Envelope, ShareError> {
+    let client = shared_client.get_client().await?;
+    let pointer = client
+        .pointer_get(address)
+        .await
+        .map_err(|err| ShareError::Network(err.to_string()))?;
+    let PointerTarget::ChunkAddress(chunk_address) = pointer.target() else {
+        return Err(ShareError::Revoked);
+    };
+
+    let chunk = client
+        .chunk_get(chunk_address)
+        .await
+        .map_err(|err| ShareError::Network(err.to_string()))?;
+    decrypt_envelope(&sharing_key(vault_key), &chunk.value)
+}
+
+fn parse_address(address: &str) -> Result<PointerAddress, ShareError> {
+    PointerAddress::from_hex(address.trim().trim_start_matches("0x"))
+        .map_err(|_| ShareError::InvalidAddress(address.to_string()))
+}
+
+/// Add a share addressed to this vault to the received shares, and preview what it points to.
+///
+/// `name` overrides the name the sender chose.
+pub async fn receive_share(
+    address: &str,
+    name: Option<String>,
+    vault_key: &VaultSecretKey,
+    shared_client: State<'_, SharedClient>,
+    received: State<'_, ReceivedShares>,
+) -> Result<ReferencePreview, ShareError> {
+    let address = parse_address(address)?;
+    let envelope = resolve_share(&address, vault_key, &shared_client).await?;
+    let preview = import::preview(envelope.file_access, &shared_client).await?;
+
+    let name = name.unwrap_or(envelope.name);
+    info!("Received share {} as {name}", address.to_hex());
+    received
+        .put(ReceivedShare {
+            address: address.to_hex(),
+            name,
+            received: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            recipient: sharing_public_key(vault_key),
+        })
+        .await?;
+
+    Ok(preview)
+}
+
+/// Preview the current version of a received share.
+pub async fn open_received_share(
+    address: &str,
+    vault_key: &VaultSecretKey,
+    shared_client: State<'_, SharedClient>,
+) -> Result<ReferencePreview, ShareError> {
+    let envelope = resolve_share(&parse_address(address)?, vault_key, &shared_client).await?;
+    Ok(import::preview(envelope.file_access, &shared_client).await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use autonomi::data::DataAddress;
+    use autonomi::XorName;
+
+    fn envelope() -> Envelope {
+        Envelope {
+            file_access: FileAccess::Public(DataAddress::new(XorName::from_content(b"shared"))),
+            name: "report.pdf".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_envelope_round_trip() {
+        let recipient = sharing_key(&VaultSecretKey::random());
+        let chunk = encrypt_envelope(&recipient.public_key(), &envelope()).unwrap();
+
+        let decrypted = decrypt_envelope(&recipient, &chunk.value).unwrap();
+        assert_eq!(decrypted.name, "report.pdf");
+        assert_eq!(
+            decrypted.file_access.local_key(),
+            envelope().file_access.local_key()
+        );
+    }
+
+    #[test]
+    fn test_envelope_wrong_recipient() {
+        let recipient = sharing_key(&VaultSecretKey::random());
+        let chunk = encrypt_envelope(&recipient.public_key(), &envelope()).unwrap();
+
+        let other = sharing_key(&VaultSecretKey::random());
+        assert!(matches!(
+            decrypt_envelope(&other, &chunk.value),
+            Err(ShareError::NotForThisVault)
+        ));
+    }
+
+    #[test]
+    fn test_share_keys_are_distinct() {
+        let vault_key = VaultSecretKey::random();
+        assert_ne!(
+            share_owner_key(&vault_key, "a").public_key(),
+            share_owner_key(&vault_key, "b").public_key()
+        );
+        assert_ne!(
+            share_owner_key(&vault_key, "a").public_key(),
+            sharing_key(&vault_key).public_key()
+        );
+    }
+
+    #[test]
+    fn test_received_shares_per_vault() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(RECEIVED_SHARES_FILE);
+        let received = ReceivedShares {
+            path: Some(path.clone()),
+            shares: Mutex::new(vec![]),
+        };
+        let (vault, other) = (VaultSecretKey::random(), VaultSecretKey::random());
+        let share = |address: &str, name: &str, vault_key: &VaultSecretKey| ReceivedShare {
+            address: address.to_string(),
+            name: name.to_string(),
+            received: 0,
+            recipient: sharing_public_key(vault_key),
+        };
+
+        tauri::async_runtime::block_on(async {
+            received.put(share("a", "first", &vault)).await.unwrap();
+            received.put(share("b", "second", &other)).await.unwrap();
+            received.put(share("a", "renamed", &vault)).await.unwrap();
+
+            let shares = received.list(&vault).await;
+            assert_eq!(shares.len(), 1);
+            assert_eq!(shares[0].name, "renamed");
+
+            received.remove("a").await.unwrap();
+            assert!(received.list(&vault).await.is_empty());
+            assert!(matches!(
+                received.remove("a").await,
+                Err(ShareError::UnknownReceivedShare(_))
+            ));
+        });
+
+        let saved: Vec<ReceivedShare> = app_data::load_json(&path).unwrap();
+        assert_eq!(saved.len(), 1);
+        assert_eq!(saved[0].address, "b");
+    }
+}
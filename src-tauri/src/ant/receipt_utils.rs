@@ -1,6 +1,7 @@
 use autonomi::client::payment::Receipt;
 use autonomi::XorName;
 use std::collections::HashSet;
+use tracing::info;
 
 #[derive(Debug, Clone)]
 pub struct ReceiptValidation {
@@ -15,7 +16,7 @@ pub fn validate_receipt_coverage_with_content_addresses(
     // Extract chunk addresses covered by the receipt
     let covered_chunks = extract_covered_chunks(receipt);
 
-    println!(
+    info!(
         ">>> Receipt validation: receipt covers {} chunks, quote requires {} chunks",
         covered_chunks.len(),
         content_addresses.len()
@@ -32,12 +33,12 @@ pub fn validate_receipt_coverage_with_content_addresses(
     }
 
     if !missing_chunks.is_empty() {
-        println!(
+        info!(
             ">>> Receipt validation: {} chunks are missing from the cached receipt",
             missing_chunks.len()
         );
     } else {
-        println!(">>> Receipt validation: All chunks from quote are covered by cached receipt");
+        info!(">>> Receipt validation: All chunks from quote are covered by cached receipt");
     }
 
     ReceiptValidation {
@@ -55,7 +56,7 @@ pub fn merge_receipts(receipts: Vec<Receipt>) -> Receipt {
         return receipts.into_iter().next().unwrap();
     }
 
-    println!(">>> Merging {} receipts", receipts.len());
+    info!(">>> Merging {} receipts", receipts.len());
 
     // Merge all receipts (HashMap) into one
     let mut merged_receipt = Receipt::new();
@@ -63,7 +64,7 @@ pub fn merge_receipts(receipts: Vec<Receipt>) -> Receipt {
 
     for (idx, receipt) in receipts.into_iter().enumerate() {
         let chunks_in_receipt = receipt.len();
-        println!(
+        info!(
             ">>> Receipt {}: contains {} chunks",
             idx + 1,
             chunks_in_receipt
@@ -76,7 +77,7 @@ pub fn merge_receipts(receipts: Vec<Receipt>) -> Receipt {
         total_chunks_merged += chunks_in_receipt;
     }
 
-    println!(
+    info!(
         ">>> Merged receipt contains {} unique chunks (from {} total chunks)",
         merged_receipt.len(),
         total_chunks_merged
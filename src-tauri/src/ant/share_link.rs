@@ -0,0 +1,296 @@
+//! Shareable `autonomi://` links to files and archives.
+//!
+//! A link carries a [`FileAccess`] together with optional hints for the receiver:
+//!
+//! ```text
+//! autonomi://public/<address hex>?name=photo.jpg&size=52311&type=file
+//! autonomi://private/<datamap hex>?name=Holiday&type=archive
+//! ```
+//!
+//! Hints are only used to name downloads and to show something before the network answers, the
+//! actual type and size are always looked up again. Unknown query parameters are ignored so newer
+//! versions can add hints.
+//!
+//! The app is registered as the handler of the scheme. Links it receives are kept until the
+//! frontend takes them, and announced with a `share-link` event.
+
+use crate::ant::client::SharedClient;
+use crate::ant::download_queue::{DownloadQueue, DownloadQueueError, QueuedDownload};
+use crate::ant::files::{self, FileAccess};
+use crate::ant::import::{self, ImportError, ReferenceKind, ReferencePreview};
+use autonomi::chunk::DataMapChunk;
+use autonomi::data::DataAddress;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Emitter, Manager, Url};
+use thiserror::Error as ThisError;
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+pub const SHARE_SCHEME: &str = "autonomi";
+
+#[derive(ThisError, Debug)]
+pub enum ShareLinkError {
+    #[error("Not a valid link: {0}")]
+    InvalidLink(String),
+    #[error("Not an {SHARE_SCHEME}:// link: {0}")]
+    UnsupportedScheme(String),
+    #[error("Link does not contain a valid address or datamap")]
+    InvalidAddress,
+    #[error("Invalid `{0}` in link: {1}")]
+    InvalidHint(&'static str, String),
+    #[error("{0}")]
+    Import(#[from] ImportError),
+    #[error("{0}")]
+    DownloadQueue(#[from] DownloadQueueError),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShareLink {
+    pub file_access: FileAccess,
+    /// File or archive name, without any directories.
+    pub name: Option<String>,
+    /// Size in bytes.
+    pub size: Option<u64>,
+    pub kind: Option<ReferenceKind>,
+}
+
+/// What opening a link did.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum OpenedShareLink {
+    Preview {
+        link: ShareLink,
+        preview: ReferencePreview,
+    },
+    Download {
+        link: ShareLink,
+        download: QueuedDownload,
+    },
+}
+
+/// Links received from the operating system that the frontend didn't take yet.
+#[derive(Default)]
+pub struct ReceivedShareLinks {
+    links: Mutex<Vec<ShareLink>>,
+}
+
+impl ReceivedShareLinks {
+    pub async fn take(&self) -> Vec<ShareLink> {
+        std::mem::take(&mut *self.links.lock().await)
+    }
+}
+
+fn kind_hint(kind: ReferenceKind) -> &'static str {
+    match kind {
+        ReferenceKind::File => "file",
+        ReferenceKind::Archive => "archive",
+    }
+}
+
+/// A name is only accepted if it can't point a download outside its folder.
+fn validate_name(name: &str) -> Result<(), ShareLinkError> {
+    let is_plain = !name.contains(['/', '\\'])
+        && Path::new(name).file_name().and_then(|n| n.to_str()) == Some(name);
+    if is_plain {
+        Ok(())
+    } else {
+        Err(ShareLinkError::InvalidHint("name", name.to_string()))
+    }
+}
+
+pub fn encode(link: &ShareLink) -> Result<String, ShareLinkError> {
+    let (access, hex) = match &link.file_access {
+        FileAccess::Private(data_map) => ("private", data_map.to_hex()),
+        FileAccess::Public(addr) => ("public", addr.to_hex()),
+    };
+
+    let mut url = Url::parse(&format!("{SHARE_SCHEME}://{access}/{hex}"))
+        .map_err(|err| ShareLinkError::InvalidLink(err.to_string()))?;
+    {
+        let mut query = url.query_pairs_mut();
+        if let Some(name) = &link.name {
+            validate_name(name)?;
+            query.append_pair("name", name);
+        }
+        if let Some(size) = link.size {
+            query.append_pair("size", &size.to_string());
+        }
+        if let Some(kind) = link.kind {
+            query.append_pair("type", kind_hint(kind));
+        }
+    }
+    // No hints, no dangling `?`
+    if url.query() == Some("") {
+        url.set_query(None);
+    }
+
+    Ok(url.to_string())
+}
+
+pub fn decode(link: &str) -> Result<ShareLink, ShareLinkError> {
+    let url = Url::parse(link.trim()).map_err(|_| ShareLinkError::InvalidLink(link.to_string()))?;
+    if url.scheme() != SHARE_SCHEME {
+        return Err(ShareLinkError::UnsupportedScheme(link.to_string()));
+    }
+
+    let hex = url.path().trim_matches('/');
+    let file_access = match url.host_str() {
+        Some("private") => DataMapChunk::from_hex(hex)
+            .map(FileAccess::Private)
+            .map_err(|_| ShareLinkError::InvalidAddress)?,
+        Some("public") => DataAddress::from_hex(hex)
+            .map(FileAccess::Public)
+            .map_err(|_| ShareLinkError::InvalidAddress)?,
+        _ => return Err(ShareLinkError::InvalidLink(link.to_string())),
+    };
+
+    let mut share_link = ShareLink {
+        file_access,
+        name: None,
+        size: None,
+        kind: None,
+    };
+    for (key, value) in url.query_pairs() {
+        match key.as_ref() {
+            "name" => {
+                validate_name(&value)?;
+                share_link.name = Some(value.into_owned());
+            }
+            "size" => {
+                let size = value
+                    .parse()
+                    .map_err(|_| ShareLinkError::InvalidHint("size", value.to_string()))?;
+                share_link.size = Some(size);
+            }
+            "type" => {
+                share_link.kind = Some(match value.as_ref() {
+                    "file" => ReferenceKind::File,
+                    "archive" => ReferenceKind::Archive,
+                    _ => return Err(ShareLinkError::InvalidHint("type", value.to_string())),
+                });
+            }
+            _ => {}
+        }
+    }
+
+    Ok(share_link)
+}
+
+/// Open a link: preview it, or queue a download into `download_dir` if one is given.
+pub async fn open(
+    app: &AppHandle,
+    link: &str,
+    download_dir: Option<PathBuf>,
+) -> Result<OpenedShareLink, ShareLinkError> {
+    let link = decode(link)?;
+
+    let Some(download_dir) = download_dir else {
+        let preview =
+            import::preview(link.file_access.clone(), &app.state::<SharedClient>()).await?;
+        return Ok(OpenedShareLink::Preview { link, preview });
+    };
+
+    let name = link
+        .name
+        .clone()
+        .unwrap_or_else(|| link.file_access.local_key());
+    let dest = download_dir.join(name);
+    let dest = files::unique_download_path(&dest).unwrap_or(dest);
+
+    let download = app
+        .state::<DownloadQueue>()
+        .push(link.file_access.clone(), dest, false)
+        .await?;
+    Ok(OpenedShareLink::Download { link, download })
+}
+
+/// Keep links the operating system handed to the app and tell the frontend about them.
+pub async fn receive(app: AppHandle, urls: Vec<Url>) {
+    let received = app.state::<ReceivedShareLinks>();
+    for url in urls {
+        match decode(url.as_str()) {
+            Ok(link) => {
+                info!("Received share link to {}", link.file_access.local_key());
+                received.links.lock().await.push(link.clone());
+                if let Err(err) = app.emit("share-link", link) {
+                    warn!("Failed to emit share link: {err}");
+                }
+            }
+            Err(err) => warn!("Ignoring received link: {err}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use autonomi::XorName;
+
+    fn public_link() -> ShareLink {
+        ShareLink {
+            file_access: FileAccess::Public(DataAddress::new(XorName::from_content(b"shared"))),
+            name: Some("Holiday photos #1.jpg".to_string()),
+            size: Some(52311),
+            kind: Some(ReferenceKind::File),
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let link = public_link();
+        let encoded = encode(&link).unwrap();
+        assert!(encoded.starts_with("autonomi://public/"));
+
+        let decoded = decode(&encoded).unwrap();
+        assert_eq!(
+            decoded.file_access.local_key(),
+            link.file_access.local_key()
+        );
+        assert_eq!(decoded.name, link.name);
+        assert_eq!(decoded.size, link.size);
+        assert_eq!(decoded.kind, link.kind);
+    }
+
+    #[test]
+    fn test_without_hints() {
+        let link = ShareLink {
+            name: None,
+            size: None,
+            kind: None,
+            ..public_link()
+        };
+        let encoded = encode(&link).unwrap();
+        assert!(!encoded.contains('?'));
+
+        let decoded = decode(&encoded).unwrap();
+        assert!(decoded.name.is_none() && decoded.size.is_none() && decoded.kind.is_none());
+    }
+
+    #[test]
+    fn test_invalid_links() {
+        let encoded = encode(&public_link()).unwrap();
+
+        assert!(matches!(
+            decode(&encoded.replace("autonomi://", "https://")),
+            Err(ShareLinkError::UnsupportedScheme(_))
+        ));
+        assert!(matches!(
+            decode("autonomi://public/xyz"),
+            Err(ShareLinkError::InvalidAddress)
+        ));
+        assert!(matches!(
+            decode(&encoded.replace("public/", "secret/")),
+            Err(ShareLinkError::InvalidLink(_))
+        ));
+        assert!(matches!(
+            decode(&format!("{encoded}&size=big")),
+            Err(ShareLinkError::InvalidHint("size", _))
+        ));
+        assert!(matches!(
+            decode(&format!("{encoded}&name=..%2Fevil")),
+            Err(ShareLinkError::InvalidHint("name", _))
+        ));
+        assert!(decode(&format!("{encoded}&future=hint")).is_ok());
+    }
+}
@@ -1,10 +1,11 @@
+use crate::ant::file_metadata::MetadataVaultUpdate;
 use autonomi::client::key_derivation::{DerivationIndex, MainSecretKey};
 use autonomi::client::payment::{PaymentOption, Receipt};
 use autonomi::client::quote::{DataTypes, StoreQuote};
 use autonomi::vault::user_data::USER_DATA_VAULT_CONTENT_IDENTIFIER;
 use autonomi::vault::{
-    vault_split_bytes, VaultError, VaultSecretKey, NUM_OF_SCRATCHPADS_PER_GRAPH_ENTRY,
-    VAULT_HEAD_DERIVATION_INDEX,
+    vault_split_bytes, VaultContentType, VaultError, VaultSecretKey,
+    NUM_OF_SCRATCHPADS_PER_GRAPH_ENTRY, VAULT_HEAD_DERIVATION_INDEX,
 };
 use autonomi::{Bytes, Client, GraphEntry, PublicKey, Scratchpad, ScratchpadAddress};
 use tracing::info;
@@ -20,6 +21,8 @@ pub struct VaultQuoteResult {
 pub struct VaultUpdate {
     pub new_graph_entries: Vec<GraphEntry>,
     pub new_scratchpad_derivations: Vec<(PublicKey, [u8; 32])>,
+    /// Write of the vault's file metadata, paid for together with the user data
+    pub file_metadata: Option<Box<MetadataVaultUpdate>>,
 }
 
 pub async fn vault_quote(
@@ -143,6 +146,38 @@ pub async fn vault_update(
     new_graph_entries: Vec<GraphEntry>,
     new_scratchpad_derivations: Vec<(PublicKey, [u8; 32])>,
 ) -> Result<(), VaultError> {
+    let update = VaultUpdate {
+        new_graph_entries,
+        new_scratchpad_derivations,
+        file_metadata: None,
+    };
+
+    vault_write(
+        client,
+        data,
+        *USER_DATA_VAULT_CONTENT_IDENTIFIER,
+        secret_key,
+        receipt,
+        update,
+    )
+    .await
+}
+
+/// Write `data` to the vault of `secret_key`, creating the graph entries and scratchpads quoted
+/// by [`vault_quote`].
+pub async fn vault_write(
+    client: &Client,
+    data: Bytes,
+    content_type: VaultContentType,
+    secret_key: &VaultSecretKey,
+    receipt: Receipt,
+    update: VaultUpdate,
+) -> Result<(), VaultError> {
+    let VaultUpdate {
+        new_graph_entries,
+        new_scratchpad_derivations,
+        ..
+    } = update;
     let main_secret_key = MainSecretKey::new(secret_key.clone());
 
     // Get initial vault capacity
@@ -185,11 +220,7 @@ pub async fn vault_update(
             );
 
             client
-                .scratchpad_update(
-                    &sp_secret_key.clone().into(),
-                    *USER_DATA_VAULT_CONTENT_IDENTIFIER,
-                    &content,
-                )
+                .scratchpad_update(&sp_secret_key.clone().into(), content_type, &content)
                 .await?;
 
             info!(
@@ -201,12 +232,8 @@ pub async fn vault_update(
 
             let counter = 0;
 
-            let scratchpad = Scratchpad::new(
-                &sp_secret_key.into(),
-                *USER_DATA_VAULT_CONTENT_IDENTIFIER,
-                &content,
-                counter,
-            );
+            let scratchpad =
+                Scratchpad::new(&sp_secret_key.into(), content_type, &content, counter);
 
             info!(
                 "Looking for scratchpad xor name in receipt: {:?}",
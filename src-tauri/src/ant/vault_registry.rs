@@ -0,0 +1,432 @@
+//! Named vault identities.
+//!
+//! The identities themselves are recorded in [`AppData`](crate::ant::app_data::AppData), while
+//! their secret keys only ever live in memory and are wiped when an identity is locked or the app
+//! exits. The frontend never holds a key, it refers to an unlocked identity by a session handle.
+
+use autonomi::client::key_derivation::{DerivationIndex, MainSecretKey};
+use autonomi::client::vault::key::vault_key_from_signature_hex;
+use autonomi::client::vault::VaultSecretKey;
+use autonomi::XorName;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use thiserror::Error as ThisError;
+use tokio::sync::RwLock;
+use zeroize::Zeroizing;
+
+/// Id of the identity used when no identities have been configured.
+pub const DEFAULT_VAULT_ID: &str = "default";
+
+#[derive(ThisError, Debug)]
+pub enum VaultRegistryError {
+    #[error("Unknown vault identity: {0}")]
+    UnknownIdentity(String),
+    #[error("Vault identity is locked: {0}")]
+    Locked(String),
+    #[error("Invalid vault key: {0}")]
+    InvalidKey(String),
+    #[error("Vault identity {0} is derived from itself")]
+    DerivationCycle(String),
+    #[error("Vault identity {0} is still used by other identities")]
+    InUse(String),
+    #[error("Derived vault identities need a label")]
+    EmptyLabel,
+    #[error("Another identity is already derived with label {0}")]
+    LabelInUse(String),
+    #[error("Vault session has expired, please unlock the vault again")]
+    UnknownSession,
+}
+
+/// Where the secret key of a vault identity comes from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum VaultKeySource {
+    /// Derived from a signature made by the connected wallet.
+    WalletSignature,
+    /// Derived from the key of another identity, so unlocking that one unlocks this one too.
+    Derived { parent_id: String, label: String },
+    /// A secret key shared with the user, e.g. for a team vault. Supplied on every unlock and
+    /// never written to disk.
+    Imported,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VaultIdentity {
+    pub id: String,
+    pub name: String,
+    pub key_source: VaultKeySource,
+}
+
+impl VaultIdentity {
+    pub fn new(name: String, key_source: VaultKeySource) -> Self {
+        Self {
+            id: hex::encode(rand::random::<[u8; 8]>()),
+            name,
+            key_source,
+        }
+    }
+
+    pub fn default_wallet() -> Self {
+        Self {
+            id: DEFAULT_VAULT_ID.to_string(),
+            name: "Personal".to_string(),
+            key_source: VaultKeySource::WalletSignature,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VaultIdentityInfo {
+    #[serde(flatten)]
+    pub identity: VaultIdentity,
+    pub is_active: bool,
+    pub is_unlocked: bool,
+}
+
+/// Derive a key for data the app keeps alongside a vault, e.g. its file metadata.
+pub fn derive_vault_key(parent: &VaultSecretKey, label: &str) -> VaultSecretKey {
+    let index = DerivationIndex::from_bytes(XorName::from_content(label.as_bytes()).0);
+    MainSecretKey::new(parent.clone()).derive_key(&index).into()
+}
+
+/// Derive the key of a [`VaultKeySource::Derived`] identity from its parent key.
+///
+/// Labels are chosen by the user, so they get their own prefix to never match a key from
+/// [`derive_vault_key`].
+pub fn derive_identity_key(parent: &VaultSecretKey, label: &str) -> VaultSecretKey {
+    derive_vault_key(parent, &format!("identity/{label}"))
+}
+
+/// Check that a new identity can be added, a derived identity needs a known parent and a label
+/// not used by the parent's other derived identities, as those would share its vault.
+pub fn validate_key_source(
+    identities: &[VaultIdentity],
+    key_source: &VaultKeySource,
+) -> Result<(), VaultRegistryError> {
+    let VaultKeySource::Derived { parent_id, label } = key_source else {
+        return Ok(());
+    };
+
+    if !identities.iter().any(|identity| identity.id == *parent_id) {
+        return Err(VaultRegistryError::UnknownIdentity(parent_id.clone()));
+    }
+    if label.trim().is_empty() {
+        return Err(VaultRegistryError::EmptyLabel);
+    }
+
+    let in_use = identities.iter().any(|identity| {
+        matches!(
+            &identity.key_source,
+            VaultKeySource::Derived { parent_id: other_parent, label: other_label }
+                if other_parent == parent_id && other_label == label
+        )
+    });
+    if in_use {
+        return Err(VaultRegistryError::LabelInUse(label.clone()));
+    }
+
+    Ok(())
+}
+
+/// Parse the secret supplied when unlocking an identity.
+fn key_from_secret(
+    key_source: &VaultKeySource,
+    secret: &str,
+) -> Result<VaultSecretKey, VaultRegistryError> {
+    let secret = secret.trim().trim_start_matches("0x");
+
+    match key_source {
+        VaultKeySource::WalletSignature => vault_key_from_signature_hex(secret)
+            .map_err(|err| VaultRegistryError::InvalidKey(err.to_string())),
+        VaultKeySource::Imported => VaultSecretKey::from_hex(secret)
+            .map_err(|err| VaultRegistryError::InvalidKey(err.to_string())),
+        VaultKeySource::Derived { .. } => Err(VaultRegistryError::InvalidKey(
+            "derived identities are unlocked through their parent".to_string(),
+        )),
+    }
+}
+
+/// A vault secret key held by the session keyring.
+///
+/// Only the raw key bytes are kept, which are zeroized when the last copy is dropped. A
+/// [`VaultSecretKey`] is rebuilt for the duration of a single operation.
+#[derive(Clone)]
+pub struct SessionKey(Zeroizing<[u8; 32]>);
+
+impl SessionKey {
+    pub fn secret_key(&self) -> VaultSecretKey {
+        VaultSecretKey::from_bytes(*self.0).expect("session key bytes come from a valid key")
+    }
+}
+
+impl From<&VaultSecretKey> for SessionKey {
+    fn from(key: &VaultSecretKey) -> Self {
+        Self(Zeroizing::new(key.to_bytes()))
+    }
+}
+
+impl fmt::Debug for SessionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SessionKey(<redacted>)")
+    }
+}
+
+/// Session keyring: secret keys of the unlocked vault identities, and the session handles the
+/// frontend uses to refer to them.
+#[derive(Default)]
+pub struct VaultRegistry {
+    keys: RwLock<HashMap<String, SessionKey>>,
+    /// Session handle -> identity id.
+    sessions: RwLock<HashMap<String, String>>,
+}
+
+impl VaultRegistry {
+    pub async fn unlock(
+        &self,
+        identity: &VaultIdentity,
+        secret: &str,
+    ) -> Result<(), VaultRegistryError> {
+        let key = key_from_secret(&identity.key_source, secret)?;
+        self.keys
+            .write()
+            .await
+            .insert(identity.id.clone(), SessionKey::from(&key));
+
+        Ok(())
+    }
+
+    /// Forget the key of an identity and end its sessions.
+    pub async fn lock(&self, id: &str) {
+        self.keys.write().await.remove(id);
+        self.sessions
+            .write()
+            .await
+            .retain(|_handle, session_id| session_id != id);
+    }
+
+    /// Forget all keys and end all sessions, e.g. when the wallet disconnects or the app exits.
+    pub async fn lock_all(&self) {
+        self.keys.write().await.clear();
+        self.sessions.write().await.clear();
+    }
+
+    /// Hand out a session handle for an unlocked identity.
+    pub async fn open_session(
+        &self,
+        identities: &[VaultIdentity],
+        id: &str,
+    ) -> Result<String, VaultRegistryError> {
+        // Make sure the identity can actually be resolved before handing out a handle
+        self.secret_key(identities, id).await?;
+
+        let handle = hex::encode(rand::random::<[u8; 16]>());
+        self.sessions
+            .write()
+            .await
+            .insert(handle.clone(), id.to_string());
+
+        Ok(handle)
+    }
+
+    pub async fn close_session(&self, handle: &str) {
+        self.sessions.write().await.remove(handle);
+    }
+
+    /// Id of the identity a session handle refers to.
+    pub async fn session_identity(&self, handle: &str) -> Result<String, VaultRegistryError> {
+        self.sessions
+            .read()
+            .await
+            .get(handle)
+            .cloned()
+            .ok_or(VaultRegistryError::UnknownSession)
+    }
+
+    /// Resolve the secret key of the identity a session handle refers to.
+    pub async fn session_key(
+        &self,
+        identities: &[VaultIdentity],
+        handle: &str,
+    ) -> Result<VaultSecretKey, VaultRegistryError> {
+        let id = self.session_identity(handle).await?;
+        self.secret_key(identities, &id).await
+    }
+
+    pub async fn is_unlocked(&self, identities: &[VaultIdentity], id: &str) -> bool {
+        self.secret_key(identities, id).await.is_ok()
+    }
+
+    /// Resolve the secret key of an identity, following derived identities up to their root.
+    pub async fn secret_key(
+        &self,
+        identities: &[VaultIdentity],
+        id: &str,
+    ) -> Result<VaultSecretKey, VaultRegistryError> {
+        let keys = self.keys.read().await;
+
+        let mut labels = vec![];
+        let mut current = id.to_string();
+
+        let root_key = loop {
+            let identity = identities
+                .iter()
+                .find(|identity| identity.id == current)
+                .ok_or_else(|| VaultRegistryError::UnknownIdentity(current.clone()))?;
+
+            match &identity.key_source {
+                VaultKeySource::Derived { parent_id, label } => {
+                    if labels.len() > identities.len() {
+                        return Err(VaultRegistryError::DerivationCycle(id.to_string()));
+                    }
+                    labels.push(label.clone());
+                    current = parent_id.clone();
+                }
+                _ => {
+                    break keys
+                        .get(&identity.id)
+                        .map(SessionKey::secret_key)
+                        .ok_or_else(|| VaultRegistryError::Locked(identity.name.clone()))?;
+                }
+            }
+        };
+
+        Ok(labels
+            .iter()
+            .rev()
+            .fold(root_key, |key, label| derive_identity_key(&key, label)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn imported(name: &str) -> VaultIdentity {
+        VaultIdentity::new(name.to_string(), VaultKeySource::Imported)
+    }
+
+    fn derived(parent: &VaultIdentity, label: &str) -> VaultIdentity {
+        VaultIdentity::new(
+            label.to_string(),
+            VaultKeySource::Derived {
+                parent_id: parent.id.clone(),
+                label: label.to_string(),
+            },
+        )
+    }
+
+    #[test]
+    fn test_derived_keys() {
+        let registry = VaultRegistry::default();
+        let root = imported("Team");
+        let child = derived(&root, "file-metadata");
+        let grandchild = derived(&child, "projects");
+        let identities = vec![root.clone(), child.clone(), grandchild.clone()];
+        let root_key = VaultSecretKey::random();
+
+        tauri::async_runtime::block_on(async {
+            registry.unlock(&root, &root_key.to_hex()).await.unwrap();
+
+            let child_key = registry.secret_key(&identities, &child.id).await.unwrap();
+            assert_eq!(
+                child_key.to_bytes(),
+                derive_identity_key(&root_key, "file-metadata").to_bytes()
+            );
+            // A label can't select one of the app's own keys
+            assert_ne!(
+                child_key.to_bytes(),
+                derive_vault_key(&root_key, "file-metadata").to_bytes()
+            );
+
+            let grandchild_key = registry
+                .secret_key(&identities, &grandchild.id)
+                .await
+                .unwrap();
+            assert_eq!(
+                grandchild_key.to_bytes(),
+                derive_identity_key(&child_key, "projects").to_bytes()
+            );
+        });
+    }
+
+    #[test]
+    fn test_derivation_cycle() {
+        let registry = VaultRegistry::default();
+        let mut first = imported("First");
+        let second = derived(&first, "second");
+        first.key_source = VaultKeySource::Derived {
+            parent_id: second.id.clone(),
+            label: "first".to_string(),
+        };
+        let identities = vec![first.clone(), second];
+
+        let result = tauri::async_runtime::block_on(registry.secret_key(&identities, &first.id));
+        assert!(matches!(result, Err(VaultRegistryError::DerivationCycle(id)) if id == first.id));
+    }
+
+    #[test]
+    fn test_lock_and_unlock() {
+        let registry = VaultRegistry::default();
+        let root = imported("Team");
+        let child = derived(&root, "archive");
+        let identities = vec![root.clone(), child.clone()];
+
+        tauri::async_runtime::block_on(async {
+            assert!(matches!(
+                registry.open_session(&identities, &child.id).await,
+                Err(VaultRegistryError::Locked(_))
+            ));
+            assert!(matches!(
+                registry.unlock(&root, "not a key").await,
+                Err(VaultRegistryError::InvalidKey(_))
+            ));
+
+            let root_key = VaultSecretKey::random();
+            registry.unlock(&root, &root_key.to_hex()).await.unwrap();
+            let handle = registry.open_session(&identities, &child.id).await.unwrap();
+            assert!(registry.is_unlocked(&identities, &child.id).await);
+            assert!(registry.session_key(&identities, &handle).await.is_ok());
+
+            // Locking the parent locks what is derived from it, and ends the sessions of the
+            // parent itself
+            let root_handle = registry.open_session(&identities, &root.id).await.unwrap();
+            registry.lock(&root.id).await;
+            assert!(!registry.is_unlocked(&identities, &child.id).await);
+            assert!(matches!(
+                registry.session_key(&identities, &handle).await,
+                Err(VaultRegistryError::Locked(_))
+            ));
+            assert!(matches!(
+                registry.session_identity(&root_handle).await,
+                Err(VaultRegistryError::UnknownSession)
+            ));
+        });
+    }
+
+    #[test]
+    fn test_validate_key_source() {
+        let root = imported("Team");
+        let child = derived(&root, "projects");
+        let identities = vec![root.clone(), child];
+        let with_label = |parent_id: &str, label: &str| VaultKeySource::Derived {
+            parent_id: parent_id.to_string(),
+            label: label.to_string(),
+        };
+
+        assert!(validate_key_source(&identities, &VaultKeySource::Imported).is_ok());
+        assert!(validate_key_source(&identities, &with_label(&root.id, "photos")).is_ok());
+        assert!(matches!(
+            validate_key_source(&identities, &with_label("unknown", "photos")),
+            Err(VaultRegistryError::UnknownIdentity(_))
+        ));
+        assert!(matches!(
+            validate_key_source(&identities, &with_label(&root.id, " ")),
+            Err(VaultRegistryError::EmptyLabel)
+        ));
+        assert!(matches!(
+            validate_key_source(&identities, &with_label(&root.id, "projects")),
+            Err(VaultRegistryError::LabelInUse(_))
+        ));
+    }
+}
@@ -0,0 +1,719 @@
+//! Watched folders that are backed up automatically.
+//!
+//! A filesystem watcher marks a folder as changed, and a scheduler publishes a new version of the
+//! folder's private archive once the changes have settled and the backup interval has passed.
+//! The first backup goes through the regular archive upload pipeline, later ones are published as
+//! updates of the previous backup so only changed files are paid for. Backups are recorded in the
+//! vault the folder belongs to. The cost policy decides whether a quote is offered for payment or
+//! the backup is deferred, quotes that aren't paid in time are dropped again.
+
+use crate::ant::app_data;
+use crate::ant::archive_update;
+use crate::ant::client::SharedClient;
+use crate::ant::files::{self, File, FileAccess};
+use crate::ant::health::NetworkStatus;
+use crate::ant::vault_registry::VaultRegistry;
+use crate::{AppState, PendingUploadsState};
+use autonomi::Amount;
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter, Manager};
+use thiserror::Error as ThisError;
+use tokio::sync::Mutex;
+use tracing::{error, info, warn};
+
+const STATE_FILE: &str = "watched_folders.json";
+
+/// How often the scheduler looks for folders that are due for a backup.
+const TICK: Duration = Duration::from_secs(30);
+
+/// Changes are batched until a folder has been quiet for this long.
+const SETTLE_TIME: Duration = Duration::from_secs(60);
+
+/// Quotes of backups that aren't paid within this time are dropped.
+const PAYMENT_TIMEOUT: Duration = Duration::from_secs(15 * 60);
+
+pub const DEFAULT_INTERVAL_SECS: u64 = 60 * 60;
+
+#[derive(ThisError, Debug)]
+pub enum WatchedFolderError {
+    #[error("Could not determine the app data directory")]
+    NoDataDir,
+    #[error("Not a directory: {0:?}")]
+    NotADirectory(PathBuf),
+    #[error("Folder is already watched: {0:?}")]
+    AlreadyWatched(PathBuf),
+    #[error("Unknown watched folder: {0}")]
+    UnknownFolder(String),
+    #[error("Invalid maximum cost: {0}")]
+    InvalidCost(String),
+    #[error("Could not watch folder: {0}")]
+    Watch(#[from] notify::Error),
+    #[error("Could not store watched folders: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Could not serialize watched folders: {0}")]
+    Serialization(#[from] serde_json::Error),
+    #[error("Could not read folder: {0}")]
+    Read(#[from] files::UploadError),
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CostPolicy {
+    /// Highest cost of a single backup in atto, more expensive backups are deferred.
+    pub max_cost: Option<String>,
+}
+
+impl CostPolicy {
+    fn max_cost(&self) -> Result<Option<Amount>, WatchedFolderError> {
+        self.max_cost
+            .as_deref()
+            .map(|max_cost| {
+                Amount::from_str(max_cost)
+                    .map_err(|_| WatchedFolderError::InvalidCost(max_cost.to_string()))
+            })
+            .transpose()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "state")]
+pub enum BackupState {
+    Idle,
+    /// Waiting for the wallet to pay the quote of the backup.
+    AwaitingPayment {
+        upload_id: String,
+        cost: String,
+        /// When the quote was offered, in seconds since the epoch.
+        since: u64,
+    },
+    Uploading {
+        upload_id: String,
+    },
+    /// The backup is tried again at the next interval.
+    Deferred {
+        reason: String,
+    },
+    Failed {
+        error: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatchedFolder {
+    pub id: String,
+    pub path: PathBuf,
+    /// Vault identity the archive is recorded in.
+    pub vault_id: String,
+    pub interval_secs: u64,
+    pub cost_policy: CostPolicy,
+    pub last_attempt: Option<u64>,
+    pub last_backup: Option<u64>,
+    /// The last backup, the next one is published as an update of it.
+    #[serde(default)]
+    pub archive: Option<FileAccess>,
+    pub state: BackupState,
+}
+
+impl WatchedFolder {
+    fn name(&self) -> String {
+        self.path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| self.path.to_string_lossy().into_owned())
+    }
+
+    fn is_busy(&self) -> bool {
+        matches!(
+            self.state,
+            BackupState::AwaitingPayment { .. } | BackupState::Uploading { .. }
+        )
+    }
+
+    fn is_due(&self, now: u64) -> bool {
+        match self.last_attempt {
+            Some(last) => now.saturating_sub(last) >= self.interval_secs,
+            None => true,
+        }
+    }
+}
+
+/// Size and modification time of a file, to tell whether it changed since the last backup.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct FileStamp {
+    size: u64,
+    modified: u64,
+}
+
+/// Files of a folder by their path relative to the folder's parent, as they appear in the archive.
+type Snapshot = HashMap<PathBuf, FileStamp>;
+
+#[derive(Default, Serialize, Deserialize)]
+struct StoredState {
+    folders: Vec<WatchedFolder>,
+    /// Contents of each folder at its last successful backup.
+    snapshots: HashMap<String, Snapshot>,
+}
+
+#[derive(Default)]
+struct Inner {
+    stored: StoredState,
+    /// Upload id -> folder id and the snapshot the upload was made from.
+    pending: HashMap<String, (String, Snapshot)>,
+}
+
+pub struct WatchedFolders {
+    path: Option<PathBuf>,
+    inner: Mutex<Inner>,
+    /// Folder id -> time of the last change the watcher reported.
+    changes: Arc<std::sync::Mutex<HashMap<String, Instant>>>,
+    watchers: std::sync::Mutex<HashMap<String, RecommendedWatcher>>,
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+async fn snapshot(dir: &Path) -> Result<Snapshot, WatchedFolderError> {
+    let mut snapshot = Snapshot::new();
+
+    for (relative_path, absolute_path) in files::collect_files_from_directory(dir.into()).await? {
+        // Files can disappear while we look at them, they are picked up at the next change
+        let Ok(metadata) = tokio::fs::metadata(&absolute_path).await else {
+            continue;
+        };
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        snapshot.insert(
+            relative_path,
+            FileStamp {
+                size: metadata.len(),
+                modified,
+            },
+        );
+    }
+
+    Ok(snapshot)
+}
+
+/// Number of files that were added, changed or removed between two snapshots.
+fn changed_files(previous: &Snapshot, current: &Snapshot) -> usize {
+    let added_or_changed = current
+        .iter()
+        .filter(|(path, stamp)| previous.get(*path) != Some(*stamp))
+        .count();
+    let removed = previous
+        .keys()
+        .filter(|path| !current.contains_key(*path))
+        .count();
+
+    added_or_changed + removed
+}
+
+impl WatchedFolders {
+    /// Load the watched folders from the app data directory.
+    ///
+    /// Every folder starts out as changed, so changes made while the app was closed are picked up.
+    pub fn load() -> Self {
+        let path = app_data::data_dir().map(|dir| dir.join(STATE_FILE));
+
+        let mut stored: StoredState = path
+            .as_deref()
+            .and_then(app_data::load_json)
+            .unwrap_or_default();
+
+        // Quotes don't survive a restart
+        for folder in &mut stored.folders {
+            if folder.is_busy() {
+                folder.state = BackupState::Idle;
+                folder.last_attempt = None;
+            }
+        }
+
+        let settled = Instant::now()
+            .checked_sub(SETTLE_TIME)
+            .unwrap_or_else(Instant::now);
+        let changes = stored
+            .folders
+            .iter()
+            .map(|folder| (folder.id.clone(), settled))
+            .collect();
+
+        Self {
+            path,
+            inner: Mutex::new(Inner {
+                stored,
+                pending: HashMap::new(),
+            }),
+            changes: Arc::new(std::sync::Mutex::new(changes)),
+            watchers: std::sync::Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn save(&self, stored: &StoredState) -> Result<(), WatchedFolderError> {
+        let path = self.path.as_ref().ok_or(WatchedFolderError::NoDataDir)?;
+        app_data::save_json(path, stored)?;
+        Ok(())
+    }
+
+    fn mark_changed(&self, id: &str) {
+        if let Ok(mut changes) = self.changes.lock() {
+            changes.insert(id.to_string(), Instant::now());
+        }
+    }
+
+    /// Start watching a folder for changes.
+    fn watch(&self, folder: &WatchedFolder) -> Result<(), WatchedFolderError> {
+        let changes = self.changes.clone();
+        let id = folder.id.clone();
+
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<notify::Event>| match event {
+                Ok(event) if !matches!(event.kind, EventKind::Access(_)) => {
+                    if let Ok(mut changes) = changes.lock() {
+                        changes.insert(id.clone(), Instant::now());
+                    }
+                }
+                Ok(_) => {}
+                Err(err) => warn!("Watcher error: {err}"),
+            })?;
+        watcher.watch(&folder.path, RecursiveMode::Recursive)?;
+
+        if let Ok(mut watchers) = self.watchers.lock() {
+            watchers.insert(folder.id.clone(), watcher);
+        }
+
+        Ok(())
+    }
+
+    /// Start the watchers of all folders, e.g. when the app starts.
+    pub async fn watch_all(&self) {
+        for folder in self.list().await {
+            if let Err(err) = self.watch(&folder) {
+                error!("Failed to watch {:?}: {err}", folder.path);
+            }
+        }
+    }
+
+    pub async fn list(&self) -> Vec<WatchedFolder> {
+        self.inner.lock().await.stored.folders.clone()
+    }
+
+    pub async fn add(
+        &self,
+        path: PathBuf,
+        vault_id: String,
+        interval_secs: u64,
+        cost_policy: CostPolicy,
+    ) -> Result<WatchedFolder, WatchedFolderError> {
+        if !path.is_dir() {
+            return Err(WatchedFolderError::NotADirectory(path));
+        }
+        cost_policy.max_cost()?;
+
+        let mut inner = self.inner.lock().await;
+        if inner
+            .stored
+            .folders
+            .iter()
+            .any(|folder| folder.path == path)
+        {
+            return Err(WatchedFolderError::AlreadyWatched(path));
+        }
+
+        let folder = WatchedFolder {
+            id: hex::encode(rand::random::<[u8; 8]>()),
+            path,
+            vault_id,
+            interval_secs,
+            cost_policy,
+            last_attempt: None,
+            last_backup: None,
+            archive: None,
+            state: BackupState::Idle,
+        };
+        self.watch(&folder)?;
+
+        inner.stored.folders.push(folder.clone());
+        self.save(&inner.stored)?;
+        self.mark_changed(&folder.id);
+
+        info!("Watching {:?} for changes", folder.path);
+        Ok(folder)
+    }
+
+    pub async fn update(
+        &self,
+        id: &str,
+        interval_secs: u64,
+        cost_policy: CostPolicy,
+    ) -> Result<WatchedFolder, WatchedFolderError> {
+        cost_policy.max_cost()?;
+
+        self.modify(id, |folder| {
+            folder.interval_secs = interval_secs;
+            folder.cost_policy = cost_policy;
+        })
+        .await
+    }
+
+    pub async fn remove(&self, id: &str) -> Result<(), WatchedFolderError> {
+        let mut inner = self.inner.lock().await;
+
+        let len = inner.stored.folders.len();
+        inner.stored.folders.retain(|folder| folder.id != id);
+        if inner.stored.folders.len() == len {
+            return Err(WatchedFolderError::UnknownFolder(id.to_string()));
+        }
+        inner.stored.snapshots.remove(id);
+        inner
+            .pending
+            .retain(|_upload_id, (folder_id, _)| folder_id != id);
+
+        // Dropping the watcher stops it
+        if let Ok(mut watchers) = self.watchers.lock() {
+            watchers.remove(id);
+        }
+        if let Ok(mut changes) = self.changes.lock() {
+            changes.remove(id);
+        }
+
+        self.save(&inner.stored)
+    }
+
+    async fn modify(
+        &self,
+        id: &str,
+        f: impl FnOnce(&mut WatchedFolder),
+    ) -> Result<WatchedFolder, WatchedFolderError> {
+        let mut inner = self.inner.lock().await;
+
+        let folder = inner
+            .stored
+            .folders
+            .iter_mut()
+            .find(|folder| folder.id == id)
+            .ok_or_else(|| WatchedFolderError::UnknownFolder(id.to_string()))?;
+        f(folder);
+        let folder = folder.clone();
+
+        self.save(&inner.stored)?;
+        Ok(folder)
+    }
+
+    /// Folders with changes that have settled and whose backup interval has passed.
+    async fn due(&self) -> Vec<String> {
+        let now = now_secs();
+        let changes = match self.changes.lock() {
+            Ok(changes) => changes.clone(),
+            Err(_) => return vec![],
+        };
+
+        self.list()
+            .await
+            .into_iter()
+            .filter(|folder| !folder.is_busy() && folder.is_due(now))
+            .filter(|folder| {
+                changes
+                    .get(&folder.id)
+                    .is_some_and(|changed| changed.elapsed() >= SETTLE_TIME)
+            })
+            .map(|folder| folder.id)
+            .collect()
+    }
+
+    /// Record the outcome of a backup upload, returns the folder it belonged to.
+    async fn upload_finished(
+        &self,
+        upload_id: &str,
+        outcome: Result<Option<FileAccess>, String>,
+    ) -> Option<WatchedFolder> {
+        let mut inner = self.inner.lock().await;
+        let (folder_id, snapshot) = inner.pending.remove(upload_id)?;
+
+        let folder = inner
+            .stored
+            .folders
+            .iter_mut()
+            .find(|folder| folder.id == folder_id)?;
+        match outcome {
+            Ok(archive) => {
+                folder.state = BackupState::Idle;
+                folder.last_backup = Some(now_secs());
+                folder.archive = archive.or(folder.archive.take());
+            }
+            Err(error) => folder.state = BackupState::Failed { error },
+        }
+        let folder = folder.clone();
+
+        if folder.state == BackupState::Idle {
+            inner.stored.snapshots.insert(folder_id, snapshot);
+        } else {
+            // Try again at the next interval
+            self.mark_changed(&folder.id);
+        }
+
+        if let Err(err) = self.save(&inner.stored) {
+            error!("Failed to store watched folders: {err}");
+        }
+        Some(folder)
+    }
+}
+
+fn emit_folder(app: &AppHandle, folder: &WatchedFolder) {
+    if let Err(err) = app.emit("watched-folder", folder) {
+        warn!("Failed to emit watched folder update: {err}");
+    }
+}
+
+async fn set_state(app: &AppHandle, id: &str, state: BackupState) {
+    let watched_folders = app.state::<WatchedFolders>();
+    match watched_folders
+        .modify(id, |folder| folder.state = state)
+        .await
+    {
+        Ok(folder) => emit_folder(app, &folder),
+        Err(err) => error!("Failed to update watched folder {id}: {err}"),
+    }
+}
+
+/// Defer a backup to the next interval.
+async fn defer(app: &AppHandle, id: &str, reason: String) {
+    info!("Deferring backup of watched folder {id}: {reason}");
+    app.state::<WatchedFolders>().mark_changed(id);
+    set_state(app, id, BackupState::Deferred { reason }).await;
+}
+
+/// Publish a new version of the folder's private archive if anything changed since the last backup.
+pub async fn backup(app: &AppHandle, id: &str) -> Result<(), WatchedFolderError> {
+    let watched_folders = app.state::<WatchedFolders>();
+    let folder = watched_folders
+        .modify(id, |folder| {
+            if !folder.is_busy() {
+                folder.last_attempt = Some(now_secs());
+            }
+        })
+        .await?;
+    // Keep the changes seen during a running backup for the next one
+    if folder.is_busy() {
+        return Ok(());
+    }
+
+    // Cleared before the snapshot, so changes made while it is taken trigger another backup
+    if let Ok(mut changes) = watched_folders.changes.lock() {
+        changes.remove(id);
+    }
+
+    let current = snapshot(&folder.path).await?;
+    let changed = {
+        let inner = watched_folders.inner.lock().await;
+        inner
+            .stored
+            .snapshots
+            .get(id)
+            .map_or(current.len().max(1), |previous| {
+                changed_files(previous, &current)
+            })
+    };
+    if changed == 0 {
+        set_state(app, id, BackupState::Idle).await;
+        return Ok(());
+    }
+
+    if app.state::<SharedClient>().status().await == NetworkStatus::Offline {
+        defer(app, id, "The network is unreachable".to_string()).await;
+        return Ok(());
+    }
+
+    let identities = app
+        .state::<AppState>()
+        .lock()
+        .await
+        .app_data
+        .vault_identities();
+    let Ok(secret_key) = app
+        .state::<VaultRegistry>()
+        .secret_key(&identities, &folder.vault_id)
+        .await
+    else {
+        defer(app, id, "The vault is locked".to_string()).await;
+        return Ok(());
+    };
+
+    info!(
+        "Backing up watched folder {:?}, {changed} files changed",
+        folder.path
+    );
+
+    let upload_id = format!("backup-{}", hex::encode(rand::random::<[u8; 8]>()));
+    watched_folders
+        .inner
+        .lock()
+        .await
+        .pending
+        .insert(upload_id.clone(), (id.to_string(), current));
+    set_state(
+        app,
+        id,
+        BackupState::Uploading {
+            upload_id: upload_id.clone(),
+        },
+    )
+    .await;
+
+    let pending_uploads = app.state::<PendingUploadsState>();
+    let result = match folder.archive.clone() {
+        Some(previous_archive) => {
+            archive_update::start_archive_update(
+                app.clone(),
+                previous_archive,
+                folder.path.clone(),
+                folder.name(),
+                upload_id.clone(),
+                true,
+                Some(&secret_key),
+                app.state(),
+                Some(&*pending_uploads),
+            )
+            .await
+        }
+        None => {
+            files::start_private_archive_upload(
+                app.clone(),
+                vec![File {
+                    name: folder.name(),
+                    path: folder.path.clone(),
+                }],
+                folder.name(),
+                upload_id.clone(),
+                true,
+                true,
+                Some(&secret_key),
+                app.state(),
+                Some(&*pending_uploads),
+            )
+            .await
+        }
+    };
+
+    if let Err(err) = result {
+        if let Some(folder) = watched_folders
+            .upload_finished(&upload_id, Err(err.to_string()))
+            .await
+        {
+            emit_folder(app, &folder);
+        }
+        return Err(err.into());
+    }
+
+    // Without a pending quote the upload was free or paid with a cached receipt, and is already
+    // running. Its `upload-progress` events finish the backup.
+    let Some(cost) = pending_uploads.lock().await.quoted_cost(&upload_id) else {
+        return Ok(());
+    };
+
+    match folder.cost_policy.max_cost()? {
+        Some(max_cost) if cost > max_cost => {
+            pending_uploads.lock().await.take(&upload_id);
+            watched_folders
+                .inner
+                .lock()
+                .await
+                .pending
+                .remove(&upload_id);
+            defer(
+                app,
+                id,
+                format!("The backup costs {cost} ATTO, more than the maximum of {max_cost} ATTO"),
+            )
+            .await;
+        }
+        _ => {
+            set_state(
+                app,
+                id,
+                BackupState::AwaitingPayment {
+                    upload_id,
+                    cost: cost.to_string(),
+                    since: now_secs(),
+                },
+            )
+            .await;
+        }
+    }
+
+    Ok(())
+}
+
+/// Follow the `upload-progress` events of backup uploads.
+pub async fn handle_upload_progress(app: AppHandle, payload: String) {
+    let Some((upload_id, outcome)) = files::finished_upload(&payload) else {
+        return;
+    };
+
+    let watched_folders = app.state::<WatchedFolders>();
+    if let Some(folder) = watched_folders.upload_finished(&upload_id, outcome).await {
+        info!("Backup of watched folder {:?} finished", folder.path);
+        emit_folder(&app, &folder);
+    }
+}
+
+/// Drop the quotes of backups that weren't paid in time, the backups are tried again at the next
+/// interval. Quotes that were paid in the meantime are left alone, their upload is running.
+async fn expire_unpaid(app: &AppHandle) {
+    let watched_folders = app.state::<WatchedFolders>();
+    let now = now_secs();
+
+    for folder in watched_folders.list().await {
+        let BackupState::AwaitingPayment {
+            upload_id, since, ..
+        } = &folder.state
+        else {
+            continue;
+        };
+        if now.saturating_sub(*since) < PAYMENT_TIMEOUT.as_secs() {
+            continue;
+        }
+
+        let pending_uploads = app.state::<PendingUploadsState>();
+        if pending_uploads.lock().await.take(upload_id).is_none() {
+            continue;
+        }
+        watched_folders.inner.lock().await.pending.remove(upload_id);
+        defer(
+            app,
+            &folder.id,
+            "The backup was not paid in time".to_string(),
+        )
+        .await;
+    }
+}
+
+/// Watch all folders and back them up on their schedule.
+pub async fn run(app: AppHandle) {
+    let watched_folders = app.state::<WatchedFolders>();
+    watched_folders.watch_all().await;
+
+    loop {
+        tokio::time::sleep(TICK).await;
+
+        expire_unpaid(&app).await;
+        for id in watched_folders.due().await {
+            if let Err(err) = backup(&app, &id).await {
+                error!("Backup of watched folder {id} failed: {err}");
+            }
+        }
+    }
+}
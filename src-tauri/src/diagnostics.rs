@@ -0,0 +1,119 @@
+//! Diagnostics bundle to attach to bug reports.
+//!
+//! The bundle is a zip file with the app version, the settings, the network configuration and
+//! the logs of the most recent runs. Everything passes through [`logging::redact`] first, as
+//! logs of older versions were written without redaction.
+
+use crate::ant::app_data::AppData;
+use crate::logging::{self, redact};
+use chrono::Local;
+use std::fs;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use thiserror::Error as ThisError;
+use tracing::info;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+/// Number of most recent runs whose logs are included.
+const MAX_LOG_RUNS: usize = 5;
+
+/// Only the tail of larger log files is included.
+const MAX_LOG_BYTES: u64 = 10 * 1024 * 1024;
+
+#[derive(ThisError, Debug)]
+pub enum DiagnosticsError {
+    #[error("No download folder configured to save the diagnostics to")]
+    NoDestination,
+    #[error("Could not write diagnostics: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Could not write diagnostics archive: {0}")]
+    Zip(#[from] zip::result::ZipError),
+    #[error("Could not serialize settings: {0}")]
+    Settings(#[from] toml::ser::Error),
+}
+
+/// Default location of the bundle, in the download folder.
+pub fn default_destination(app_data: &AppData) -> Result<PathBuf, DiagnosticsError> {
+    let download_path = app_data
+        .download_path
+        .as_ref()
+        .ok_or(DiagnosticsError::NoDestination)?;
+    let timestamp = Local::now().format("%Y-%m-%d_%H-%M-%S");
+
+    Ok(download_path.join(format!("dave-diagnostics-{timestamp}.zip")))
+}
+
+/// Read a log file, keeping at most the last [`MAX_LOG_BYTES`], and redact it line by line.
+fn read_redacted_log(path: &Path) -> Result<String, std::io::Error> {
+    let mut file = fs::File::open(path)?;
+    let len = file.metadata()?.len();
+    if len > MAX_LOG_BYTES {
+        file.seek(SeekFrom::Start(len - MAX_LOG_BYTES))?;
+    }
+
+    let mut contents = Vec::new();
+    file.read_to_end(&mut contents)?;
+
+    let mut redacted = String::with_capacity(contents.len());
+    for line in String::from_utf8_lossy(&contents).lines() {
+        redacted.push_str(&redact(line));
+        redacted.push('\n');
+    }
+
+    Ok(redacted)
+}
+
+pub fn export_diagnostics(app_data: &AppData, destination: &Path) -> Result<(), DiagnosticsError> {
+    if let Some(parent) = destination.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut zip = ZipWriter::new(fs::File::create(destination)?);
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    zip.start_file("version.txt", options)?;
+    writeln!(zip, "version: {}", env!("CARGO_PKG_VERSION"))?;
+    writeln!(
+        zip,
+        "os: {} ({})",
+        std::env::consts::OS,
+        std::env::consts::ARCH
+    )?;
+    writeln!(zip, "exported: {}", Local::now().to_rfc3339())?;
+
+    zip.start_file("settings.toml", options)?;
+    zip.write_all(redact(&toml::to_string(app_data)?).as_bytes())?;
+
+    zip.start_file("network.json", options)?;
+    let network = serde_json::to_string_pretty(&app_data.network_config())
+        .expect("network config is valid json");
+    zip.write_all(redact(&network).as_bytes())?;
+
+    let log_dirs = logging::logs_dir()
+        .map(|logs_dir| logging::log_dirs(&logs_dir))
+        .unwrap_or_default();
+
+    for log_dir in log_dirs.into_iter().take(MAX_LOG_RUNS) {
+        let log_file = log_dir.join(logging::LOG_FILE_NAME);
+        let Some(dir_name) = log_dir.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+
+        match read_redacted_log(&log_file) {
+            Ok(contents) => {
+                zip.start_file(
+                    format!("logs/{dir_name}/{}", logging::LOG_FILE_NAME),
+                    options,
+                )?;
+                zip.write_all(contents.as_bytes())?;
+            }
+            Err(err) => info!("Skipping log file {}: {err}", log_file.display()),
+        }
+    }
+
+    zip.finish()?;
+    info!("Exported diagnostics to: {}", destination.display());
+
+    Ok(())
+}
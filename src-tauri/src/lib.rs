@@ -1,12 +1,29 @@
 use std::path::PathBuf;
 
+use crate::ant::archive_update::ArchiveUpdate;
 use crate::ant::client::SharedClient;
+use crate::ant::download_queue::{DownloadQueue, DownloadQueueError, QueuedDownload};
 use crate::ant::files::{File, FileAccess};
+use crate::ant::health::NetworkStatus;
+use crate::ant::import::{ImportError, ReferenceKind, ReferencePreview};
+use crate::ant::local_index::EntryDetails;
+use crate::ant::offline_queue::{
+    OfflineQueue, OfflineQueueError, QueuedOperation, QueuedOperationKind, QueuedOperationStatus,
+};
 use crate::ant::payments::{OrderID, OrderMessage, PaymentOrderManager};
+use crate::ant::preview::{PreviewCache, PREVIEW_SCHEME};
+use crate::ant::private_share::{
+    PendingShare, ReceivedShare, ReceivedShares, Share, ShareError, ShareRegistry,
+};
+use crate::ant::share_link::{OpenedShareLink, ReceivedShareLinks, ShareLink, ShareLinkError};
 use crate::ant::vault::VaultUpdate;
+use crate::ant::vault_registry::{
+    SessionKey, VaultIdentity, VaultIdentityInfo, VaultKeySource, VaultRegistry, VaultRegistryError,
+};
+use crate::ant::watched_folders::{CostPolicy, WatchedFolder, WatchedFolderError, WatchedFolders};
 use ant::{
     app_data::AppData,
-    files::{FileFromVault, VaultStructure},
+    files::{FileFromVault, VaultEntryRef, VaultStructure},
     local_storage::LocalFileData,
 };
 use autonomi::chunk::DataMapChunk;
@@ -15,14 +32,17 @@ use autonomi::client::payment::Receipt;
 use autonomi::client::quote::StoreQuote;
 use autonomi::client::vault::VaultSecretKey;
 use autonomi::files::{PrivateArchive, PublicArchive};
+use autonomi::Amount;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 // Removed unused rand import
-use tauri::{AppHandle, State};
+use tauri::{AppHandle, Emitter, Listener, Manager, State};
+use tauri_plugin_deep_link::DeepLinkExt;
 use tokio::sync::Mutex;
 use tracing::{error, info};
 
 mod ant;
+mod diagnostics;
 pub mod logging;
 
 pub enum PendingUploadData {
@@ -31,7 +51,7 @@ pub enum PendingUploadData {
         datamap: DataMapChunk,
         store_quote: StoreQuote,
         vault_update: VaultUpdate,
-        secret_key: Option<VaultSecretKey>,
+        secret_key: Option<SessionKey>,
         add_to_vault: bool,
         cached_receipt: Option<Receipt>,
     },
@@ -41,7 +61,7 @@ pub enum PendingUploadData {
         store_quote: StoreQuote,
         vault_update: VaultUpdate,
         add_to_vault: bool,
-        vault_secret_key: Option<VaultSecretKey>,
+        vault_secret_key: Option<SessionKey>,
         cached_receipt: Option<Receipt>,
     },
     PrivateArchive {
@@ -52,7 +72,7 @@ pub enum PendingUploadData {
         store_quote: StoreQuote,
         vault_update: VaultUpdate,
         add_to_vault: bool,
-        vault_secret_key: Option<VaultSecretKey>,
+        vault_secret_key: Option<SessionKey>,
         cached_receipt: Option<Receipt>,
     },
     PublicArchive {
@@ -62,9 +82,20 @@ pub enum PendingUploadData {
         store_quote: StoreQuote,
         vault_update: VaultUpdate,
         add_to_vault: bool,
-        vault_secret_key: Option<VaultSecretKey>,
+        vault_secret_key: Option<SessionKey>,
         cached_receipt: Option<Receipt>,
     },
+    ArchiveUpdate {
+        update: ArchiveUpdate,
+        store_quote: StoreQuote,
+        vault_update: VaultUpdate,
+        add_to_vault: bool,
+        vault_secret_key: Option<SessionKey>,
+    },
+    Share {
+        share: PendingShare,
+        store_quote: StoreQuote,
+    },
 }
 
 #[derive(Default)]
@@ -80,7 +111,7 @@ impl PendingUploads {
         datamap: DataMapChunk,
         store_quote: StoreQuote,
         vault_update: VaultUpdate,
-        secret_key: Option<VaultSecretKey>,
+        secret_key: Option<SessionKey>,
         add_to_vault: bool,
         cached_receipt: Option<Receipt>,
     ) {
@@ -106,7 +137,7 @@ impl PendingUploads {
         store_quote: StoreQuote,
         vault_update: VaultUpdate,
         add_to_vault: bool,
-        vault_secret_key: Option<VaultSecretKey>,
+        vault_secret_key: Option<SessionKey>,
         cached_receipt: Option<Receipt>,
     ) {
         self.uploads.insert(
@@ -132,7 +163,7 @@ impl PendingUploads {
         store_quote: StoreQuote,
         vault_update: VaultUpdate,
         add_to_vault: bool,
-        vault_secret_key: Option<VaultSecretKey>,
+        vault_secret_key: Option<SessionKey>,
         cached_receipt: Option<Receipt>,
     ) {
         self.uploads.insert(
@@ -160,7 +191,7 @@ impl PendingUploads {
         store_quote: StoreQuote,
         vault_update: VaultUpdate,
         add_to_vault: bool,
-        vault_secret_key: Option<VaultSecretKey>,
+        vault_secret_key: Option<SessionKey>,
         cached_receipt: Option<Receipt>,
     ) {
         self.uploads.insert(
@@ -179,9 +210,55 @@ impl PendingUploads {
         );
     }
 
+    pub fn store_archive_update(
+        &mut self,
+        upload_id: String,
+        update: ArchiveUpdate,
+        store_quote: StoreQuote,
+        vault_update: VaultUpdate,
+        add_to_vault: bool,
+        vault_secret_key: Option<SessionKey>,
+    ) {
+        self.uploads.insert(
+            upload_id,
+            PendingUploadData::ArchiveUpdate {
+                update,
+                store_quote,
+                vault_update,
+                add_to_vault,
+                vault_secret_key,
+            },
+        );
+    }
+
+    pub fn store_share(&mut self, upload_id: String, share: PendingShare, store_quote: StoreQuote) {
+        self.uploads
+            .insert(upload_id, PendingUploadData::Share { share, store_quote });
+    }
+
     pub fn take(&mut self, upload_id: &str) -> Option<PendingUploadData> {
         self.uploads.remove(upload_id)
     }
+
+    /// Total cost of the quote an upload is waiting to be paid for.
+    pub fn quoted_cost(&self, upload_id: &str) -> Option<Amount> {
+        let store_quote = match self.uploads.get(upload_id)? {
+            PendingUploadData::SingleFile { store_quote, .. }
+            | PendingUploadData::SingleFilePublic { store_quote, .. }
+            | PendingUploadData::PrivateArchive { store_quote, .. }
+            | PendingUploadData::PublicArchive { store_quote, .. }
+            | PendingUploadData::ArchiveUpdate { store_quote, .. }
+            | PendingUploadData::Share { store_quote, .. } => store_quote,
+        };
+
+        Some(
+            store_quote
+                .payments()
+                .iter()
+                .map(|(_, _, amount)| *amount)
+                .sum(),
+        )
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -218,42 +295,456 @@ async fn app_data_store(state: State<'_, AppState>, app_data: AppData) -> Result
     let mut state = state.lock().await;
 
     info!("updating app data: {app_data:?}");
+    ant::ignore_rules::set_global_patterns(app_data.ignore_patterns.clone());
+    ant::folder_scan::set_symlink_policy(app_data.symlink_policy);
+    ant::chunk_cache::set_max_size_mb(app_data.chunk_cache_max_mb);
     state.app_data = app_data;
     state.app_data.store().map_err(|_err| ()) // TODO: Map to serializable error
 }
 
+impl From<VaultRegistryError> for CommandError {
+    fn from(err: VaultRegistryError) -> Self {
+        CommandError {
+            message: err.to_string(),
+        }
+    }
+}
+
+/// Resolve the secret key of the vault identity a session handle refers to.
+async fn resolve_vault_key(
+    state: &AppState,
+    vault_registry: &VaultRegistry,
+    vault_handle: &str,
+) -> Result<VaultSecretKey, CommandError> {
+    let identities = state.lock().await.app_data.vault_identities();
+
+    Ok(vault_registry
+        .session_key(&identities, vault_handle)
+        .await?)
+}
+
+/// Unlock the identities keyed by the wallet signature and open a session for the active vault.
+#[tauri::command]
+async fn unlock_vault(
+    app: AppHandle,
+    vault_key_signature: String,
+    state: State<'_, AppState>,
+    vault_registry: State<'_, VaultRegistry>,
+) -> Result<String, CommandError> {
+    let (identities, active_id) = {
+        let state = state.lock().await;
+        (
+            state.app_data.vault_identities(),
+            state.app_data.active_vault_id(),
+        )
+    };
+
+    for identity in identities
+        .iter()
+        .filter(|identity| identity.key_source == VaultKeySource::WalletSignature)
+    {
+        vault_registry
+            .unlock(identity, &vault_key_signature)
+            .await?;
+    }
+
+    // Queued vault operations may have been waiting for the vault
+    tauri::async_runtime::spawn(process_offline_queue(app));
+
+    Ok(vault_registry.open_session(&identities, &active_id).await?)
+}
+
+#[tauri::command]
+async fn lock_vault(vault_registry: State<'_, VaultRegistry>) -> Result<(), ()> {
+    vault_registry.lock_all().await;
+    Ok(())
+}
+
+impl From<OfflineQueueError> for CommandError {
+    fn from(err: OfflineQueueError) -> Self {
+        CommandError {
+            message: err.to_string(),
+        }
+    }
+}
+
+fn emit_queued_operation(app: &AppHandle, operation: &QueuedOperation) {
+    if let Err(err) = app.emit("queued-operation", operation) {
+        error!("Failed to emit queued operation: {err}");
+    }
+}
+
+/// Queue an operation instead of running it if the network is unreachable, returns whether it was
+/// queued.
+async fn queue_if_offline(
+    app: &AppHandle,
+    shared_client: &SharedClient,
+    offline_queue: &OfflineQueue,
+    operation: impl FnOnce() -> QueuedOperationKind,
+) -> Result<bool, CommandError> {
+    match shared_client.get_client().await {
+        Ok(_client) => return Ok(false),
+        Err(err) if err.is_offline() => info!("Network unreachable, queueing operation: {err}"),
+        Err(err) => {
+            return Err(CommandError {
+                message: err.to_string(),
+            })
+        }
+    }
+
+    let operation = offline_queue.push(operation()).await?;
+    emit_queued_operation(app, &operation);
+
+    Ok(true)
+}
+
+async fn run_queued_operation(
+    app: &AppHandle,
+    kind: QueuedOperationKind,
+) -> Result<(), CommandError> {
+    let vault_key = match kind.vault_id() {
+        Some(vault_id) => {
+            let identities = app
+                .state::<AppState>()
+                .lock()
+                .await
+                .app_data
+                .vault_identities();
+            Some(
+                app.state::<VaultRegistry>()
+                    .secret_key(&identities, vault_id)
+                    .await?,
+            )
+        }
+        None => None,
+    };
+
+    let vault_result = match (kind, vault_key.as_ref()) {
+        (
+            QueuedOperationKind::Upload {
+                upload_id,
+                files,
+                archive_name,
+                is_private,
+                add_to_vault,
+                use_cached_receipts,
+                ..
+            },
+            vault_key,
+        ) => {
+            return dispatch_upload(
+                app.clone(),
+                files,
+                archive_name,
+                vault_key,
+                upload_id,
+                is_private,
+                add_to_vault,
+                use_cached_receipts,
+                app.state(),
+                app.state(),
+            )
+            .await;
+        }
+        (
+            QueuedOperationKind::AddFileToVault {
+                file_access,
+                file_name,
+                ..
+            },
+            Some(secret_key),
+        ) => {
+            ant::files::add_local_file_to_vault(secret_key, file_access, &file_name, app.state())
+                .await
+        }
+        (
+            QueuedOperationKind::AddArchiveToVault {
+                archive_access,
+                archive_name,
+                ..
+            },
+            Some(secret_key),
+        ) => {
+            ant::files::add_local_archive_to_vault(
+                secret_key,
+                archive_access,
+                &archive_name,
+                app.state(),
+            )
+            .await
+        }
+        (
+            QueuedOperationKind::RemoveFromVault {
+                file_path,
+                archive_address,
+                ..
+            },
+            Some(secret_key),
+        ) => {
+            ant::files::remove_from_vault(secret_key, &file_path, archive_address, app.state())
+                .await
+        }
+        (_, None) => {
+            return Err(CommandError {
+                message: "The queued vault operation doesn't name a vault".to_string(),
+            })
+        }
+    };
+
+    vault_result.map_err(|err| CommandError {
+        message: err.to_string(),
+    })
+}
+
+/// Run the pending operations of the offline queue in order.
+///
+/// Operations for a vault that is locked are left pending until it is unlocked. Processing stops
+/// when the network becomes unreachable again.
+async fn process_offline_queue(app: AppHandle) {
+    let offline_queue = app.state::<OfflineQueue>();
+    let Some(_processing) = offline_queue.try_start_processing() else {
+        return;
+    };
+    let shared_client = app.state::<SharedClient>();
+    let vault_registry = app.state::<VaultRegistry>();
+
+    for operation in offline_queue.pending().await {
+        if shared_client.current_client().await.is_none() {
+            info!("Network unreachable, pausing the offline queue");
+            return;
+        }
+
+        if let Some(vault_id) = operation.kind.vault_id() {
+            let identities = app
+                .state::<AppState>()
+                .lock()
+                .await
+                .app_data
+                .vault_identities();
+            if !vault_registry.is_unlocked(&identities, vault_id).await {
+                continue;
+            }
+        }
+
+        let set_status = |status: QueuedOperationStatus, error: Option<String>| {
+            let offline_queue = &offline_queue;
+            let app = &app;
+            let id = operation.id.clone();
+            async move {
+                match offline_queue.set_status(&id, status, error).await {
+                    Ok(operation) => emit_queued_operation(app, &operation),
+                    Err(err) => error!("Failed to update queued operation {id}: {err}"),
+                }
+            }
+        };
+
+        info!("Running queued operation {}", operation.id);
+        set_status(QueuedOperationStatus::Running, None).await;
+
+        match run_queued_operation(&app, operation.kind.clone()).await {
+            // Uploads only got their quote, see `finish_queued_upload`
+            Ok(()) if matches!(operation.kind, QueuedOperationKind::Upload { .. }) => {}
+            Ok(()) => set_status(QueuedOperationStatus::Completed, None).await,
+            // Lost the connection halfway, try again once it is back
+            Err(err) if shared_client.current_client().await.is_none() => {
+                set_status(QueuedOperationStatus::Pending, Some(err.message)).await;
+                return;
+            }
+            Err(err) => {
+                error!("Queued operation {} failed: {}", operation.id, err.message);
+                set_status(QueuedOperationStatus::Failed, Some(err.message)).await;
+            }
+        }
+    }
+}
+
+/// Complete or fail a queued upload once the upload itself finished.
+async fn finish_queued_upload(app: AppHandle, payload: String) {
+    let Some((upload_id, outcome)) = ant::files::finished_upload(&payload) else {
+        return;
+    };
+
+    let offline_queue = app.state::<OfflineQueue>();
+    if let Some(operation) = offline_queue
+        .upload_finished(&upload_id, outcome.err())
+        .await
+    {
+        emit_queued_operation(&app, &operation);
+    }
+}
+
+#[tauri::command]
+async fn list_queued_operations(
+    offline_queue: State<'_, OfflineQueue>,
+) -> Result<Vec<QueuedOperation>, ()> {
+    Ok(offline_queue.list().await)
+}
+
+#[tauri::command]
+async fn remove_queued_operation(
+    id: String,
+    offline_queue: State<'_, OfflineQueue>,
+) -> Result<(), CommandError> {
+    Ok(offline_queue.remove(&id).await?)
+}
+
+/// Put a failed operation back in the queue and try to run it.
+#[tauri::command]
+async fn retry_queued_operation(
+    app: AppHandle,
+    id: String,
+    offline_queue: State<'_, OfflineQueue>,
+) -> Result<(), CommandError> {
+    let operation = offline_queue
+        .set_status(&id, QueuedOperationStatus::Pending, None)
+        .await?;
+    emit_queued_operation(&app, &operation);
+
+    tauri::async_runtime::spawn(process_offline_queue(app));
+    Ok(())
+}
+
+impl From<WatchedFolderError> for CommandError {
+    fn from(err: WatchedFolderError) -> Self {
+        CommandError {
+            message: err.to_string(),
+        }
+    }
+}
+
+#[tauri::command]
+async fn list_watched_folders(
+    watched_folders: State<'_, WatchedFolders>,
+) -> Result<Vec<WatchedFolder>, ()> {
+    Ok(watched_folders.list().await)
+}
+
+/// Watch a folder and back it up to the vault of the session on a schedule.
+#[tauri::command]
+async fn add_watched_folder(
+    path: PathBuf,
+    vault_handle: String,
+    interval_secs: Option<u64>,
+    cost_policy: Option<CostPolicy>,
+    vault_registry: State<'_, VaultRegistry>,
+    watched_folders: State<'_, WatchedFolders>,
+) -> Result<WatchedFolder, CommandError> {
+    let vault_id = vault_registry.session_identity(&vault_handle).await?;
+
+    Ok(watched_folders
+        .add(
+            path,
+            vault_id,
+            interval_secs.unwrap_or(ant::watched_folders::DEFAULT_INTERVAL_SECS),
+            cost_policy.unwrap_or_default(),
+        )
+        .await?)
+}
+
+#[tauri::command]
+async fn update_watched_folder(
+    id: String,
+    interval_secs: u64,
+    cost_policy: CostPolicy,
+    watched_folders: State<'_, WatchedFolders>,
+) -> Result<WatchedFolder, CommandError> {
+    Ok(watched_folders
+        .update(&id, interval_secs, cost_policy)
+        .await?)
+}
+
+#[tauri::command]
+async fn remove_watched_folder(
+    id: String,
+    watched_folders: State<'_, WatchedFolders>,
+) -> Result<(), CommandError> {
+    Ok(watched_folders.remove(&id).await?)
+}
+
+/// Back up a watched folder now instead of waiting for its schedule.
+#[tauri::command]
+async fn backup_watched_folder(app: AppHandle, id: String) -> Result<(), CommandError> {
+    Ok(ant::watched_folders::backup(&app, &id).await?)
+}
+
 #[tauri::command]
 async fn start_upload(
     app: AppHandle,
     files: Vec<File>,
     archive_name: Option<String>,
-    vault_key_signature: String,
+    vault_handle: Option<String>,
     upload_id: String,         // Frontend provides the upload ID
     is_private: bool,          // New: privacy option
     add_to_vault: bool,        // New: vault storage option
     use_cached_receipts: bool, // New: whether to use cached receipts
+    state: State<'_, AppState>,
+    vault_registry: State<'_, VaultRegistry>,
     shared_client: State<'_, SharedClient>,
     pending_uploads: State<'_, PendingUploadsState>,
-) -> Result<(), CommandError> {
+    offline_queue: State<'_, OfflineQueue>,
+) -> Result<bool, CommandError> {
     // No need to return ID since frontend already has it
 
     // Determine vault secret key based on options
     // Parse vault key if:
     // 1. Private upload (always needs key for encryption)
     // 2. Public upload with add_to_vault=true
-    let vault_secret_key = if !vault_key_signature.is_empty() && add_to_vault {
-        Some(
-            autonomi::client::vault::key::vault_key_from_signature_hex(
-                vault_key_signature.trim_start_matches("0x"),
-            )
-            .map_err(|e| CommandError {
-                message: e.to_string(),
-            })?,
-        )
-    } else {
-        None
+    let (vault_id, vault_secret_key) = match vault_handle {
+        Some(vault_handle) if add_to_vault => (
+            Some(vault_registry.session_identity(&vault_handle).await?),
+            Some(resolve_vault_key(&state, &vault_registry, &vault_handle).await?),
+        ),
+        _ => (None, None),
     };
 
+    // Keep the selection when offline, the upload starts once the network is reachable
+    let queued = queue_if_offline(&app, &shared_client, &offline_queue, || {
+        QueuedOperationKind::Upload {
+            upload_id: upload_id.clone(),
+            files: files.clone(),
+            archive_name: archive_name.clone(),
+            is_private,
+            add_to_vault,
+            use_cached_receipts,
+            vault_id,
+        }
+    })
+    .await?;
+    if queued {
+        return Ok(true);
+    }
+
+    dispatch_upload(
+        app,
+        files,
+        archive_name,
+        vault_secret_key.as_ref(),
+        upload_id,
+        is_private,
+        add_to_vault,
+        use_cached_receipts,
+        shared_client,
+        pending_uploads,
+    )
+    .await?;
+
+    Ok(false)
+}
+
+/// Start the quote step of an upload, picking the pipeline for single files or archives.
+#[allow(clippy::too_many_arguments)]
+async fn dispatch_upload(
+    app: AppHandle,
+    files: Vec<File>,
+    archive_name: Option<String>,
+    vault_secret_key: Option<&VaultSecretKey>,
+    upload_id: String,
+    is_private: bool,
+    add_to_vault: bool,
+    use_cached_receipts: bool,
+    shared_client: State<'_, SharedClient>,
+    pending_uploads: State<'_, PendingUploadsState>,
+) -> Result<(), CommandError> {
     // Check if this is a single file upload (not a directory)
     let is_single_file = files.len() == 1 && {
         use std::fs;
@@ -268,7 +759,7 @@ async fn start_upload(
             ant::files::start_private_single_file_upload(
                 app,
                 files.into_iter().next().unwrap(),
-                vault_secret_key.as_ref(),
+                vault_secret_key,
                 upload_id.clone(),
                 add_to_vault,
                 use_cached_receipts,
@@ -287,7 +778,7 @@ async fn start_upload(
                 upload_id.clone(),
                 add_to_vault,
                 use_cached_receipts,
-                vault_secret_key.as_ref(),
+                vault_secret_key,
                 shared_client,
                 Some(&*pending_uploads),
             )
@@ -309,7 +800,7 @@ async fn start_upload(
                 upload_id.clone(),
                 add_to_vault,
                 use_cached_receipts,
-                vault_secret_key.as_ref(),
+                vault_secret_key,
                 shared_client,
                 Some(&*pending_uploads),
             )
@@ -326,7 +817,7 @@ async fn start_upload(
                 upload_id.clone(),
                 add_to_vault,
                 use_cached_receipts,
-                vault_secret_key.as_ref(),
+                vault_secret_key,
                 shared_client,
                 Some(&*pending_uploads),
             )
@@ -340,12 +831,72 @@ async fn start_upload(
     Ok(())
 }
 
+/// Publish a new version of an archive from a local folder, paying only for changed files.
+#[tauri::command]
+async fn start_archive_update(
+    app: AppHandle,
+    previous_archive: FileAccess,
+    folder: PathBuf,
+    archive_name: String,
+    upload_id: String,
+    add_to_vault: bool,
+    vault_handle: Option<String>,
+    state: State<'_, AppState>,
+    vault_registry: State<'_, VaultRegistry>,
+    shared_client: State<'_, SharedClient>,
+    pending_uploads: State<'_, PendingUploadsState>,
+) -> Result<(), CommandError> {
+    let vault_secret_key = match vault_handle {
+        Some(vault_handle) if add_to_vault => {
+            Some(resolve_vault_key(&state, &vault_registry, &vault_handle).await?)
+        }
+        _ => None,
+    };
+
+    ant::archive_update::start_archive_update(
+        app,
+        previous_archive,
+        folder,
+        archive_name,
+        upload_id,
+        add_to_vault,
+        vault_secret_key.as_ref(),
+        shared_client,
+        Some(&*pending_uploads),
+    )
+    .await
+    .map_err(|err| CommandError {
+        message: err.to_string(),
+    })
+}
+
+/// Earlier versions of an archive that was published as an update, newest first.
+#[tauri::command]
+async fn get_archive_versions(
+    archive: FileAccess,
+    shared_client: State<'_, SharedClient>,
+) -> Result<Vec<FileAccess>, CommandError> {
+    let client = shared_client
+        .get_client()
+        .await
+        .map_err(|err| CommandError {
+            message: err.to_string(),
+        })?;
+
+    ant::archive_update::archive_versions(&client, archive)
+        .await
+        .map_err(|err| CommandError {
+            message: err.to_string(),
+        })
+}
+
 #[tauri::command]
 async fn confirm_upload_payment(
     app: AppHandle,
     upload_id: String,
     shared_client: State<'_, SharedClient>,
     pending_uploads: State<'_, PendingUploadsState>,
+    shares: State<'_, ShareRegistry>,
 ) -> Result<(), CommandError> {
     let mut pending = pending_uploads.lock().await;
 
@@ -389,7 +940,7 @@ async fn confirm_upload_payment(
                     datamap,
                     final_receipt,
                     vault_update,
-                    secret_key.as_ref(),
+                    secret_key.map(|key| key.secret_key()).as_ref(),
                     upload_id,
                     add_to_vault,
                     shared_client,
@@ -413,7 +964,7 @@ async fn confirm_upload_payment(
 
                 // Merge with cached receipt if we have one
                 let final_receipt = if let Some(cached) = cached_receipt {
-                    println!(">>> Merging new receipt with cached receipt");
+                    info!(">>> Merging new receipt with cached receipt");
                     ant::receipt_utils::merge_receipts(vec![cached, new_receipt])
                 } else {
                     new_receipt
@@ -422,9 +973,9 @@ async fn confirm_upload_payment(
                 // Cache the merged payment receipt
                 if let Ok(cache) = ant::files::get_payment_cache() {
                     if let Err(e) = cache.save_payment(&file.path, &final_receipt) {
-                        println!(">>> Failed to cache payment receipt: {}", e);
+                        error!(">>> Failed to cache payment receipt: {}", e);
                     } else {
-                        println!(
+                        info!(
                             ">>> Successfully cached merged payment receipt for file: {:?}",
                             file.path
                         );
@@ -439,7 +990,7 @@ async fn confirm_upload_payment(
                     vault_update,
                     upload_id,
                     add_to_vault,
-                    vault_secret_key.as_ref(),
+                    vault_secret_key.map(|key| key.secret_key()).as_ref(),
                     shared_client,
                 )
                 .await
@@ -462,7 +1013,7 @@ async fn confirm_upload_payment(
 
                 // Merge with cached receipt if we have one
                 let final_receipt = if let Some(cached) = cached_receipt {
-                    println!(">>> Merging new receipt with cached receipt for archive");
+                    info!(">>> Merging new receipt with cached receipt for archive");
                     ant::receipt_utils::merge_receipts(vec![cached, new_receipt])
                 } else {
                     new_receipt
@@ -473,9 +1024,9 @@ async fn confirm_upload_payment(
                     if let Err(e) =
                         cache.save_archive_payment(&files, &archive_name, &final_receipt)
                     {
-                        println!(">>> Failed to cache archive payment receipt: {}", e);
+                        error!(">>> Failed to cache archive payment receipt: {}", e);
                     } else {
-                        println!(
+                        info!(
                             ">>> Successfully cached merged archive payment receipt for: {}",
                             archive_name
                         );
@@ -491,7 +1042,31 @@ async fn confirm_upload_payment(
                     vault_update,
                     upload_id,
                     add_to_vault,
-                    vault_secret_key.as_ref(),
+                    vault_secret_key.map(|key| key.secret_key()).as_ref(),
+                    shared_client,
+                )
+                .await
+                .map_err(|e| CommandError {
+                    message: e.to_string(),
+                })?;
+            }
+            PendingUploadData::ArchiveUpdate {
+                update,
+                store_quote,
+                vault_update,
+                add_to_vault,
+                vault_secret_key,
+            } => {
+                let receipt = autonomi::client::payment::receipt_from_store_quotes(store_quote);
+
+                ant::archive_update::execute_archive_update(
+                    app,
+                    update,
+                    receipt,
+                    vault_update,
+                    upload_id,
+                    add_to_vault,
+                    vault_secret_key.map(|key| key.secret_key()).as_ref(),
                     shared_client,
                 )
                 .await
@@ -499,6 +1074,19 @@ async fn confirm_upload_payment(
                     message: e.to_string(),
                 })?;
             }
+            PendingUploadData::Share { share, store_quote } => {
+                let receipt = autonomi::client::payment::receipt_from_store_quotes(store_quote);
+
+                ant::private_share::execute_share(
+                    app,
+                    share,
+                    receipt,
+                    upload_id,
+                    shared_client,
+                    shares,
+                )
+                .await?;
+            }
             PendingUploadData::PrivateArchive {
                 files,
                 archive_name,
@@ -515,7 +1103,7 @@ async fn confirm_upload_payment(
 
                 // Merge with cached receipt if we have one
                 let final_receipt = if let Some(cached) = cached_receipt {
-                    println!(">>> Merging new receipt with cached receipt for archive");
+                    info!(">>> Merging new receipt with cached receipt for archive");
                     ant::receipt_utils::merge_receipts(vec![cached, new_receipt])
                 } else {
                     new_receipt
@@ -526,9 +1114,9 @@ async fn confirm_upload_payment(
                     if let Err(e) =
                         cache.save_archive_payment(&files, &archive_name, &final_receipt)
                     {
-                        println!(">>> Failed to cache archive payment receipt: {}", e);
+                        error!(">>> Failed to cache archive payment receipt: {}", e);
                     } else {
-                        println!(
+                        info!(
                             ">>> Successfully cached merged archive payment receipt for: {}",
                             archive_name
                         );
@@ -545,7 +1133,7 @@ async fn confirm_upload_payment(
                     vault_update,
                     upload_id,
                     add_to_vault,
-                    vault_secret_key.as_ref(),
+                    vault_secret_key.map(|key| key.secret_key()).as_ref(),
                     shared_client,
                 )
                 .await
@@ -578,13 +1166,14 @@ async fn send_payment_order_message(
 
 #[tauri::command]
 async fn get_vault_structure(
-    vault_key_signature: String,
+    vault_handle: String,
+    state: State<'_, AppState>,
+    vault_registry: State<'_, VaultRegistry>,
     shared_client: State<'_, SharedClient>,
 ) -> Result<VaultStructure, ()> {
-    let secret_key = autonomi::client::vault::key::vault_key_from_signature_hex(
-        vault_key_signature.trim_start_matches("0x"),
-    )
-    .expect("Invalid vault key signature");
+    let secret_key = resolve_vault_key(&state, &vault_registry, &vault_handle)
+        .await
+        .map_err(|_err| ())?;
 
     ant::files::get_vault_structure(&secret_key, shared_client)
         .await
@@ -594,14 +1183,15 @@ async fn get_vault_structure(
 #[tauri::command]
 async fn get_vault_structure_streaming(
     app: AppHandle,
-    vault_key_signature: String,
+    vault_handle: String,
     temp_code: String,
+    state: State<'_, AppState>,
+    vault_registry: State<'_, VaultRegistry>,
     shared_client: State<'_, SharedClient>,
 ) -> Result<(), ()> {
-    let secret_key = autonomi::client::vault::key::vault_key_from_signature_hex(
-        vault_key_signature.trim_start_matches("0x"),
-    )
-    .expect("Invalid vault key signature");
+    let secret_key = resolve_vault_key(&state, &vault_registry, &vault_handle)
+        .await
+        .map_err(|_err| ())?;
 
     ant::files::get_vault_structure_streaming(app, &secret_key, temp_code, shared_client)
         .await
@@ -610,13 +1200,14 @@ async fn get_vault_structure_streaming(
 
 #[tauri::command]
 async fn get_files_from_vault(
-    vault_key_signature: String,
+    vault_handle: String,
+    state: State<'_, AppState>,
+    vault_registry: State<'_, VaultRegistry>,
     shared_client: State<'_, SharedClient>,
 ) -> Result<Vec<FileFromVault>, ()> {
-    let secret_key = autonomi::client::vault::key::vault_key_from_signature_hex(
-        vault_key_signature.trim_start_matches("0x"),
-    )
-    .expect("Invalid vault key signature");
+    let secret_key = resolve_vault_key(&state, &vault_registry, &vault_handle)
+        .await
+        .map_err(|_err| ())?;
 
     ant::files::get_files_from_vault(&secret_key, shared_client)
         .await
@@ -625,15 +1216,29 @@ async fn get_files_from_vault(
 
 #[tauri::command]
 async fn remove_from_vault(
-    vault_key_signature: String,
+    vault_handle: String,
     file_path: String,
     archive_address: Option<String>,
+    app: AppHandle,
+    state: State<'_, AppState>,
+    vault_registry: State<'_, VaultRegistry>,
     shared_client: State<'_, SharedClient>,
+    offline_queue: State<'_, OfflineQueue>,
 ) -> Result<(), CommandError> {
-    let secret_key = autonomi::client::vault::key::vault_key_from_signature_hex(
-        vault_key_signature.trim_start_matches("0x"),
-    )
-    .expect("Invalid vault key signature");
+    let secret_key = resolve_vault_key(&state, &vault_registry, &vault_handle).await?;
+
+    let vault_id = vault_registry.session_identity(&vault_handle).await?;
+    let queued = queue_if_offline(&app, &shared_client, &offline_queue, || {
+        QueuedOperationKind::RemoveFromVault {
+            vault_id,
+            file_path: file_path.clone(),
+            archive_address: archive_address.clone(),
+        }
+    })
+    .await?;
+    if queued {
+        return Ok(());
+    }
 
     ant::files::remove_from_vault(&secret_key, &file_path, archive_address, shared_client)
         .await
@@ -644,30 +1249,33 @@ async fn remove_from_vault(
 
 #[tauri::command]
 async fn add_local_archive_to_vault(
-    vault_key_signature: String,
+    vault_handle: String,
     archive_access: FileAccess,
     archive_name: String,
+    app: AppHandle,
+    state: State<'_, AppState>,
+    vault_registry: State<'_, VaultRegistry>,
     shared_client: State<'_, SharedClient>,
+    offline_queue: State<'_, OfflineQueue>,
 ) -> Result<(), CommandError> {
-    eprintln!("=== add_local_archive_to_vault COMMAND ===");
-    eprintln!("vault_key_signature: {}", vault_key_signature);
-    eprintln!("archive_access: {:?}", archive_access);
-    eprintln!("archive_name: {}", archive_name);
+    info!("add_local_archive_to_vault: {archive_name}");
 
-    let secret_key = match autonomi::client::vault::key::vault_key_from_signature_hex(
-        vault_key_signature.trim_start_matches("0x"),
-    ) {
-        Ok(key) => {
-            eprintln!("Successfully parsed vault key");
-            key
-        }
-        Err(e) => {
-            eprintln!("Failed to parse vault key: {:?}", e);
-            return Err(CommandError {
-                message: format!("Invalid vault key signature: {:?}", e),
-            });
+    let secret_key = resolve_vault_key(&state, &vault_registry, &vault_handle)
+        .await
+        .inspect_err(|err| error!("Failed to resolve vault session: {}", err.message))?;
+
+    let vault_id = vault_registry.session_identity(&vault_handle).await?;
+    let queued = queue_if_offline(&app, &shared_client, &offline_queue, || {
+        QueuedOperationKind::AddArchiveToVault {
+            vault_id,
+            archive_access: archive_access.clone(),
+            archive_name: archive_name.clone(),
         }
-    };
+    })
+    .await?;
+    if queued {
+        return Ok(());
+    }
 
     ant::files::add_local_archive_to_vault(
         &secret_key,
@@ -677,7 +1285,7 @@ async fn add_local_archive_to_vault(
     )
     .await
     .map_err(|err| {
-        eprintln!("add_local_archive_to_vault failed with error: {:?}", err);
+        error!("add_local_archive_to_vault failed: {err}");
         CommandError {
             message: err.to_string(),
         }
@@ -686,35 +1294,38 @@ async fn add_local_archive_to_vault(
 
 #[tauri::command]
 async fn add_local_file_to_vault(
-    vault_key_signature: String,
+    vault_handle: String,
     file_access: FileAccess,
     file_name: String,
+    app: AppHandle,
+    state: State<'_, AppState>,
+    vault_registry: State<'_, VaultRegistry>,
     shared_client: State<'_, SharedClient>,
+    offline_queue: State<'_, OfflineQueue>,
 ) -> Result<(), CommandError> {
-    eprintln!("=== add_local_file_to_vault COMMAND ===");
-    eprintln!("vault_key_signature: {}", vault_key_signature);
-    eprintln!("file_access: {:?}", file_access);
-    eprintln!("file_name: {}", file_name);
+    info!("add_local_file_to_vault: {file_name}");
 
-    let secret_key = match autonomi::client::vault::key::vault_key_from_signature_hex(
-        vault_key_signature.trim_start_matches("0x"),
-    ) {
-        Ok(key) => {
-            eprintln!("Successfully parsed vault key");
-            key
-        }
-        Err(e) => {
-            eprintln!("Failed to parse vault key: {:?}", e);
-            return Err(CommandError {
-                message: format!("Invalid vault key signature: {:?}", e),
-            });
+    let secret_key = resolve_vault_key(&state, &vault_registry, &vault_handle)
+        .await
+        .inspect_err(|err| error!("Failed to resolve vault session: {}", err.message))?;
+
+    let vault_id = vault_registry.session_identity(&vault_handle).await?;
+    let queued = queue_if_offline(&app, &shared_client, &offline_queue, || {
+        QueuedOperationKind::AddFileToVault {
+            vault_id,
+            file_access: file_access.clone(),
+            file_name: file_name.clone(),
         }
-    };
+    })
+    .await?;
+    if queued {
+        return Ok(());
+    }
 
     ant::files::add_local_file_to_vault(&secret_key, file_access, &file_name, shared_client)
         .await
         .map_err(|err| {
-            eprintln!("add_local_file_to_vault failed with error: {:?}", err);
+            error!("add_local_file_to_vault failed: {err}");
             CommandError {
                 message: err.to_string(),
             }
@@ -723,30 +1334,18 @@ async fn add_local_file_to_vault(
 
 #[tauri::command]
 async fn add_to_vault_with_analysis(
-    vault_key_signature: String,
+    vault_handle: String,
     file_access: FileAccess,
     file_name: String,
+    state: State<'_, AppState>,
+    vault_registry: State<'_, VaultRegistry>,
     shared_client: State<'_, SharedClient>,
 ) -> Result<(), CommandError> {
-    eprintln!("=== add_to_vault_with_analysis COMMAND ===");
-    eprintln!("vault_key_signature: {}", vault_key_signature);
-    eprintln!("file_access: {:?}", file_access);
-    eprintln!("file_name: {}", file_name);
+    info!("add_to_vault_with_analysis: {file_name}");
 
-    let secret_key = match autonomi::client::vault::key::vault_key_from_signature_hex(
-        vault_key_signature.trim_start_matches("0x"),
-    ) {
-        Ok(key) => {
-            eprintln!("Successfully parsed vault key");
-            key
-        }
-        Err(e) => {
-            eprintln!("Failed to parse vault key: {:?}", e);
-            return Err(CommandError {
-                message: format!("Invalid vault key signature: {:?}", e),
-            });
-        }
-    };
+    let secret_key = resolve_vault_key(&state, &vault_registry, &vault_handle)
+        .await
+        .inspect_err(|err| error!("Failed to resolve vault session: {}", err.message))?;
 
     let client = shared_client
         .get_client()
@@ -762,7 +1361,7 @@ async fn add_to_vault_with_analysis(
 
             match client.analyze_address(&hex_addr, false).await {
                 Ok(autonomi::client::analyze::Analysis::PublicArchive { .. }) => {
-                    eprintln!("Detected public archive, adding as archive");
+                    info!("Detected public archive, adding as archive");
                     ant::files::add_local_archive_to_vault(
                         &secret_key,
                         FileAccess::Public(*addr),
@@ -772,7 +1371,7 @@ async fn add_to_vault_with_analysis(
                     .await
                 }
                 _ => {
-                    eprintln!("Detected public file, adding as file");
+                    info!("Detected public file, adding as file");
                     ant::files::add_local_file_to_vault(
                         &secret_key,
                         file_access,
@@ -788,7 +1387,7 @@ async fn add_to_vault_with_analysis(
 
             match client.analyze_address(&hex_addr, true).await {
                 Ok(autonomi::client::analyze::Analysis::PrivateArchive { .. }) => {
-                    eprintln!("Detected private archive, adding as archive");
+                    info!("Detected private archive, adding as archive");
                     ant::files::add_local_archive_to_vault(
                         &secret_key,
                         FileAccess::Private(data_map.clone()),
@@ -798,7 +1397,7 @@ async fn add_to_vault_with_analysis(
                     .await
                 }
                 _ => {
-                    eprintln!("Detected private file, adding as file");
+                    info!("Detected private file, adding as file");
                     ant::files::add_local_file_to_vault(
                         &secret_key,
                         file_access,
@@ -812,50 +1411,592 @@ async fn add_to_vault_with_analysis(
     };
 
     result.map_err(|err| {
-        eprintln!("add_to_vault_with_analysis failed with error: {:?}", err);
+        error!("add_to_vault_with_analysis failed: {err}");
         CommandError {
             message: err.to_string(),
         }
     })
 }
 
+impl From<ImportError> for CommandError {
+    fn from(err: ImportError) -> Self {
+        CommandError {
+            message: err.to_string(),
+        }
+    }
+}
+
+/// Validate a shared address or datamap and list what it contains, without storing it.
+#[tauri::command]
+async fn preview_reference(
+    reference: String,
+    shared_client: State<'_, SharedClient>,
+) -> Result<ReferencePreview, CommandError> {
+    Ok(ant::import::preview_reference(&reference, &shared_client).await?)
+}
+
+/// Store a shared address or datamap under `name`, in the vault or in local storage.
+#[tauri::command]
+async fn import_reference(
+    reference: String,
+    name: String,
+    add_to_vault: bool,
+    vault_handle: Option<String>,
+    state: State<'_, AppState>,
+    vault_registry: State<'_, VaultRegistry>,
+    shared_client: State<'_, SharedClient>,
+) -> Result<ReferencePreview, CommandError> {
+    let secret_key = match (add_to_vault, vault_handle) {
+        (true, Some(vault_handle)) => {
+            Some(resolve_vault_key(&state, &vault_registry, &vault_handle).await?)
+        }
+        (true, None) => {
+            return Err(CommandError {
+                message: "No vault selected to import into".to_string(),
+            })
+        }
+        (false, _) => None,
+    };
+
+    Ok(
+        ant::import::import_reference(&reference, &name, secret_key.as_ref(), shared_client)
+            .await?,
+    )
+}
+
+impl From<ShareLinkError> for CommandError {
+    fn from(err: ShareLinkError) -> Self {
+        CommandError {
+            message: err.to_string(),
+        }
+    }
+}
+
+/// Build an `autonomi://` link to share a file or archive.
+#[tauri::command]
+fn create_share_link(
+    file_access: FileAccess,
+    name: Option<String>,
+    size: Option<u64>,
+    kind: Option<ReferenceKind>,
+) -> Result<String, CommandError> {
+    Ok(ant::share_link::encode(&ShareLink {
+        file_access,
+        name,
+        size,
+        kind,
+    })?)
+}
+
+#[tauri::command]
+fn parse_share_link(link: String) -> Result<ShareLink, CommandError> {
+    Ok(ant::share_link::decode(&link)?)
+}
+
+/// Open a received link, as a preview or, with a `download_dir`, as a queued download.
+#[tauri::command]
+async fn open_share_link(
+    link: String,
+    download_dir: Option<PathBuf>,
+    app: AppHandle,
+) -> Result<OpenedShareLink, CommandError> {
+    Ok(ant::share_link::open(&app, &link, download_dir).await?)
+}
+
+/// Links the app was opened with since the last call.
+#[tauri::command]
+async fn take_share_links(
+    received: State<'_, ReceivedShareLinks>,
+) -> Result<Vec<ShareLink>, CommandError> {
+    Ok(received.take().await)
+}
+
+impl From<ShareError> for CommandError {
+    fn from(err: ShareError) -> Self {
+        CommandError {
+            message: err.to_string(),
+        }
+    }
+}
+
+/// The public key others use to share files with a vault.
+#[tauri::command]
+async fn get_sharing_public_key(
+    vault_handle: String,
+    state: State<'_, AppState>,
+    vault_registry: State<'_, VaultRegistry>,
+) -> Result<String, CommandError> {
+    let secret_key = resolve_vault_key(&state, &vault_registry, &vault_handle).await?;
+    Ok(ant::private_share::sharing_public_key(&secret_key))
+}
+
+/// Quote sharing a file or archive with the owner of `recipient`, paid like an upload.
+#[tauri::command]
+async fn start_share(
+    app: AppHandle,
+    file_access: FileAccess,
+    name: String,
+    recipient: String,
+    upload_id: String,
+    vault_handle: String,
+    state: State<'_, AppState>,
+    vault_registry: State<'_, VaultRegistry>,
+    shared_client: State<'_, SharedClient>,
+    pending_uploads: State<'_, PendingUploadsState>,
+) -> Result<(), CommandError> {
+    let secret_key = resolve_vault_key(&state, &vault_registry, &vault_handle).await?;
+    Ok(ant::private_share::start_share(
+        app,
+        file_access,
+        name,
+        &recipient,
+        &secret_key,
+        upload_id,
+        shared_client,
+        &pending_uploads,
+    )
+    .await?)
+}
+
+/// Quote moving a share onto a new version, paid like an upload.
+#[tauri::command]
+async fn update_share(
+    app: AppHandle,
+    id: String,
+    file_access: FileAccess,
+    name: String,
+    upload_id: String,
+    vault_handle: String,
+    state: State<'_, AppState>,
+    vault_registry: State<'_, VaultRegistry>,
+    shared_client: State<'_, SharedClient>,
+    shares: State<'_, ShareRegistry>,
+    pending_uploads: State<'_, PendingUploadsState>,
+) -> Result<(), CommandError> {
+    let secret_key = resolve_vault_key(&state, &vault_registry, &vault_handle).await?;
+    Ok(ant::private_share::update_share(
+        app,
+        &id,
+        file_access,
+        name,
+        &secret_key,
+        upload_id,
+        shared_client,
+        shares,
+        &pending_uploads,
+    )
+    .await?)
+}
+
+#[tauri::command]
+async fn list_shares(shares: State<'_, ShareRegistry>) -> Result<Vec<Share>, CommandError> {
+    Ok(shares.list().await)
+}
+
+#[tauri::command]
+async fn revoke_share(
+    id: String,
+    vault_handle: String,
+    state: State<'_, AppState>,
+    vault_registry: State<'_, VaultRegistry>,
+    shared_client: State<'_, SharedClient>,
+    shares: State<'_, ShareRegistry>,
+) -> Result<Share, CommandError> {
+    let secret_key = resolve_vault_key(&state, &vault_registry, &vault_handle).await?;
+    Ok(ant::private_share::revoke_share(&id, &secret_key, shared_client, shares).await?)
+}
+
+/// Add a share addressed to a vault to the shares that vault received.
+#[tauri::command]
+async fn receive_share(
+    address: String,
+    name: Option<String>,
+    vault_handle: String,
+    state: State<'_, AppState>,
+    vault_registry: State<'_, VaultRegistry>,
+    shared_client: State<'_, SharedClient>,
+    received: State<'_, ReceivedShares>,
+) -> Result<ReferencePreview, CommandError> {
+    let secret_key = resolve_vault_key(&state, &vault_registry, &vault_handle).await?;
+    Ok(
+        ant::private_share::receive_share(&address, name, &secret_key, shared_client, received)
+            .await?,
+    )
+}
+
+#[tauri::command]
+async fn list_received_shares(
+    vault_handle: String,
+    state: State<'_, AppState>,
+    vault_registry: State<'_, VaultRegistry>,
+    received: State<'_, ReceivedShares>,
+) -> Result<Vec<ReceivedShare>, CommandError> {
+    let secret_key = resolve_vault_key(&state, &vault_registry, &vault_handle).await?;
+    Ok(received.list(&secret_key).await)
+}
+
+/// Resolve a received share to what it currently points to.
+#[tauri::command]
+async fn open_received_share(
+    address: String,
+    vault_handle: String,
+    state: State<'_, AppState>,
+    vault_registry: State<'_, VaultRegistry>,
+    shared_client: State<'_, SharedClient>,
+) -> Result<ReferencePreview, CommandError> {
+    let secret_key = resolve_vault_key(&state, &vault_registry, &vault_handle).await?;
+    Ok(ant::private_share::open_received_share(&address, &secret_key, shared_client).await?)
+}
+
+#[tauri::command]
+async fn remove_received_share(
+    address: String,
+    received: State<'_, ReceivedShares>,
+) -> Result<(), CommandError> {
+    Ok(received.remove(&address).await?)
+}
+
 #[tauri::command]
 async fn download_private_file(
     data_map_chunk: DataMapChunk,
     to_dest: PathBuf,
+    restore_metadata: Option<bool>,
     shared_client: State<'_, SharedClient>,
 ) -> Result<(), CommandError> {
-    ant::files::download_private(&data_map_chunk, to_dest, shared_client)
-        .await
-        .map_err(|err| CommandError {
-            message: err.to_string(),
-        })
+    ant::files::download_private(
+        &data_map_chunk,
+        to_dest,
+        restore_metadata.unwrap_or(false),
+        &Default::default(),
+        shared_client,
+    )
+    .await
+    .map_err(|err| CommandError {
+        message: err.to_string(),
+    })
 }
 
 #[tauri::command]
 async fn download_public_file(
     addr: DataAddress,
     to_dest: PathBuf,
+    restore_metadata: Option<bool>,
     shared_client: State<'_, SharedClient>,
 ) -> Result<(), CommandError> {
-    ant::files::download_public(&addr, to_dest, shared_client)
-        .await
-        .map_err(|err| CommandError {
+    ant::files::download_public(
+        &addr,
+        to_dest,
+        restore_metadata.unwrap_or(false),
+        &Default::default(),
+        shared_client,
+    )
+    .await
+    .map_err(|err| CommandError {
+        message: err.to_string(),
+    })
+}
+
+#[tauri::command]
+async fn download_archive_entries(
+    archive: FileAccess,
+    paths: Vec<String>,
+    to_dest: PathBuf,
+    restore_metadata: Option<bool>,
+    shared_client: State<'_, SharedClient>,
+) -> Result<usize, CommandError> {
+    ant::files::download_archive_entries(
+        &archive,
+        &paths,
+        to_dest,
+        restore_metadata.unwrap_or(false),
+        shared_client,
+    )
+    .await
+    .map_err(|err| CommandError {
+        message: err.to_string(),
+    })
+}
+
+impl From<DownloadQueueError> for CommandError {
+    fn from(err: DownloadQueueError) -> Self {
+        CommandError {
             message: err.to_string(),
-        })
+        }
+    }
+}
+
+/// Queue a file or archive download, it runs in the background.
+#[tauri::command]
+async fn queue_download(
+    file_access: FileAccess,
+    to_dest: PathBuf,
+    restore_metadata: Option<bool>,
+    download_queue: State<'_, DownloadQueue>,
+) -> Result<QueuedDownload, CommandError> {
+    Ok(download_queue
+        .push(file_access, to_dest, restore_metadata.unwrap_or(false))
+        .await?)
+}
+
+#[tauri::command]
+async fn list_downloads(
+    download_queue: State<'_, DownloadQueue>,
+) -> Result<Vec<QueuedDownload>, ()> {
+    Ok(download_queue.list().await)
+}
+
+#[tauri::command]
+async fn pause_download(
+    id: String,
+    download_queue: State<'_, DownloadQueue>,
+) -> Result<QueuedDownload, CommandError> {
+    Ok(download_queue.pause(&id).await?)
+}
+
+#[tauri::command]
+async fn resume_download(
+    id: String,
+    download_queue: State<'_, DownloadQueue>,
+) -> Result<QueuedDownload, CommandError> {
+    Ok(download_queue.resume(&id).await?)
+}
+
+#[tauri::command]
+async fn retry_download(
+    id: String,
+    download_queue: State<'_, DownloadQueue>,
+) -> Result<QueuedDownload, CommandError> {
+    Ok(download_queue.retry(&id).await?)
+}
+
+#[tauri::command]
+async fn remove_download(
+    id: String,
+    download_queue: State<'_, DownloadQueue>,
+) -> Result<(), CommandError> {
+    Ok(download_queue.remove(&id).await?)
 }
 
 #[tauri::command]
 async fn get_single_file_data(
-    vault_key_signature: String,
+    vault_handle: String,
     file_path: String,
+    state: State<'_, AppState>,
+    vault_registry: State<'_, VaultRegistry>,
     shared_client: State<'_, SharedClient>,
 ) -> Result<FileFromVault, ()> {
-    ant::files::get_single_file_data(&vault_key_signature, &file_path, shared_client)
+    let secret_key = resolve_vault_key(&state, &vault_registry, &vault_handle)
+        .await
+        .map_err(|_err| ())?;
+
+    ant::files::get_single_file_data(&secret_key, &file_path, shared_client)
         .await
         .map_err(|_err| ()) // TODO: Map to serializable error
 }
 
+#[tauri::command]
+async fn list_vault_identities(
+    state: State<'_, AppState>,
+    vault_registry: State<'_, VaultRegistry>,
+) -> Result<Vec<VaultIdentityInfo>, CommandError> {
+    let (identities, active_id) = {
+        let state = state.lock().await;
+        (
+            state.app_data.vault_identities(),
+            state.app_data.active_vault_id(),
+        )
+    };
+
+    let mut infos = Vec::with_capacity(identities.len());
+    for identity in &identities {
+        infos.push(VaultIdentityInfo {
+            is_active: identity.id == active_id,
+            is_unlocked: vault_registry.is_unlocked(&identities, &identity.id).await,
+            identity: identity.clone(),
+        });
+    }
+
+    Ok(infos)
+}
+
+#[tauri::command]
+async fn create_vault_identity(
+    name: String,
+    key_source: VaultKeySource,
+    state: State<'_, AppState>,
+) -> Result<VaultIdentity, CommandError> {
+    let mut state = state.lock().await;
+    let mut identities = state.app_data.vault_identities();
+
+    ant::vault_registry::validate_key_source(&identities, &key_source)?;
+
+    let identity = VaultIdentity::new(name, key_source);
+    info!(
+        "creating vault identity: {} ({})",
+        identity.name, identity.id
+    );
+
+    identities.push(identity.clone());
+    state.app_data.vaults = Some(identities);
+    state.app_data.store().map_err(|err| CommandError {
+        message: err.to_string(),
+    })?;
+
+    Ok(identity)
+}
+
+#[tauri::command]
+async fn rename_vault_identity(
+    vault_id: String,
+    name: String,
+    state: State<'_, AppState>,
+) -> Result<(), CommandError> {
+    let mut state = state.lock().await;
+    let mut identities = state.app_data.vault_identities();
+
+    let identity = identities
+        .iter_mut()
+        .find(|identity| identity.id == vault_id)
+        .ok_or_else(|| VaultRegistryError::UnknownIdentity(vault_id.clone()))?;
+    identity.name = name;
+
+    state.app_data.vaults = Some(identities);
+    state.app_data.store().map_err(|err| CommandError {
+        message: err.to_string(),
+    })
+}
+
+#[tauri::command]
+async fn remove_vault_identity(
+    vault_id: String,
+    state: State<'_, AppState>,
+    vault_registry: State<'_, VaultRegistry>,
+) -> Result<(), CommandError> {
+    let mut state = state.lock().await;
+    let mut identities = state.app_data.vault_identities();
+
+    if !identities.iter().any(|identity| identity.id == vault_id) {
+        return Err(VaultRegistryError::UnknownIdentity(vault_id).into());
+    }
+
+    let is_parent = identities.iter().any(|identity| {
+        matches!(&identity.key_source, VaultKeySource::Derived { parent_id, .. } if *parent_id == vault_id)
+    });
+    if is_parent {
+        return Err(VaultRegistryError::InUse(vault_id).into());
+    }
+
+    // Only the identity is forgotten, the vault itself stays on the network
+    identities.retain(|identity| identity.id != vault_id);
+    vault_registry.lock(&vault_id).await;
+
+    if state.app_data.active_vault_id() == vault_id {
+        state.app_data.active_vault = identities.first().map(|identity| identity.id.clone());
+    }
+    state.app_data.vaults = Some(identities);
+    state.app_data.store().map_err(|err| CommandError {
+        message: err.to_string(),
+    })
+}
+
+#[tauri::command]
+async fn unlock_vault_identity(
+    app: AppHandle,
+    vault_id: String,
+    secret: String,
+    state: State<'_, AppState>,
+    vault_registry: State<'_, VaultRegistry>,
+) -> Result<String, CommandError> {
+    let identities = state.lock().await.app_data.vault_identities();
+
+    let identity = identities
+        .iter()
+        .find(|identity| identity.id == vault_id)
+        .ok_or_else(|| VaultRegistryError::UnknownIdentity(vault_id.clone()))?;
+
+    vault_registry.unlock(identity, &secret).await?;
+    tauri::async_runtime::spawn(process_offline_queue(app));
+
+    Ok(vault_registry.open_session(&identities, &vault_id).await?)
+}
+
+/// Open a session for an identity that is already unlocked, e.g. one derived from an unlocked
+/// parent.
+#[tauri::command]
+async fn open_vault_session(
+    vault_id: String,
+    state: State<'_, AppState>,
+    vault_registry: State<'_, VaultRegistry>,
+) -> Result<String, CommandError> {
+    let identities = state.lock().await.app_data.vault_identities();
+
+    Ok(vault_registry.open_session(&identities, &vault_id).await?)
+}
+
+#[tauri::command]
+async fn close_vault_session(
+    vault_handle: String,
+    vault_registry: State<'_, VaultRegistry>,
+) -> Result<(), ()> {
+    vault_registry.close_session(&vault_handle).await;
+    Ok(())
+}
+
+#[tauri::command]
+async fn lock_vault_identity(
+    vault_id: String,
+    vault_registry: State<'_, VaultRegistry>,
+) -> Result<(), CommandError> {
+    vault_registry.lock(&vault_id).await;
+    Ok(())
+}
+
+#[tauri::command]
+async fn set_active_vault_identity(
+    vault_id: String,
+    state: State<'_, AppState>,
+) -> Result<(), CommandError> {
+    let mut state = state.lock().await;
+
+    if !state
+        .app_data
+        .vault_identities()
+        .iter()
+        .any(|identity| identity.id == vault_id)
+    {
+        return Err(VaultRegistryError::UnknownIdentity(vault_id).into());
+    }
+
+    info!("switching active vault identity to: {vault_id}");
+    state.app_data.active_vault = Some(vault_id);
+    state.app_data.store().map_err(|err| CommandError {
+        message: err.to_string(),
+    })
+}
+
+#[tauri::command]
+async fn copy_vault_entries(
+    from_vault_id: String,
+    to_vault_id: String,
+    entries: Vec<VaultEntryRef>,
+    state: State<'_, AppState>,
+    vault_registry: State<'_, VaultRegistry>,
+    shared_client: State<'_, SharedClient>,
+) -> Result<(), CommandError> {
+    let identities = state.lock().await.app_data.vault_identities();
+
+    let from_key = vault_registry
+        .secret_key(&identities, &from_vault_id)
+        .await?;
+    let to_key = vault_registry.secret_key(&identities, &to_vault_id).await?;
+
+    ant::files::copy_vault_entries(&from_key, &to_key, &entries, shared_client)
+        .await
+        .map_err(|err| CommandError {
+            message: err.to_string(),
+        })
+}
+
 #[tauri::command]
 async fn confirm_payment(
     order_id: u64,
@@ -882,6 +2023,22 @@ async fn get_local_files() -> Result<LocalFileData, CommandError> {
     })
 }
 
+/// Replace the tags of a local upload.
+#[tauri::command]
+async fn set_local_tags(
+    file_access: FileAccess,
+    tags: Vec<String>,
+) -> Result<EntryDetails, CommandError> {
+    let index = ant::local_index::get_local_index().ok_or_else(|| CommandError {
+        message: "Local index is not available".to_string(),
+    })?;
+    index
+        .set_tags(&file_access, tags)
+        .map_err(|err| CommandError {
+            message: err.to_string(),
+        })
+}
+
 #[tauri::command]
 async fn load_local_private_archive(
     local_addr: String,
@@ -1022,38 +2179,11 @@ async fn get_local_private_file_access(
 
 #[tauri::command]
 async fn get_unique_download_path(downloads_path: String, filename: String) -> Result<String, ()> {
-    use std::path::Path;
-
-    let base_path = Path::new(&downloads_path);
-    let file_path = base_path.join(&filename);
+    let file_path = PathBuf::from(downloads_path).join(filename);
 
-    // If file doesn't exist, return original path
-    if !file_path.exists() {
-        return Ok(file_path.to_string_lossy().to_string());
-    }
-
-    // Extract name and extension
-    let stem = file_path
-        .file_stem()
-        .and_then(|s| s.to_str())
-        .unwrap_or("file");
-    let extension = file_path
-        .extension()
-        .and_then(|s| s.to_str())
-        .map(|s| format!(".{}", s))
-        .unwrap_or_default();
-
-    // Try numbered variants until we find one that doesn't exist
-    for i in 1..1000 {
-        let new_filename = format!("{} ({}){}", stem, i, extension);
-        let new_path = base_path.join(&new_filename);
-        if !new_path.exists() {
-            return Ok(new_path.to_string_lossy().to_string());
-        }
-    }
-
-    // Fallback if we can't find a unique name
-    Err(())
+    ant::files::unique_download_path(&file_path)
+        .map(|path| path.to_string_lossy().to_string())
+        .ok_or(())
 }
 
 #[tauri::command]
@@ -1073,6 +2203,18 @@ fn clear_payment_cache() -> Result<(), String> {
     Ok(())
 }
 
+#[tauri::command]
+fn clear_chunk_cache() -> Result<(), String> {
+    let cache = ant::chunk_cache::get_chunk_cache()
+        .ok_or_else(|| "Could not get app data directory".to_string())?;
+
+    cache
+        .clear_cache()
+        .map_err(|e| format!("Failed to clear chunk cache: {}", e))?;
+
+    Ok(())
+}
+
 #[tauri::command]
 fn get_logs_directory() -> Result<String, String> {
     use directories::ProjectDirs;
@@ -1101,19 +2243,197 @@ fn get_app_version() -> String {
     env!("CARGO_PKG_VERSION").to_string()
 }
 
+/// Connect with the network configuration from the settings, without restarting the app.
+#[tauri::command]
+async fn reconnect(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    shared_client: State<'_, SharedClient>,
+) -> Result<(), CommandError> {
+    let config = state.lock().await.app_data.network_config();
+
+    ant::health::set_status(&app, &shared_client, NetworkStatus::Connecting).await;
+    if let Err(err) = shared_client.reconnect(config).await {
+        ant::health::set_status(&app, &shared_client, NetworkStatus::Offline).await;
+        return Err(CommandError {
+            message: err.to_string(),
+        });
+    }
+
+    // Don't leave the status at connecting until the monitor's next check
+    let status = ant::health::check(&shared_client)
+        .await
+        .unwrap_or(NetworkStatus::Degraded(0));
+    ant::health::set_status(&app, &shared_client, status).await;
+
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_network_status(shared_client: State<'_, SharedClient>) -> Result<NetworkStatus, ()> {
+    Ok(shared_client.status().await)
+}
+
+#[tauri::command]
+fn get_log_level() -> Result<String, CommandError> {
+    logging::log_level().map_err(|err| CommandError {
+        message: err.to_string(),
+    })
+}
+
+/// Change the log filter while the app runs and remember it for the next start.
+#[tauri::command]
+async fn set_log_level(directives: String, state: State<'_, AppState>) -> Result<(), CommandError> {
+    logging::set_log_level(&directives).map_err(|err| CommandError {
+        message: err.to_string(),
+    })?;
+
+    let mut state = state.lock().await;
+    state.app_data.log_level = Some(directives);
+    state.app_data.store().map_err(|err| CommandError {
+        message: err.to_string(),
+    })
+}
+
+/// Bundle redacted logs, version, settings and network configuration into a zip file.
+///
+/// Without a `destination` the bundle is saved to the download folder. Returns the path of the
+/// written file.
+#[tauri::command]
+async fn export_diagnostics(
+    destination: Option<PathBuf>,
+    state: State<'_, AppState>,
+) -> Result<String, CommandError> {
+    let app_data = state.lock().await.app_data.clone();
+
+    let destination = match destination {
+        Some(destination) => destination,
+        None => diagnostics::default_destination(&app_data).map_err(|err| CommandError {
+            message: err.to_string(),
+        })?,
+    };
+
+    tauri::async_runtime::spawn_blocking({
+        let destination = destination.clone();
+        move || diagnostics::export_diagnostics(&app_data, &destination)
+    })
+    .await
+    .map_err(|err| CommandError {
+        message: err.to_string(),
+    })?
+    .map_err(|err| CommandError {
+        message: err.to_string(),
+    })?;
+
+    Ok(destination.display().to_string())
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub async fn run() {
-    tauri::Builder::default()
-        .manage(AppState::default())
-        .manage(SharedClient::default())
+    let app_state = AppStateInner::default();
+    ant::ignore_rules::set_global_patterns(app_state.app_data.ignore_patterns.clone());
+    ant::folder_scan::set_symlink_policy(app_state.app_data.symlink_policy);
+    ant::chunk_cache::set_max_size_mb(app_state.app_data.chunk_cache_max_mb);
+
+    let builder = tauri::Builder::default();
+
+    // Opening a link while the app runs starts a second instance on Windows and Linux, which
+    // hands its arguments over and exits. Has to be the first plugin.
+    #[cfg(desktop)]
+    let builder = builder.plugin(tauri_plugin_single_instance::init(|app, argv, _cwd| {
+        let urls = argv
+            .iter()
+            .skip(1)
+            .filter_map(|arg| tauri::Url::parse(arg).ok())
+            .filter(|url| url.scheme() == ant::share_link::SHARE_SCHEME)
+            .collect();
+        tauri::async_runtime::spawn(ant::share_link::receive(app.clone(), urls));
+
+        if let Some(window) = app.get_webview_window("main") {
+            let _ = window.unminimize();
+            let _ = window.set_focus();
+        }
+    }));
+
+    builder
+        .manage(SharedClient::new(app_state.app_data.network_config()))
+        .manage(Mutex::new(app_state))
         .manage(PaymentOrderManager::default())
         .manage(PendingUploadsState::default())
+        .manage(VaultRegistry::default())
+        .manage(OfflineQueue::load())
+        .manage(WatchedFolders::load())
+        .manage(PreviewCache::default())
+        .manage(DownloadQueue::load())
+        .manage(ReceivedShareLinks::default())
+        .manage(ShareRegistry::load())
+        .manage(ReceivedShares::load())
+        .register_asynchronous_uri_scheme_protocol(PREVIEW_SCHEME, |ctx, request, responder| {
+            let app = ctx.app_handle().clone();
+            tauri::async_runtime::spawn(async move {
+                responder.respond(ant::preview::handle_request(app, request).await);
+            });
+        })
+        .setup(|app| {
+            tauri::async_runtime::spawn(ant::health::monitor(app.handle().clone()));
+            tauri::async_runtime::spawn(ant::watched_folders::run(app.handle().clone()));
+            tauri::async_runtime::spawn(ant::download_queue::run(app.handle().clone()));
+
+            // Links the app was started with, and links opened while it runs
+            #[cfg(any(target_os = "linux", all(debug_assertions, windows)))]
+            {
+                if let Err(err) = app.deep_link().register_all() {
+                    error!("Failed to register the share link scheme: {err}");
+                }
+            }
+            if let Ok(Some(urls)) = app.deep_link().get_current() {
+                tauri::async_runtime::spawn(ant::share_link::receive(app.handle().clone(), urls));
+            }
+            // On Windows and Linux links opened later arrive through the single instance plugin
+            #[cfg(not(any(target_os = "linux", windows)))]
+            {
+                let app_handle = app.handle().clone();
+                app.deep_link().on_open_url(move |event| {
+                    tauri::async_runtime::spawn(ant::share_link::receive(
+                        app_handle.clone(),
+                        event.urls(),
+                    ));
+                });
+            }
+
+            let app_handle = app.handle().clone();
+            app.listen("upload-progress", move |event| {
+                tauri::async_runtime::spawn(ant::watched_folders::handle_upload_progress(
+                    app_handle.clone(),
+                    event.payload().to_string(),
+                ));
+                tauri::async_runtime::spawn(finish_queued_upload(
+                    app_handle.clone(),
+                    event.payload().to_string(),
+                ));
+            });
+
+            // Work through the offline queue whenever the network becomes reachable
+            let app_handle = app.handle().clone();
+            app.listen("network-status", move |event| {
+                if let Ok(NetworkStatus::Connected(_) | NetworkStatus::Degraded(1..)) =
+                    serde_json::from_str::<NetworkStatus>(event.payload())
+                {
+                    tauri::async_runtime::spawn(process_offline_queue(app_handle.clone()));
+                }
+            });
+
+            Ok(())
+        })
+        .plugin(tauri_plugin_deep_link::init())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_opener::init())
         .invoke_handler(tauri::generate_handler![
             start_upload,
             confirm_upload_payment,
+            start_archive_update,
+            get_archive_versions,
             send_payment_order_message,
             get_vault_structure,
             get_vault_structure_streaming,
@@ -1121,16 +2441,39 @@ pub async fn run() {
             remove_from_vault,
             download_private_file,
             download_public_file,
+            download_archive_entries,
+            queue_download,
+            list_downloads,
+            pause_download,
+            resume_download,
+            retry_download,
+            remove_download,
             get_single_file_data,
             confirm_payment,
             get_unique_download_path,
             get_local_files,
+            set_local_tags,
             get_local_structure_streaming,
             load_local_private_archive,
             load_local_public_archive,
             add_local_archive_to_vault,
             add_local_file_to_vault,
             add_to_vault_with_analysis,
+            preview_reference,
+            import_reference,
+            create_share_link,
+            parse_share_link,
+            open_share_link,
+            take_share_links,
+            get_sharing_public_key,
+            start_share,
+            update_share,
+            list_shares,
+            revoke_share,
+            receive_share,
+            list_received_shares,
+            open_received_share,
+            remove_received_share,
             delete_local_public_file,
             delete_local_private_file,
             delete_local_public_archive,
@@ -1139,10 +2482,43 @@ pub async fn run() {
             app_data,
             app_data_store,
             clear_payment_cache,
+            clear_chunk_cache,
             show_item_in_file_manager,
             get_logs_directory,
             get_app_version,
+            export_diagnostics,
+            reconnect,
+            get_network_status,
+            list_queued_operations,
+            remove_queued_operation,
+            retry_queued_operation,
+            list_watched_folders,
+            add_watched_folder,
+            update_watched_folder,
+            remove_watched_folder,
+            backup_watched_folder,
+            get_log_level,
+            set_log_level,
+            list_vault_identities,
+            create_vault_identity,
+            rename_vault_identity,
+            remove_vault_identity,
+            unlock_vault_identity,
+            lock_vault_identity,
+            unlock_vault,
+            lock_vault,
+            open_vault_session,
+            close_vault_session,
+            set_active_vault_identity,
+            copy_vault_entries,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            if let tauri::RunEvent::Exit = event {
+                // Wipe vault keys from memory before the process goes away
+                let vault_registry = app.state::<VaultRegistry>();
+                tauri::async_runtime::block_on(vault_registry.lock_all());
+            }
+        });
 }
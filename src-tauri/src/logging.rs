@@ -1,10 +1,68 @@
-use std::path::PathBuf;
-use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime};
+use thiserror::Error as ThisError;
+use tracing::Subscriber;
+use tracing::{info, warn};
+use tracing_subscriber::filter::ParseError;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::{
+    fmt, layer::SubscriberExt, reload, util::SubscriberInitExt, EnvFilter, Registry,
+};
 use chrono::Local;
 
+use crate::ant::app_data::AppData;
+
+mod redact;
+
+pub use redact::redact;
+use redact::{RedactingFields, RedactingMakeWriter};
+
+/// Name of the log file inside every log directory.
+pub const LOG_FILE_NAME: &str = "dave.log";
+
+const DEFAULT_LOG_RETENTION_DAYS: u64 = 14;
+const DEFAULT_LOG_RETENTION_MAX_MB: u64 = 500;
+
+const DEFAULT_LOG_LEVEL: &str = "info";
+
+/// Handle to swap the filter of the running subscriber.
+static FILTER_HANDLE: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+
+#[derive(ThisError, Debug)]
+pub enum LogLevelError {
+    #[error("Invalid log level: {0}")]
+    Invalid(#[from] ParseError),
+    #[error("Logging has not been set up")]
+    NotInitialized,
+    #[error("Could not change log level: {0}")]
+    Reload(#[from] reload::Error),
+}
+
+/// Current filter directives, e.g. `info,autonomi=debug`.
+pub fn log_level() -> Result<String, LogLevelError> {
+    let handle = FILTER_HANDLE.get().ok_or(LogLevelError::NotInitialized)?;
+    Ok(handle.with_current(|filter| filter.to_string())?)
+}
+
+/// Replace the filter directives of the running subscriber, e.g. with
+/// `info,autonomi=debug,project_dave_lib::ant::files=trace`.
+pub fn set_log_level(directives: &str) -> Result<(), LogLevelError> {
+    let filter = EnvFilter::try_new(directives)?;
+    let handle = FILTER_HANDLE.get().ok_or(LogLevelError::NotInitialized)?;
+    handle.reload(filter)?;
+
+    info!("Log level set to: {directives}");
+    Ok(())
+}
+
 pub fn setup_logging() {
+    let app_data = AppData::load().unwrap_or_default();
+
     // Get the unique log directory for this run
     let log_dir = get_unique_log_dir();
+    let current_log_dir = log_dir.clone();
 
     // Create the unique log directory if it doesn't exist
     if let Some(ref dir) = log_dir {
@@ -14,7 +72,7 @@ pub fn setup_logging() {
     // Set up file appender if we have a valid log directory
     let file_layer = log_dir.and_then(|dir| {
         // Create a non-rolling file writer - just write to dave.log in the unique directory
-        let log_file_path = dir.join("dave.log");
+        let log_file_path = dir.join(LOG_FILE_NAME);
         let file = std::fs::OpenOptions::new()
             .create(true)
             .append(true)
@@ -27,15 +85,26 @@ pub fn setup_logging() {
         Box::leak(Box::new(_guard));
 
         Some(
-            fmt::layer().with_writer(non_blocking).with_ansi(false), // No ANSI colors in log files
+            fmt::layer()
+                .fmt_fields(RedactingFields::default())
+                .with_writer(RedactingMakeWriter::new(non_blocking))
+                .with_ansi(false), // No ANSI colors in log files
         )
     });
 
     // Set up console layer
-    let console_layer = fmt::layer().with_writer(std::io::stdout).with_ansi(true); // Enable ANSI colors for console
+    let console_layer = console_layer(std::io::stdout);
 
-    // Set up the subscriber with environment filter
-    let env_filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    // Set up the subscriber with a reloadable filter: `RUST_LOG` wins over the saved log level
+    let env_filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| {
+        app_data
+            .log_level
+            .as_deref()
+            .and_then(|directives| EnvFilter::try_new(directives).ok())
+            .unwrap_or_else(|| EnvFilter::new(DEFAULT_LOG_LEVEL))
+    });
+    let (env_filter, filter_handle) = reload::Layer::new(env_filter);
+    let _ = FILTER_HANDLE.set(filter_handle);
 
     let subscriber = tracing_subscriber::registry()
         .with(env_filter)
@@ -47,17 +116,120 @@ pub fn setup_logging() {
     } else {
         subscriber.init();
     }
+
+    if let (Some(logs_dir), Some(current_log_dir)) = (logs_dir(), current_log_dir) {
+        let max_age = Duration::from_secs(
+            app_data
+                .log_retention_days
+                .unwrap_or(DEFAULT_LOG_RETENTION_DAYS)
+                * 24
+                * 60
+                * 60,
+        );
+        let max_total_size = app_data
+            .log_retention_max_mb
+            .unwrap_or(DEFAULT_LOG_RETENTION_MAX_MB)
+            * 1024
+            * 1024;
+
+        apply_log_retention(&logs_dir, &current_log_dir, max_age, max_total_size);
+    }
 }
 
-fn get_unique_log_dir() -> Option<PathBuf> {
-    let qualifier = "com";
-    let organization = "autonomi";
-    let application = "dave";
+/// Coloured console output. Fields are redacted before they are coloured, the escape codes would
+/// otherwise keep secret-key fields from being recognised.
+fn console_layer<S, W>(
+    make_writer: W,
+) -> fmt::Layer<S, RedactingFields, fmt::format::Format, RedactingMakeWriter<W>>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'writer> MakeWriter<'writer> + 'static,
+{
+    fmt::layer()
+        .fmt_fields(RedactingFields::default())
+        .with_writer(RedactingMakeWriter::new(make_writer))
+        .with_ansi(true) // Enable ANSI colors for console
+}
 
-    directories::ProjectDirs::from(qualifier, organization, application).map(|proj_dirs| {
-        let mut log_dir = proj_dirs.data_dir().to_owned();
-        log_dir.push("logs");
-        
+/// Directory holding the `log_<timestamp>` directories of all runs.
+pub fn logs_dir() -> Option<PathBuf> {
+    directories::ProjectDirs::from("com", "autonomi", "dave")
+        .map(|proj_dirs| proj_dirs.data_dir().join("logs"))
+}
+
+/// Log directories of previous and current runs, newest first.
+pub fn log_dirs(logs_dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(logs_dir) else {
+        return vec![];
+    };
+
+    let mut dirs: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.is_dir()
+                && path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.starts_with("log_"))
+        })
+        .collect();
+
+    // The timestamp format sorts chronologically
+    dirs.sort();
+    dirs.reverse();
+    dirs
+}
+
+/// Delete log directories that are older than `max_age`, or that push the total size of all logs
+/// over `max_total_size`. The directory of the current run is always kept.
+fn apply_log_retention(
+    logs_dir: &Path,
+    current_log_dir: &Path,
+    max_age: Duration,
+    max_total_size: u64,
+) {
+    let now = SystemTime::now();
+    let mut total_size = 0;
+
+    for dir in log_dirs(logs_dir) {
+        let size = dir_size(&dir);
+        total_size += size;
+
+        if dir == current_log_dir {
+            continue;
+        }
+
+        let age = std::fs::metadata(&dir)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| now.duration_since(modified).ok())
+            .unwrap_or_default();
+
+        if age > max_age || total_size > max_total_size {
+            match std::fs::remove_dir_all(&dir) {
+                Ok(()) => {
+                    info!("Removed old log directory: {}", dir.display());
+                    total_size -= size;
+                }
+                Err(err) => warn!("Failed to remove log directory {}: {err}", dir.display()),
+            }
+        }
+    }
+}
+
+fn dir_size(dir: &Path) -> u64 {
+    walkdir::WalkDir::new(dir)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.metadata().ok())
+        .filter(|metadata| metadata.is_file())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+fn get_unique_log_dir() -> Option<PathBuf> {
+    logs_dir().map(|mut log_dir| {
         // Create a unique folder name with timestamp
         let timestamp = Local::now().format("%Y-%m-%d_%H-%M-%S").to_string();
         log_dir.push(format!("log_{}", timestamp));
@@ -65,3 +237,47 @@ fn get_unique_log_dir() -> Option<PathBuf> {
         log_dir
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use autonomi::client::vault::VaultSecretKey;
+    use std::io::{self, Write};
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn console_layer_redacts_secret_key_fields() {
+        let buffer = SharedBuffer::default();
+        let make_writer = {
+            let buffer = buffer.clone();
+            move || buffer.clone()
+        };
+        let subscriber = tracing_subscriber::registry().with(console_layer(make_writer));
+
+        let secret_hex = VaultSecretKey::random().to_hex();
+        tracing::subscriber::with_default(subscriber, || {
+            info!(secret_key = %secret_hex, upload_id = 42, "Starting upload");
+            info!(vault_secret_key = ?Some(&secret_hex), "Resolving vault");
+        });
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        assert!(output.contains('\x1b'), "console output is coloured");
+        assert!(output.contains("Starting upload"));
+        assert!(output.contains("upload_id=42"));
+        assert!(!output.contains(&secret_hex), "leaked in: {output}");
+    }
+}
@@ -0,0 +1,254 @@
+//! Masking of secrets in log output.
+//!
+//! Fields are redacted as they are formatted, before a layer adds ANSI colours that would split
+//! field names from their values, and whole log lines are redacted again before they reach a
+//! sink. Secrets are masked no matter which call site or `Debug` impl produced them. Masked are:
+//! - hex strings longer than an address, e.g. datamaps and vault key signatures,
+//! - byte strings and long byte lists, as produced by the `Debug` output of datamap chunks,
+//! - the values of secret-key shaped fields, e.g. `secret_key: ...` or `signature=...`.
+
+use regex::Regex;
+use std::fmt;
+use std::io::{self, Write};
+use std::sync::OnceLock;
+use tracing_subscriber::field::RecordFields;
+use tracing_subscriber::fmt::format::{DefaultFields, Writer};
+use tracing_subscriber::fmt::{FormatFields, MakeWriter};
+
+/// Addresses (XorNames) are 32 bytes and are fine to log, anything longer is masked.
+const MAX_PLAIN_HEX_LEN: usize = 64;
+
+/// Byte lists of up to the size of an address are fine to log.
+const MAX_PLAIN_BYTE_LIST_LEN: usize = 32;
+
+struct Patterns {
+    secret_field: Regex,
+    byte_string: Regex,
+    byte_list: Regex,
+    long_hex: Regex,
+}
+
+fn patterns() -> &'static Patterns {
+    static PATTERNS: OnceLock<Patterns> = OnceLock::new();
+
+    PATTERNS.get_or_init(|| Patterns {
+        secret_field: Regex::new(
+            r#"(?i)\b((?:vault_)?(?:secret_key|key_signature|private_key|secret|signature))(["']?\s*[:=]\s*)(?:"[^"]*"|Some\([^)]*\)|[^\s,;})\]]+)"#,
+        )
+        .expect("valid secret field pattern"),
+        byte_string: Regex::new(r#"b"(?:[^"\\]|\\.)*""#).expect("valid byte string pattern"),
+        byte_list: Regex::new(&format!(
+            r"\[\s*\d{{1,3}}(?:\s*,\s*\d{{1,3}}){{{MAX_PLAIN_BYTE_LIST_LEN},}}\s*,?\s*\]"
+        ))
+        .expect("valid byte list pattern"),
+        long_hex: Regex::new(&format!(
+            r"(?:0x)?[0-9a-fA-F]{{{},}}",
+            MAX_PLAIN_HEX_LEN + 1
+        ))
+        .expect("valid hex pattern"),
+    })
+}
+
+/// Mask secrets in a formatted log line.
+pub fn redact(line: &str) -> String {
+    let patterns = patterns();
+
+    let line = patterns.secret_field.replace_all(line, "$1$2<redacted>");
+    let line = patterns.byte_string.replace_all(&line, r#"b"<redacted>""#);
+    let line = patterns.byte_list.replace_all(&line, "[<redacted>]");
+    let line = patterns
+        .long_hex
+        .replace_all(&line, |caps: &regex::Captures| {
+            format!(
+                "<redacted {} hex chars>",
+                caps[0].trim_start_matches("0x").len()
+            )
+        });
+
+    line.into_owned()
+}
+
+/// [`FormatFields`] that formats fields without colours and redacts them before they are written.
+#[derive(Default)]
+pub struct RedactingFields {
+    inner: DefaultFields,
+}
+
+impl<'writer> FormatFields<'writer> for RedactingFields {
+    fn format_fields<R: RecordFields>(
+        &self,
+        mut writer: Writer<'writer>,
+        fields: R,
+    ) -> fmt::Result {
+        let mut plain = String::new();
+        self.inner.format_fields(Writer::new(&mut plain), fields)?;
+        writer.write_str(&redact(&plain))
+    }
+}
+
+/// [`MakeWriter`] that redacts every log line before handing it to the wrapped writer.
+pub struct RedactingMakeWriter<M> {
+    inner: M,
+}
+
+impl<M> RedactingMakeWriter<M> {
+    pub fn new(inner: M) -> Self {
+        Self { inner }
+    }
+}
+
+impl<'a, M: MakeWriter<'a>> MakeWriter<'a> for RedactingMakeWriter<M> {
+    type Writer = RedactingWriter<M::Writer>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        RedactingWriter {
+            inner: self.inner.make_writer(),
+        }
+    }
+}
+
+pub struct RedactingWriter<W> {
+    inner: W,
+}
+
+impl<W: Write> Write for RedactingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // The fmt layer writes each event in one go, so a buffer always holds complete lines
+        let redacted = redact(&String::from_utf8_lossy(buf));
+        self.inner.write_all(redacted.as_bytes())?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ant::files::{FileAccess, FileFromVault};
+    use crate::ant::vault_registry::SessionKey;
+    use autonomi::chunk::{Chunk, DataMapChunk};
+    use autonomi::client::data::DataAddress;
+    use autonomi::client::vault::VaultSecretKey;
+    use autonomi::files::Metadata;
+    use autonomi::{Bytes, XorName};
+
+    fn private_access() -> (FileAccess, DataMapChunk) {
+        let content: Vec<u8> = (0..=255u8).cycle().take(300).collect();
+        let data_map = DataMapChunk::from(Chunk::new(Bytes::from(content)));
+        (FileAccess::Private(data_map.clone()), data_map)
+    }
+
+    fn assert_no_datamap(output: &str, data_map: &DataMapChunk) {
+        assert!(!output.contains(&data_map.to_hex()));
+        assert!(!output.contains(&format!("{:?}", data_map.0.value)));
+        assert!(!output.contains(&format!("{:?}", data_map.0.value.to_vec())));
+    }
+
+    #[test]
+    fn redacts_private_file_access() {
+        let (access, data_map) = private_access();
+
+        let output = redact(&format!("adding file to vault: {access:?}"));
+
+        assert!(output.starts_with("adding file to vault: Private"));
+        assert_no_datamap(&output, &data_map);
+    }
+
+    #[test]
+    fn redacts_private_file_from_vault() {
+        let (access, data_map) = private_access();
+        let file = FileFromVault::new(
+            "docs/notes.txt".to_string(),
+            Metadata::new_with_size(300),
+            access,
+        );
+
+        let output = redact(&format!("{file:?}"));
+
+        assert!(output.contains("docs/notes.txt"));
+        assert_no_datamap(&output, &data_map);
+    }
+
+    #[test]
+    fn redacts_datamap_hex() {
+        let (_, data_map) = private_access();
+
+        let output = redact(&format!("local private file: 0x{}", data_map.to_hex()));
+
+        assert_no_datamap(&output, &data_map);
+        assert!(output.starts_with("local private file: <redacted "));
+    }
+
+    #[test]
+    fn keeps_public_addresses() {
+        let address = DataAddress::new(XorName::from_content(b"public file"));
+        let line = format!(
+            "public file at {}: {:?}",
+            address.to_hex(),
+            FileAccess::Public(address)
+        );
+
+        assert_eq!(redact(&line), line);
+    }
+
+    #[test]
+    fn redacts_vault_key_signature() {
+        let signature = "ab".repeat(64);
+
+        let output = redact(&format!("vault_key_signature: {signature}"));
+        assert_eq!(output, "vault_key_signature: <redacted>");
+
+        let output = redact(&format!("signing with 0x{signature} now"));
+        assert!(!output.contains(&signature));
+        assert!(output.ends_with(" now"));
+    }
+
+    #[test]
+    fn redacts_secret_key_fields() {
+        let secret_key = VaultSecretKey::random();
+        let secret_hex = secret_key.to_hex();
+
+        for line in [
+            format!("secret_key={secret_hex} upload_id=42"),
+            format!("vault_secret_key: Some({secret_hex}), add_to_vault: true"),
+            format!(r#"{{"secret": "{secret_hex}"}}"#),
+        ] {
+            let output = redact(&line);
+            assert!(!output.contains(&secret_hex), "leaked in: {output}");
+            assert!(output.contains("<redacted>"));
+        }
+    }
+
+    #[test]
+    fn session_key_debug_is_redacted() {
+        let secret_key = VaultSecretKey::random();
+        let session_key = SessionKey::from(&secret_key);
+
+        let output = format!("{session_key:?}");
+        assert!(!output.contains(&secret_key.to_hex()));
+        assert_eq!(redact(&output), output);
+    }
+
+    #[test]
+    fn keeps_ordinary_lines() {
+        let line = "Uploaded 3 files (1024 bytes) to archive \"photos\" in 2.5s";
+        assert_eq!(redact(line), line);
+    }
+
+    #[test]
+    fn writer_redacts_output() {
+        let (access, data_map) = private_access();
+        let mut writer = RedactingWriter { inner: Vec::new() };
+
+        writer
+            .write_all(format!("{access:?}\n").as_bytes())
+            .unwrap();
+
+        let output = String::from_utf8(writer.inner).unwrap();
+        assert!(output.ends_with('\n'));
+        assert_no_datamap(&output, &data_map);
+    }
+}
@@ -315,8 +315,8 @@ export const useFileStore = defineStore("files", () => {
         pendingVaultStructure.value = true;
 
         try {
-            // Get vault key signature
-            let vaultKeySignature = await walletStore.getVaultKeySignature();
+            // Get vault session handle
+            let vaultHandle = await walletStore.getVaultHandle();
 
             // Initialize vault structure 
             vaultStructure.value = {
@@ -326,7 +326,7 @@ export const useFileStore = defineStore("files", () => {
             };
 
             // Start streaming vault structure updates with temp code
-            await invoke("get_vault_structure_streaming", {vaultKeySignature, tempCode});
+            await invoke("get_vault_structure_streaming", {vaultHandle, tempCode});
 
         } catch (error: any) {
             console.log(">>> ERROR: Failed to get vault structure:", error);
@@ -406,6 +406,14 @@ export const useFileStore = defineStore("files", () => {
                     // Remove from loading list
                     removeLoadingArchive(update.archive.address, update.archive.is_private);
 
+                    // A failed archive may load later on a background retry
+                    const isRetried = (a: IFailedArchive) => {
+                        const aAddress = 'Private' in a.archive_access ? a.archive_access.Private : a.archive_access.Public;
+                        return aAddress === update.archive.address;
+                    };
+                    failedArchives.value = failedArchives.value.filter(a => !isRetried(a));
+                    vaultStructure.value.failed_archives = vaultStructure.value.failed_archives.filter(a => !isRetried(a));
+
                     // Add the archive
                     const archive = {
                         name: update.archive.name,
@@ -493,12 +501,12 @@ export const useFileStore = defineStore("files", () => {
                 };
             }
 
-            // Get vault key signature
-            let vaultKeySignature = await walletStore.getVaultKeySignature();
+            // Get vault session handle
+            let vaultHandle = await walletStore.getVaultHandle();
 
             // Load the file data
             const loadedFile = await invoke("get_single_file_data", {
-                vaultKeySignature,
+                vaultHandle,
                 filePath: file.path
             }) as any;
 
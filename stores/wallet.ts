@@ -1,4 +1,5 @@
 import {useAppKit, useAppKitAccount, useDisconnect} from "@reown/appkit/vue";
+import {invoke} from "@tauri-apps/api/core";
 import {
     getBalance,
     getChainId,
@@ -154,6 +155,7 @@ export const useWalletStore = defineStore("wallet", () => {
     const callbackConnectWallet = ref<Function | null>(null);
     const callbackDisconnectWallet = ref<Function | null>(null);
     const cachedVaultKeySignature = ref<string>();
+    const cachedVaultHandle = ref<string>();
     const ethBalance = ref<string>('0');
     const antBalance = ref<string>('0');
     const balancesLoading = ref(false);
@@ -197,8 +199,10 @@ export const useWalletStore = defineStore("wallet", () => {
 
             await disconnect();
 
-            // Clear cached vault key signature on disconnect
+            // Clear cached vault key signature on disconnect and wipe the keys in the backend
             cachedVaultKeySignature.value = undefined;
+            cachedVaultHandle.value = undefined;
+            await invoke("lock_vault");
 
             console.log("Disconnected wallet");
 
@@ -794,10 +798,6 @@ export const useWalletStore = defineStore("wallet", () => {
         const config = useRuntimeConfig();
         const devVaultSignature = config.public.devVaultSignature;
 
-        console.log("DEBUG: Runtime config devVaultSignature:", devVaultSignature);
-        console.log("DEBUG: devVaultSignature type:", typeof devVaultSignature);
-        console.log("DEBUG: devVaultSignature length:", devVaultSignature?.length);
-
         if (devVaultSignature) {
             console.log("Using development vault key signature from ENV");
             return devVaultSignature;
@@ -815,11 +815,19 @@ export const useWalletStore = defineStore("wallet", () => {
             }
         }
 
-        console.log("Cached vault key signature:", cachedVaultKeySignature.value);
-
         return cachedVaultKeySignature.value;
     }
 
+    // Session handle of the active vault, the key itself stays in the backend
+    const getVaultHandle = async (): Promise<string> => {
+        if (!cachedVaultHandle.value) {
+            const vaultKeySignature = await getVaultKeySignature();
+            cachedVaultHandle.value = await invoke<string>("unlock_vault", {vaultKeySignature});
+        }
+
+        return cachedVaultHandle.value;
+    }
+
     const sign = async (hex: `0x${string}`): Promise<string> => {
         console.log("Signing message:", hex);
 
@@ -1420,6 +1428,7 @@ export const useWalletStore = defineStore("wallet", () => {
         payForQuotes,
         approveTokens,
         getVaultKeySignature,
+        getVaultHandle,
         sign,
         hasVaultSignature,
         fetchEthBalance,